@@ -3,6 +3,7 @@ import { check, group } from 'k6';
 import { Trend } from 'k6/metrics';
 
 const BASE_URL = 'http://localhost:3000';
+const USERNAME = 'admin';
 const PASSWORD = 'heslo';
 
 // --- Configuration: Define START and END HTTP REQUESTS per second ---
@@ -156,7 +157,11 @@ function findId(response, field, value) {
 }
 
 export function setup() {
-  const res = http.get(`${BASE_URL}/auth?password=${PASSWORD}`);
+  const res = http.post(
+    `${BASE_URL}/auth`,
+    JSON.stringify({ username: USERNAME, password: PASSWORD }),
+    { headers: { 'Content-Type': 'application/json' } },
+  );
   if (res.status !== 200) {
       throw new Error(`Auth failed in setup: ${res.status} ${res.body}`);
   }
@@ -1,8 +1,250 @@
-use anyhow::Result;
-use deadpool_postgres::Pool;
+use deadpool_postgres::{Pool, Transaction};
 use rust_decimal::Decimal;
 
+use crate::error::{AppError, AppResult as Result};
+use crate::identifiers::birth_details;
+use crate::listing::{ColumnType, InsertRow, ListQuery, Listable, Page, Patch, Table};
 use crate::models::*;
+use crate::validation::Validate;
+use crate::versioning::{IfMatch, VERSION_COLUMN};
+use std::pin::pin;
+use tokio_postgres::Row;
+use tokio_postgres::binary_copy::BinaryCopyInWriter;
+use tokio_postgres::types::{ToSql, Type};
+
+/// Runs a validated list query against `T`'s table. `total` counts every row
+/// matching the filters, regardless of paging.
+pub async fn list_rows<T: Listable>(pool: Pool, query: &ListQuery) -> Result<Page<T>> {
+    let client = pool.get().await?;
+
+    let (count_sql, count_params) = query.count_sql(&T::TABLE);
+    let total: i64 = client.query_one(&count_sql, &count_params).await?.get(0);
+
+    let (select_sql, params) = query.select_sql(&T::TABLE);
+    let rows = client.query(&select_sql, &params).await?;
+
+    // A full page in key order may have a successor; hand out its key as the cursor
+    let next_cursor = match rows.last() {
+        Some(last) if query.uses_key_order() && rows.len() as i64 == query.limit => Some(
+            T::TABLE
+                .key
+                .iter()
+                .map(|k| last.get::<_, i32>(*k).to_string())
+                .collect::<Vec<_>>()
+                .join(","),
+        ),
+        _ => None,
+    };
+
+    Ok(Page {
+        items: rows.iter().map(T::from_row).collect(),
+        total,
+        next_cursor,
+    })
+}
+
+fn key_params(key: &[i32]) -> Vec<&(dyn ToSql + Sync)> {
+    key.iter().map(|k| k as &(dyn ToSql + Sync)).collect()
+}
+
+/// Fetches a single row of `T`'s table by its primary key, given in `TABLE.key` order.
+pub async fn get_by_key<T: Listable>(pool: Pool, key: &[i32]) -> Result<Option<T>> {
+    Ok(get_versioned(pool, key).await?.map(|(item, _)| item))
+}
+
+/// Like `get_by_key`, also returning the row version for `ETag`.
+pub async fn get_versioned<T: Listable>(pool: Pool, key: &[i32]) -> Result<Option<(T, String)>> {
+    let client = pool.get().await?;
+    let sql = format!(
+        "SELECT {} FROM {} WHERE {}",
+        T::TABLE.versioned_select_list(),
+        T::TABLE.name,
+        T::TABLE.key_condition(1)
+    );
+    let row = client.query_opt(&sql, &key_params(key)).await?;
+    Ok(row.map(|row| (T::from_row(&row), T::TABLE.version(&row))))
+}
+
+/// Like `get_by_key`, but within `tx`, so it sees the transaction's own writes.
+pub async fn get_by_key_in<T: Listable>(tx: &Transaction<'_>, key: &[i32]) -> Result<Option<T>> {
+    let sql = format!(
+        "SELECT {} FROM {} WHERE {}",
+        T::TABLE.select_list(),
+        T::TABLE.name,
+        T::TABLE.key_condition(1)
+    );
+    let row = tx.query_opt(&sql, &key_params(key)).await?;
+    Ok(row.map(|row| T::from_row(&row)))
+}
+
+/// The version of a row as `tx` sees it, e.g. right after writing it.
+pub(crate) async fn version_in<T: Listable>(
+    tx: &Transaction<'_>,
+    key: &[i32],
+) -> Result<Option<String>> {
+    let sql = format!(
+        "SELECT {} FROM {} WHERE {}",
+        VERSION_COLUMN,
+        T::TABLE.name,
+        T::TABLE.key_condition(1)
+    );
+    let row = tx.query_opt(&sql, &key_params(key)).await?;
+    Ok(row.map(|row| row.get(0)))
+}
+
+/// Locks the row with the given key for the rest of `tx` and checks it against
+/// `If-Match`. A missing row passes, leaving the write to report it as not found.
+pub(crate) async fn check_version<T: Listable>(
+    tx: &Transaction<'_>,
+    key: &[i32],
+    if_match: &IfMatch,
+) -> Result<()> {
+    if if_match.is_absent() {
+        return Ok(());
+    }
+    let sql = format!(
+        "SELECT {} FROM {} WHERE {} FOR UPDATE",
+        VERSION_COLUMN,
+        T::TABLE.name,
+        T::TABLE.key_condition(1)
+    );
+    match tx.query_opt(&sql, &key_params(key)).await? {
+        Some(row) if !if_match.matches(row.get(0)) => Err(AppError::PreconditionFailed(
+            "The item was changed since it was read".to_string(),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Opens a transaction whose writes the audit triggers attribute to `actor`.
+/// The setting is transaction-local, so it cannot leak to the next request
+/// served by the same pooled connection. The entity write functions below take
+/// the transaction rather than the pool, so several writes can share one.
+pub(crate) async fn begin_audited<'a>(
+    client: &'a mut deadpool_postgres::Client,
+    actor: &User,
+) -> Result<Transaction<'a>> {
+    let tx = client.transaction().await?;
+    tx.execute(
+        "SELECT set_config('katastr.user_id', $1, true), set_config('katastr.username', $2, true)",
+        &[&actor.id.to_string(), &actor.username],
+    )
+    .await?;
+    Ok(tx)
+}
+
+/// Applies a partial update to the row with the given key, returning the row
+/// as stored afterwards with its new version, or `None` if it does not exist.
+pub async fn patch_by_key<T: Listable + Validate>(
+    pool: Pool,
+    actor: &User,
+    key: &[i32],
+    patch: &Patch,
+    if_match: &IfMatch,
+) -> Result<Option<(T, String)>> {
+    if patch.is_empty() {
+        return match get_versioned::<T>(pool, key).await? {
+            Some((_, version)) if !if_match.matches(&version) => Err(AppError::PreconditionFailed(
+                "The item was changed since it was read".to_string(),
+            )),
+            found => Ok(found),
+        };
+    }
+    let mut client = pool.get().await?;
+    let tx = begin_audited(&mut client, actor).await?;
+    check_version::<T>(&tx, key, if_match).await?;
+    let (sql, params) = patch.update_sql(&T::TABLE, key);
+    let Some(row) = tx.query_opt(&sql, &params).await? else {
+        return Ok(None);
+    };
+    // Rules can span fields the patch did not touch, so check the merged row;
+    // returning early drops the transaction and rolls the update back
+    let item = T::from_row(&row);
+    item.validate()?;
+    tx.commit().await?;
+    Ok(Some((item, T::TABLE.version(&row))))
+}
+
+/// Deletes the row with the given key within `tx`, returning the number of rows deleted.
+pub async fn delete_by_key<T: Listable>(
+    tx: &Transaction<'_>,
+    key: &[i32],
+    if_match: &IfMatch,
+) -> Result<u64> {
+    check_version::<T>(tx, key, if_match).await?;
+    let sql = format!(
+        "DELETE FROM {} WHERE {}",
+        T::TABLE.name,
+        T::TABLE.key_condition(1)
+    );
+    Ok(tx.execute(&sql, &key_params(key)).await?)
+}
+
+/// Streams `rows` into `table` with a binary `COPY`, returning the number of
+/// rows copied. A single bad row fails the whole `COPY`.
+pub async fn copy_rows(tx: &Transaction<'_>, table: &Table, rows: &[InsertRow]) -> Result<u64> {
+    let columns = table.insert_columns();
+    let sql = format!(
+        "COPY {} ({}) FROM STDIN (FORMAT binary)",
+        table.name,
+        columns
+            .iter()
+            .map(|(c, _)| *c)
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    let types: Vec<Type> = columns.iter().map(|(_, ty)| ty.pg_type()).collect();
+    let sink = tx.copy_in(&sql).await?;
+    let mut writer = pin!(BinaryCopyInWriter::new(sink, &types));
+    for row in rows {
+        writer.as_mut().write(&row.params()).await?;
+    }
+    Ok(writer.finish().await?)
+}
+
+/// Inserts one row of `table`; the slow path for finding which rows a `COPY` trips on.
+pub async fn insert_row(tx: &Transaction<'_>, table: &Table, row: &InsertRow) -> Result<()> {
+    let columns = table.insert_columns();
+    let sql = format!(
+        "INSERT INTO {} ({}) VALUES ({})",
+        table.name,
+        columns
+            .iter()
+            .map(|(c, _)| *c)
+            .collect::<Vec<_>>()
+            .join(", "),
+        (1..=columns.len())
+            .map(|i| format!("${}", i))
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    tx.execute(&sql, &row.params()).await?;
+    Ok(())
+}
+
+/// Parcels among `parcely` whose shares do not sum to 100, with the message
+/// the `podily_celek` check would reject them by.
+pub async fn podily_necele(tx: &Transaction<'_>, parcely: &[i32]) -> Result<Vec<(i32, String)>> {
+    let rows = tx
+        .query(
+            "SELECT parcela_id, zprava FROM fn_podily_necele($1)",
+            &[&parcely],
+        )
+        .await?;
+    rows.iter()
+        .map(|row| Ok((row.try_get("parcela_id")?, row.try_get("zprava")?)))
+        .collect()
+}
+
+/// Deletes the shares of `majitele` in a parcel, returning how many there were.
+pub async fn delete_podily(tx: &Transaction<'_>, parcela_id: i32, majitele: &[i32]) -> Result<u64> {
+    Ok(tx
+        .execute(
+            "DELETE FROM vlastnictvi WHERE parcela_id = $1 AND majitel_id = ANY($2)",
+            &[&parcela_id, &majitele],
+        )
+        .await?)
+}
 
 pub async fn query_part_a(
     pool: Pool,
@@ -36,14 +278,18 @@ pub async fn query_majitel_custom(
 
     let mut items = Vec::new();
     for row in rows {
+        let rodne_cislo: Option<String> = row.try_get("rodne_cislo")?;
+        let (datum_narozeni, pohlavi) = birth_details(rodne_cislo.as_deref());
         let item = Majitel {
             id: row.try_get("id")?,
             jmeno: row.try_get("jmeno")?,
             prijmeni: row.try_get("prijmeni")?,
             titul: row.try_get("titul")?,
             bydliste: row.try_get("bydliste")?,
-            rodne_cislo: row.try_get("rodne_cislo")?,
+            rodne_cislo,
             ico: row.try_get("ico")?,
+            datum_narozeni,
+            pohlavi,
         };
         items.push(item);
     }
@@ -51,35 +297,51 @@ pub async fn query_majitel_custom(
     Ok(items)
 }
 
-pub async fn get_majitel(pool: Pool) -> Result<Vec<Majitel>> {
-    let client = pool.get().await?;
-    let rows = client
-        .query(
-            "SELECT id, jmeno, prijmeni, titul, bydliste, rodne_cislo, ico FROM majitel",
-            &[],
-        )
-        .await?;
-
-    let mut items = Vec::new();
-    for row in rows {
-        let item = Majitel {
-            id: row.try_get("id")?,
-            jmeno: row.try_get("jmeno")?,
-            prijmeni: row.try_get("prijmeni")?,
-            titul: row.try_get("titul")?,
-            bydliste: row.try_get("bydliste")?,
-            rodne_cislo: row.try_get("rodne_cislo")?,
-            ico: row.try_get("ico")?,
-        };
-        items.push(item);
+impl Listable for Majitel {
+    const TABLE: Table = Table {
+        name: "majitel",
+        columns: &[
+            ("id", ColumnType::Int),
+            ("jmeno", ColumnType::Text),
+            ("prijmeni", ColumnType::Text),
+            ("titul", ColumnType::Text),
+            ("bydliste", ColumnType::Text),
+            ("rodne_cislo", ColumnType::Text),
+            ("ico", ColumnType::Text),
+        ],
+        key: &["id"],
+        nullable: &["titul", "bydliste", "rodne_cislo", "ico"],
+    };
+
+    fn from_row(row: &Row) -> Self {
+        let rodne_cislo: Option<String> = row.get(5);
+        let (datum_narozeni, pohlavi) = birth_details(rodne_cislo.as_deref());
+        Majitel {
+            id: row.get(0),
+            jmeno: row.get(1),
+            prijmeni: row.get(2),
+            titul: row.get(3),
+            bydliste: row.get(4),
+            rodne_cislo,
+            ico: row.get(6),
+            datum_narozeni,
+            pohlavi,
+        }
     }
-    Ok(items)
 }
 
-pub async fn update_majitel(pool: Pool, majitel: Majitel) -> Result<u64> {
-    let client = pool.get().await?;
+pub async fn get_majitel(pool: Pool, query: &ListQuery) -> Result<Page<Majitel>> {
+    list_rows(pool, query).await
+}
+
+pub async fn update_majitel(
+    tx: &Transaction<'_>,
+    majitel: Majitel,
+    if_match: &IfMatch,
+) -> Result<u64> {
+    check_version::<Majitel>(tx, &[majitel.id], if_match).await?;
     let stmt = "UPDATE majitel SET jmeno = $2, prijmeni = $3, titul = $4, bydliste = $5, rodne_cislo = $6, ico = $7 WHERE id = $1";
-    let rows_affected = client
+    let rows_affected = tx
         .execute(
             stmt,
             &[
@@ -96,11 +358,10 @@ pub async fn update_majitel(pool: Pool, majitel: Majitel) -> Result<u64> {
     Ok(rows_affected)
 }
 
-pub async fn create_majitel(pool: Pool, majitel: NewMajitel) -> Result<u64> {
-    let client = pool.get().await?;
-    let stmt = "INSERT INTO majitel (jmeno, prijmeni, titul, bydliste, rodne_cislo, ico) VALUES ($1, $2, $3, $4, $5, $6)";
-    let rows_affected = client
-        .execute(
+pub async fn create_majitel(tx: &Transaction<'_>, majitel: NewMajitel) -> Result<Majitel> {
+    let stmt = "INSERT INTO majitel (jmeno, prijmeni, titul, bydliste, rodne_cislo, ico) VALUES ($1, $2, $3, $4, $5, $6) RETURNING id, jmeno, prijmeni, titul, bydliste, rodne_cislo, ico";
+    let row = tx
+        .query_one(
             stmt,
             &[
                 &majitel.jmeno,
@@ -112,13 +373,13 @@ pub async fn create_majitel(pool: Pool, majitel: NewMajitel) -> Result<u64> {
             ],
         )
         .await?;
-    Ok(rows_affected)
+    Ok(Majitel::from_row(&row))
 }
 
-pub async fn delete_majitel(pool: Pool, id: i32) -> Result<u64> {
-    let client = pool.get().await?;
+pub async fn delete_majitel(tx: &Transaction<'_>, id: i32, if_match: &IfMatch) -> Result<u64> {
+    check_version::<Majitel>(tx, &[id], if_match).await?;
     let stmt = "DELETE FROM majitel WHERE id = $1";
-    let rows_affected = client.execute(stmt, &[&id]).await?;
+    let rows_affected = tx.execute(stmt, &[&id]).await?;
     Ok(rows_affected)
 }
 
@@ -253,6 +514,33 @@ pub async fn query_part_d(
     Ok(items)
 }
 
+pub async fn query_part_e(
+    pool: Pool,
+    query: &str,
+    params: &[&(dyn tokio_postgres::types::ToSql + Sync)],
+) -> Result<Vec<PartE>> {
+    let client = pool.get().await?;
+    let rows = client.query(query, params).await?;
+
+    let mut items = Vec::new();
+    for row in rows {
+        let item = PartE {
+            druh: row.try_get("druh")?,
+            popis: row.try_get("popis")?,
+            datum_vyhotoveni: row.try_get("datum_vyhotoveni")?,
+            datum_pravnich_ucinku: row.try_get("datum_pravnich_ucinku")?,
+            typ_rizeni_zkratka: row.try_get("typ_rizeni_zkratka")?,
+            cislo_rizeni: row.try_get::<_, i32>("cislo_rizeni")? as i64,
+            rok_rizeni: row.try_get::<_, i32>("rok_rizeni")? as i64,
+            jmeno: row.try_get("jmeno")?,
+            prijmeni: row.try_get("prijmeni")?,
+        };
+        items.push(item);
+    }
+
+    Ok(items)
+}
+
 pub async fn query_part_f(
     pool: Pool,
     query: &str,
@@ -275,6 +563,126 @@ pub async fn query_part_f(
     Ok(items)
 }
 
+pub async fn query_parcely_na_lv(
+    pool: Pool,
+    query: &str,
+    params: &[&(dyn tokio_postgres::types::ToSql + Sync)],
+) -> Result<Vec<ParcelaNaLv>> {
+    let client = pool.get().await?;
+    let rows = client.query(query, params).await?;
+
+    let mut items = Vec::new();
+    for row in rows {
+        let item = ParcelaNaLv {
+            parcela_id: row.try_get("parcela_id")?,
+            je_stavebni: row.try_get("je_stavebni")?,
+            parcelni_cislo: row.try_get("parcelni_cislo")?,
+            cast_parcely: row.try_get("cast_parcely")?,
+        };
+        items.push(item);
+    }
+
+    Ok(items)
+}
+
+pub async fn query_podily_na_lv(
+    pool: Pool,
+    query: &str,
+    params: &[&(dyn tokio_postgres::types::ToSql + Sync)],
+) -> Result<Vec<PodilNaLv>> {
+    let client = pool.get().await?;
+    let rows = client.query(query, params).await?;
+
+    let mut items = Vec::new();
+    for row in rows {
+        let item = PodilNaLv {
+            parcela_id: row.try_get("parcela_id")?,
+            majitel_id: row.try_get("majitel_id")?,
+            jmeno: row.try_get("jmeno")?,
+            prijmeni: row.try_get("prijmeni")?,
+            podil_setin: row.try_get("podil_setin")?,
+        };
+        items.push(item);
+    }
+
+    Ok(items)
+}
+
+/// Stored and computed ownership hashes of the LVs matching the filters.
+pub async fn query_vlastnicky_hash(
+    pool: Pool,
+    katastralni_uzemi: Option<&str>,
+    cislo_lv: Option<i32>,
+) -> Result<Vec<VlastnickyHash>> {
+    let client = pool.get().await?;
+    let rows = client
+        .query(
+            "SELECT l.id, k.nazev, l.cislo_lv, l.vlastnicky_hash, fn_vlastnicky_hash(l.id) AS computed
+             FROM list_vlastnictvi l
+             JOIN katastralni_uzemi k ON k.id = l.katastralni_uzemi_id
+             WHERE ($1::text IS NULL OR k.nazev = $1) AND ($2::int IS NULL OR l.cislo_lv = $2)
+             ORDER BY k.nazev, l.cislo_lv",
+            &[&katastralni_uzemi, &cislo_lv],
+        )
+        .await?;
+
+    let mut items = Vec::new();
+    for row in rows {
+        let item = VlastnickyHash {
+            list_vlastnictvi_id: row.try_get("id")?,
+            katastralni_uzemi: row.try_get("nazev")?,
+            cislo_lv: row.try_get("cislo_lv")?,
+            stored: row.try_get("vlastnicky_hash")?,
+            computed: row.try_get("computed")?,
+        };
+        items.push(item);
+    }
+
+    Ok(items)
+}
+
+/// Parcels with owners whose shares do not sum to 100, ordered like an LV.
+pub async fn query_nevyrovnane_parcely(
+    pool: Pool,
+    katastralni_uzemi: Option<&str>,
+    cislo_lv: Option<i32>,
+) -> Result<Vec<NevyrovnanaParcela>> {
+    let client = pool.get().await?;
+    let rows = client
+        .query(
+            "SELECT p.id, l.id AS list_vlastnictvi_id, k.nazev, l.cislo_lv, p.je_stavebni, p.parcelni_cislo, p.cast_parcely,
+                count(*) AS pocet_vlastniku, sum(v.podil_setin) AS soucet_setin
+             FROM parcela p
+             JOIN katastralni_uzemi k ON k.id = p.katastralni_uzemi_id
+             JOIN list_vlastnictvi l ON l.id = p.list_vlastnictvi_id
+             JOIN vlastnictvi v ON v.parcela_id = p.id
+             WHERE ($1::text IS NULL OR k.nazev = $1) AND ($2::int IS NULL OR l.cislo_lv = $2)
+             GROUP BY p.id, l.id, k.nazev
+             HAVING sum(v.podil_setin) <> 100
+             ORDER BY k.nazev, l.cislo_lv, p.je_stavebni DESC, p.parcelni_cislo, p.cast_parcely",
+            &[&katastralni_uzemi, &cislo_lv],
+        )
+        .await?;
+
+    let mut items = Vec::new();
+    for row in rows {
+        let item = NevyrovnanaParcela {
+            parcela_id: row.try_get("id")?,
+            list_vlastnictvi_id: row.try_get("list_vlastnictvi_id")?,
+            katastralni_uzemi: row.try_get("nazev")?,
+            cislo_lv: row.try_get("cislo_lv")?,
+            je_stavebni: row.try_get("je_stavebni")?,
+            parcelni_cislo: row.try_get("parcelni_cislo")?,
+            cast_parcely: row.try_get("cast_parcely")?,
+            pocet_vlastniku: row.try_get("pocet_vlastniku")?,
+            soucet_setin: row.try_get("soucet_setin")?,
+        };
+        items.push(item);
+    }
+
+    Ok(items)
+}
+
 pub async fn query_parcela(
     pool: Pool,
     query: &str,
@@ -363,29 +771,39 @@ pub async fn query_rizeni_operace(
 }
 
 // --- Kraj ---
-pub async fn get_kraj(pool: Pool) -> Result<Vec<Kraj>> {
-    let client = pool.get().await?;
-    let rows = client.query("SELECT id, nazev FROM kraj", &[]).await?;
-    Ok(rows
-        .iter()
-        .map(|row| Kraj {
+impl Listable for Kraj {
+    const TABLE: Table = Table {
+        name: "kraj",
+        columns: &[("id", ColumnType::Int), ("nazev", ColumnType::Text)],
+        key: &["id"],
+        nullable: &[],
+    };
+
+    fn from_row(row: &Row) -> Self {
+        Kraj {
             id: row.get(0),
             nazev: row.get(1),
-        })
-        .collect())
+        }
+    }
 }
 
-pub async fn create_kraj(pool: Pool, item: NewKraj) -> Result<u64> {
-    let client = pool.get().await?;
-    let rows = client
-        .execute("INSERT INTO kraj (nazev) VALUES ($1)", &[&item.nazev])
+pub async fn get_kraj(pool: Pool, query: &ListQuery) -> Result<Page<Kraj>> {
+    list_rows(pool, query).await
+}
+
+pub async fn create_kraj(tx: &Transaction<'_>, item: NewKraj) -> Result<Kraj> {
+    let row = tx
+        .query_one(
+            "INSERT INTO kraj (nazev) VALUES ($1) RETURNING id, nazev",
+            &[&item.nazev],
+        )
         .await?;
-    Ok(rows)
+    Ok(Kraj::from_row(&row))
 }
 
-pub async fn update_kraj(pool: Pool, item: Kraj) -> Result<u64> {
-    let client = pool.get().await?;
-    let rows = client
+pub async fn update_kraj(tx: &Transaction<'_>, item: Kraj, if_match: &IfMatch) -> Result<u64> {
+    check_version::<Kraj>(tx, &[item.id], if_match).await?;
+    let rows = tx
         .execute(
             "UPDATE kraj SET nazev = $2 WHERE id = $1",
             &[&item.id, &item.nazev],
@@ -394,44 +812,51 @@ pub async fn update_kraj(pool: Pool, item: Kraj) -> Result<u64> {
     Ok(rows)
 }
 
-pub async fn delete_kraj(pool: Pool, id: i32) -> Result<u64> {
-    let client = pool.get().await?;
-    let rows = client
-        .execute("DELETE FROM kraj WHERE id = $1", &[&id])
-        .await?;
+pub async fn delete_kraj(tx: &Transaction<'_>, id: i32, if_match: &IfMatch) -> Result<u64> {
+    check_version::<Kraj>(tx, &[id], if_match).await?;
+    let rows = tx.execute("DELETE FROM kraj WHERE id = $1", &[&id]).await?;
     Ok(rows)
 }
 
 // --- Okres ---
-pub async fn get_okres(pool: Pool) -> Result<Vec<Okres>> {
-    let client = pool.get().await?;
-    let rows = client
-        .query("SELECT id, kraj_id, nazev FROM okres", &[])
-        .await?;
-    Ok(rows
-        .iter()
-        .map(|row| Okres {
+impl Listable for Okres {
+    const TABLE: Table = Table {
+        name: "okres",
+        columns: &[
+            ("id", ColumnType::Int),
+            ("kraj_id", ColumnType::Int),
+            ("nazev", ColumnType::Text),
+        ],
+        key: &["id"],
+        nullable: &[],
+    };
+
+    fn from_row(row: &Row) -> Self {
+        Okres {
             id: row.get(0),
             kraj_id: row.get(1),
             nazev: row.get(2),
-        })
-        .collect())
+        }
+    }
 }
 
-pub async fn create_okres(pool: Pool, item: NewOkres) -> Result<u64> {
-    let client = pool.get().await?;
-    let rows = client
-        .execute(
-            "INSERT INTO okres (kraj_id, nazev) VALUES ($1, $2)",
+pub async fn get_okres(pool: Pool, query: &ListQuery) -> Result<Page<Okres>> {
+    list_rows(pool, query).await
+}
+
+pub async fn create_okres(tx: &Transaction<'_>, item: NewOkres) -> Result<Okres> {
+    let row = tx
+        .query_one(
+            "INSERT INTO okres (kraj_id, nazev) VALUES ($1, $2) RETURNING id, kraj_id, nazev",
             &[&item.kraj_id, &item.nazev],
         )
         .await?;
-    Ok(rows)
+    Ok(Okres::from_row(&row))
 }
 
-pub async fn update_okres(pool: Pool, item: Okres) -> Result<u64> {
-    let client = pool.get().await?;
-    let rows = client
+pub async fn update_okres(tx: &Transaction<'_>, item: Okres, if_match: &IfMatch) -> Result<u64> {
+    check_version::<Okres>(tx, &[item.id], if_match).await?;
+    let rows = tx
         .execute(
             "UPDATE okres SET kraj_id = $2, nazev = $3 WHERE id = $1",
             &[&item.id, &item.kraj_id, &item.nazev],
@@ -440,44 +865,53 @@ pub async fn update_okres(pool: Pool, item: Okres) -> Result<u64> {
     Ok(rows)
 }
 
-pub async fn delete_okres(pool: Pool, id: i32) -> Result<u64> {
-    let client = pool.get().await?;
-    let rows = client
+pub async fn delete_okres(tx: &Transaction<'_>, id: i32, if_match: &IfMatch) -> Result<u64> {
+    check_version::<Okres>(tx, &[id], if_match).await?;
+    let rows = tx
         .execute("DELETE FROM okres WHERE id = $1", &[&id])
         .await?;
     Ok(rows)
 }
 
 // --- Obec ---
-pub async fn get_obec(pool: Pool) -> Result<Vec<Obec>> {
-    let client = pool.get().await?;
-    let rows = client
-        .query("SELECT id, okres_id, nazev FROM obec", &[])
-        .await?;
-    Ok(rows
-        .iter()
-        .map(|row| Obec {
+impl Listable for Obec {
+    const TABLE: Table = Table {
+        name: "obec",
+        columns: &[
+            ("id", ColumnType::Int),
+            ("okres_id", ColumnType::Int),
+            ("nazev", ColumnType::Text),
+        ],
+        key: &["id"],
+        nullable: &[],
+    };
+
+    fn from_row(row: &Row) -> Self {
+        Obec {
             id: row.get(0),
             okres_id: row.get(1),
             nazev: row.get(2),
-        })
-        .collect())
+        }
+    }
 }
 
-pub async fn create_obec(pool: Pool, item: NewObec) -> Result<u64> {
-    let client = pool.get().await?;
-    let rows = client
-        .execute(
-            "INSERT INTO obec (okres_id, nazev) VALUES ($1, $2)",
+pub async fn get_obec(pool: Pool, query: &ListQuery) -> Result<Page<Obec>> {
+    list_rows(pool, query).await
+}
+
+pub async fn create_obec(tx: &Transaction<'_>, item: NewObec) -> Result<Obec> {
+    let row = tx
+        .query_one(
+            "INSERT INTO obec (okres_id, nazev) VALUES ($1, $2) RETURNING id, okres_id, nazev",
             &[&item.okres_id, &item.nazev],
         )
         .await?;
-    Ok(rows)
+    Ok(Obec::from_row(&row))
 }
 
-pub async fn update_obec(pool: Pool, item: Obec) -> Result<u64> {
-    let client = pool.get().await?;
-    let rows = client
+pub async fn update_obec(tx: &Transaction<'_>, item: Obec, if_match: &IfMatch) -> Result<u64> {
+    check_version::<Obec>(tx, &[item.id], if_match).await?;
+    let rows = tx
         .execute(
             "UPDATE obec SET okres_id = $2, nazev = $3 WHERE id = $1",
             &[&item.id, &item.okres_id, &item.nazev],
@@ -486,44 +920,61 @@ pub async fn update_obec(pool: Pool, item: Obec) -> Result<u64> {
     Ok(rows)
 }
 
-pub async fn delete_obec(pool: Pool, id: i32) -> Result<u64> {
-    let client = pool.get().await?;
-    let rows = client
-        .execute("DELETE FROM obec WHERE id = $1", &[&id])
-        .await?;
+pub async fn delete_obec(tx: &Transaction<'_>, id: i32, if_match: &IfMatch) -> Result<u64> {
+    check_version::<Obec>(tx, &[id], if_match).await?;
+    let rows = tx.execute("DELETE FROM obec WHERE id = $1", &[&id]).await?;
     Ok(rows)
 }
 
 // --- KatastralniUzemi ---
-pub async fn get_katastralni_uzemi(pool: Pool) -> Result<Vec<KatastralniUzemi>> {
-    let client = pool.get().await?;
-    let rows = client
-        .query("SELECT id, obec_id, nazev FROM katastralni_uzemi", &[])
-        .await?;
-    Ok(rows
-        .iter()
-        .map(|row| KatastralniUzemi {
+impl Listable for KatastralniUzemi {
+    const TABLE: Table = Table {
+        name: "katastralni_uzemi",
+        columns: &[
+            ("id", ColumnType::Int),
+            ("obec_id", ColumnType::Int),
+            ("nazev", ColumnType::Text),
+        ],
+        key: &["id"],
+        nullable: &[],
+    };
+
+    fn from_row(row: &Row) -> Self {
+        KatastralniUzemi {
             id: row.get(0),
             obec_id: row.get(1),
             nazev: row.get(2),
-        })
-        .collect())
+        }
+    }
 }
 
-pub async fn create_katastralni_uzemi(pool: Pool, item: NewKatastralniUzemi) -> Result<u64> {
-    let client = pool.get().await?;
-    let rows = client
-        .execute(
-            "INSERT INTO katastralni_uzemi (obec_id, nazev) VALUES ($1, $2)",
+pub async fn get_katastralni_uzemi(
+    pool: Pool,
+    query: &ListQuery,
+) -> Result<Page<KatastralniUzemi>> {
+    list_rows(pool, query).await
+}
+
+pub async fn create_katastralni_uzemi(
+    tx: &Transaction<'_>,
+    item: NewKatastralniUzemi,
+) -> Result<KatastralniUzemi> {
+    let row = tx
+        .query_one(
+            "INSERT INTO katastralni_uzemi (obec_id, nazev) VALUES ($1, $2) RETURNING id, obec_id, nazev",
             &[&item.obec_id, &item.nazev],
         )
         .await?;
-    Ok(rows)
+    Ok(KatastralniUzemi::from_row(&row))
 }
 
-pub async fn update_katastralni_uzemi(pool: Pool, item: KatastralniUzemi) -> Result<u64> {
-    let client = pool.get().await?;
-    let rows = client
+pub async fn update_katastralni_uzemi(
+    tx: &Transaction<'_>,
+    item: KatastralniUzemi,
+    if_match: &IfMatch,
+) -> Result<u64> {
+    check_version::<KatastralniUzemi>(tx, &[item.id], if_match).await?;
+    let rows = tx
         .execute(
             "UPDATE katastralni_uzemi SET obec_id = $2, nazev = $3 WHERE id = $1",
             &[&item.id, &item.obec_id, &item.nazev],
@@ -532,38 +983,52 @@ pub async fn update_katastralni_uzemi(pool: Pool, item: KatastralniUzemi) -> Res
     Ok(rows)
 }
 
-pub async fn delete_katastralni_uzemi(pool: Pool, id: i32) -> Result<u64> {
-    let client = pool.get().await?;
-    let rows = client
+pub async fn delete_katastralni_uzemi(
+    tx: &Transaction<'_>,
+    id: i32,
+    if_match: &IfMatch,
+) -> Result<u64> {
+    check_version::<KatastralniUzemi>(tx, &[id], if_match).await?;
+    let rows = tx
         .execute("DELETE FROM katastralni_uzemi WHERE id = $1", &[&id])
         .await?;
     Ok(rows)
 }
 
 // --- Bpej ---
-pub async fn get_bpej(pool: Pool) -> Result<Vec<Bpej>> {
-    let client = pool.get().await?;
-    let rows = client.query("SELECT id, hodnota FROM bpej", &[]).await?;
-    Ok(rows
-        .iter()
-        .map(|row| Bpej {
+impl Listable for Bpej {
+    const TABLE: Table = Table {
+        name: "bpej",
+        columns: &[("id", ColumnType::Int), ("hodnota", ColumnType::Int)],
+        key: &["id"],
+        nullable: &[],
+    };
+
+    fn from_row(row: &Row) -> Self {
+        Bpej {
             id: row.get(0),
             hodnota: row.get(1),
-        })
-        .collect())
+        }
+    }
 }
 
-pub async fn create_bpej(pool: Pool, item: NewBpej) -> Result<u64> {
-    let client = pool.get().await?;
-    let rows = client
-        .execute("INSERT INTO bpej (hodnota) VALUES ($1)", &[&item.hodnota])
+pub async fn get_bpej(pool: Pool, query: &ListQuery) -> Result<Page<Bpej>> {
+    list_rows(pool, query).await
+}
+
+pub async fn create_bpej(tx: &Transaction<'_>, item: NewBpej) -> Result<Bpej> {
+    let row = tx
+        .query_one(
+            "INSERT INTO bpej (hodnota) VALUES ($1) RETURNING id, hodnota",
+            &[&item.hodnota],
+        )
         .await?;
-    Ok(rows)
+    Ok(Bpej::from_row(&row))
 }
 
-pub async fn update_bpej(pool: Pool, item: Bpej) -> Result<u64> {
-    let client = pool.get().await?;
-    let rows = client
+pub async fn update_bpej(tx: &Transaction<'_>, item: Bpej, if_match: &IfMatch) -> Result<u64> {
+    check_version::<Bpej>(tx, &[item.id], if_match).await?;
+    let rows = tx
         .execute(
             "UPDATE bpej SET hodnota = $2 WHERE id = $1",
             &[&item.id, &item.hodnota],
@@ -572,44 +1037,55 @@ pub async fn update_bpej(pool: Pool, item: Bpej) -> Result<u64> {
     Ok(rows)
 }
 
-pub async fn delete_bpej(pool: Pool, id: i32) -> Result<u64> {
-    let client = pool.get().await?;
-    let rows = client
-        .execute("DELETE FROM bpej WHERE id = $1", &[&id])
-        .await?;
+pub async fn delete_bpej(tx: &Transaction<'_>, id: i32, if_match: &IfMatch) -> Result<u64> {
+    check_version::<Bpej>(tx, &[id], if_match).await?;
+    let rows = tx.execute("DELETE FROM bpej WHERE id = $1", &[&id]).await?;
     Ok(rows)
 }
 
 // --- TypRizeni ---
-pub async fn get_typ_rizeni(pool: Pool) -> Result<Vec<TypRizeni>> {
-    let client = pool.get().await?;
-    let rows = client
-        .query("SELECT id, nazev, zkratka FROM typ_rizeni", &[])
-        .await?;
-    Ok(rows
-        .iter()
-        .map(|row| TypRizeni {
+impl Listable for TypRizeni {
+    const TABLE: Table = Table {
+        name: "typ_rizeni",
+        columns: &[
+            ("id", ColumnType::Int),
+            ("nazev", ColumnType::Text),
+            ("zkratka", ColumnType::Text),
+        ],
+        key: &["id"],
+        nullable: &[],
+    };
+
+    fn from_row(row: &Row) -> Self {
+        TypRizeni {
             id: row.get(0),
             nazev: row.get(1),
             zkratka: row.get(2),
-        })
-        .collect())
+        }
+    }
 }
 
-pub async fn create_typ_rizeni(pool: Pool, item: NewTypRizeni) -> Result<u64> {
-    let client = pool.get().await?;
-    let rows = client
-        .execute(
-            "INSERT INTO typ_rizeni (nazev, zkratka) VALUES ($1, $2)",
+pub async fn get_typ_rizeni(pool: Pool, query: &ListQuery) -> Result<Page<TypRizeni>> {
+    list_rows(pool, query).await
+}
+
+pub async fn create_typ_rizeni(tx: &Transaction<'_>, item: NewTypRizeni) -> Result<TypRizeni> {
+    let row = tx
+        .query_one(
+            "INSERT INTO typ_rizeni (nazev, zkratka) VALUES ($1, $2) RETURNING id, nazev, zkratka",
             &[&item.nazev, &item.zkratka],
         )
         .await?;
-    Ok(rows)
+    Ok(TypRizeni::from_row(&row))
 }
 
-pub async fn update_typ_rizeni(pool: Pool, item: TypRizeni) -> Result<u64> {
-    let client = pool.get().await?;
-    let rows = client
+pub async fn update_typ_rizeni(
+    tx: &Transaction<'_>,
+    item: TypRizeni,
+    if_match: &IfMatch,
+) -> Result<u64> {
+    check_version::<TypRizeni>(tx, &[item.id], if_match).await?;
+    let rows = tx
         .execute(
             "UPDATE typ_rizeni SET nazev = $2, zkratka = $3 WHERE id = $1",
             &[&item.id, &item.nazev, &item.zkratka],
@@ -618,43 +1094,52 @@ pub async fn update_typ_rizeni(pool: Pool, item: TypRizeni) -> Result<u64> {
     Ok(rows)
 }
 
-pub async fn delete_typ_rizeni(pool: Pool, id: i32) -> Result<u64> {
-    let client = pool.get().await?;
-    let rows = client
+pub async fn delete_typ_rizeni(tx: &Transaction<'_>, id: i32, if_match: &IfMatch) -> Result<u64> {
+    check_version::<TypRizeni>(tx, &[id], if_match).await?;
+    let rows = tx
         .execute("DELETE FROM typ_rizeni WHERE id = $1", &[&id])
         .await?;
     Ok(rows)
 }
 
 // --- TypOperace ---
-pub async fn get_typ_operace(pool: Pool) -> Result<Vec<TypOperace>> {
-    let client = pool.get().await?;
-    let rows = client
-        .query("SELECT id, popis FROM typ_operace", &[])
-        .await?;
-    Ok(rows
-        .iter()
-        .map(|row| TypOperace {
+impl Listable for TypOperace {
+    const TABLE: Table = Table {
+        name: "typ_operace",
+        columns: &[("id", ColumnType::Int), ("popis", ColumnType::Text)],
+        key: &["id"],
+        nullable: &[],
+    };
+
+    fn from_row(row: &Row) -> Self {
+        TypOperace {
             id: row.get(0),
             popis: row.get(1),
-        })
-        .collect())
+        }
+    }
 }
 
-pub async fn create_typ_operace(pool: Pool, item: NewTypOperace) -> Result<u64> {
-    let client = pool.get().await?;
-    let rows = client
-        .execute(
-            "INSERT INTO typ_operace (popis) VALUES ($1)",
+pub async fn get_typ_operace(pool: Pool, query: &ListQuery) -> Result<Page<TypOperace>> {
+    list_rows(pool, query).await
+}
+
+pub async fn create_typ_operace(tx: &Transaction<'_>, item: NewTypOperace) -> Result<TypOperace> {
+    let row = tx
+        .query_one(
+            "INSERT INTO typ_operace (popis) VALUES ($1) RETURNING id, popis",
             &[&item.popis],
         )
         .await?;
-    Ok(rows)
+    Ok(TypOperace::from_row(&row))
 }
 
-pub async fn update_typ_operace(pool: Pool, item: TypOperace) -> Result<u64> {
-    let client = pool.get().await?;
-    let rows = client
+pub async fn update_typ_operace(
+    tx: &Transaction<'_>,
+    item: TypOperace,
+    if_match: &IfMatch,
+) -> Result<u64> {
+    check_version::<TypOperace>(tx, &[item.id], if_match).await?;
+    let rows = tx
         .execute(
             "UPDATE typ_operace SET popis = $2 WHERE id = $1",
             &[&item.id, &item.popis],
@@ -663,43 +1148,55 @@ pub async fn update_typ_operace(pool: Pool, item: TypOperace) -> Result<u64> {
     Ok(rows)
 }
 
-pub async fn delete_typ_operace(pool: Pool, id: i32) -> Result<u64> {
-    let client = pool.get().await?;
-    let rows = client
+pub async fn delete_typ_operace(tx: &Transaction<'_>, id: i32, if_match: &IfMatch) -> Result<u64> {
+    check_version::<TypOperace>(tx, &[id], if_match).await?;
+    let rows = tx
         .execute("DELETE FROM typ_operace WHERE id = $1", &[&id])
         .await?;
     Ok(rows)
 }
 
 // --- TypUcastnika ---
-pub async fn get_typ_ucastnika(pool: Pool) -> Result<Vec<TypUcastnika>> {
-    let client = pool.get().await?;
-    let rows = client
-        .query("SELECT id, nazev FROM typ_ucastnika", &[])
-        .await?;
-    Ok(rows
-        .iter()
-        .map(|row| TypUcastnika {
+impl Listable for TypUcastnika {
+    const TABLE: Table = Table {
+        name: "typ_ucastnika",
+        columns: &[("id", ColumnType::Int), ("nazev", ColumnType::Text)],
+        key: &["id"],
+        nullable: &[],
+    };
+
+    fn from_row(row: &Row) -> Self {
+        TypUcastnika {
             id: row.get(0),
             nazev: row.get(1),
-        })
-        .collect())
+        }
+    }
 }
 
-pub async fn create_typ_ucastnika(pool: Pool, item: NewTypUcastnika) -> Result<u64> {
-    let client = pool.get().await?;
-    let rows = client
-        .execute(
-            "INSERT INTO typ_ucastnika (nazev) VALUES ($1)",
+pub async fn get_typ_ucastnika(pool: Pool, query: &ListQuery) -> Result<Page<TypUcastnika>> {
+    list_rows(pool, query).await
+}
+
+pub async fn create_typ_ucastnika(
+    tx: &Transaction<'_>,
+    item: NewTypUcastnika,
+) -> Result<TypUcastnika> {
+    let row = tx
+        .query_one(
+            "INSERT INTO typ_ucastnika (nazev) VALUES ($1) RETURNING id, nazev",
             &[&item.nazev],
         )
         .await?;
-    Ok(rows)
+    Ok(TypUcastnika::from_row(&row))
 }
 
-pub async fn update_typ_ucastnika(pool: Pool, item: TypUcastnika) -> Result<u64> {
-    let client = pool.get().await?;
-    let rows = client
+pub async fn update_typ_ucastnika(
+    tx: &Transaction<'_>,
+    item: TypUcastnika,
+    if_match: &IfMatch,
+) -> Result<u64> {
+    check_version::<TypUcastnika>(tx, &[item.id], if_match).await?;
+    let rows = tx
         .execute(
             "UPDATE typ_ucastnika SET nazev = $2 WHERE id = $1",
             &[&item.id, &item.nazev],
@@ -708,43 +1205,59 @@ pub async fn update_typ_ucastnika(pool: Pool, item: TypUcastnika) -> Result<u64>
     Ok(rows)
 }
 
-pub async fn delete_typ_ucastnika(pool: Pool, id: i32) -> Result<u64> {
-    let client = pool.get().await?;
-    let rows = client
+pub async fn delete_typ_ucastnika(
+    tx: &Transaction<'_>,
+    id: i32,
+    if_match: &IfMatch,
+) -> Result<u64> {
+    check_version::<TypUcastnika>(tx, &[id], if_match).await?;
+    let rows = tx
         .execute("DELETE FROM typ_ucastnika WHERE id = $1", &[&id])
         .await?;
     Ok(rows)
 }
 
 // --- UcastnikRizeni ---
-pub async fn get_ucastnik_rizeni(pool: Pool) -> Result<Vec<UcastnikRizeni>> {
-    let client = pool.get().await?;
-    let rows = client
-        .query("SELECT id, jmeno FROM ucastnik_rizeni", &[])
-        .await?;
-    Ok(rows
-        .iter()
-        .map(|row| UcastnikRizeni {
+impl Listable for UcastnikRizeni {
+    const TABLE: Table = Table {
+        name: "ucastnik_rizeni",
+        columns: &[("id", ColumnType::Int), ("jmeno", ColumnType::Text)],
+        key: &["id"],
+        nullable: &[],
+    };
+
+    fn from_row(row: &Row) -> Self {
+        UcastnikRizeni {
             id: row.get(0),
             jmeno: row.get(1),
-        })
-        .collect())
+        }
+    }
 }
 
-pub async fn create_ucastnik_rizeni(pool: Pool, item: NewUcastnikRizeni) -> Result<u64> {
-    let client = pool.get().await?;
-    let rows = client
-        .execute(
-            "INSERT INTO ucastnik_rizeni (jmeno) VALUES ($1)",
+pub async fn get_ucastnik_rizeni(pool: Pool, query: &ListQuery) -> Result<Page<UcastnikRizeni>> {
+    list_rows(pool, query).await
+}
+
+pub async fn create_ucastnik_rizeni(
+    tx: &Transaction<'_>,
+    item: NewUcastnikRizeni,
+) -> Result<UcastnikRizeni> {
+    let row = tx
+        .query_one(
+            "INSERT INTO ucastnik_rizeni (jmeno) VALUES ($1) RETURNING id, jmeno",
             &[&item.jmeno],
         )
         .await?;
-    Ok(rows)
+    Ok(UcastnikRizeni::from_row(&row))
 }
 
-pub async fn update_ucastnik_rizeni(pool: Pool, item: UcastnikRizeni) -> Result<u64> {
-    let client = pool.get().await?;
-    let rows = client
+pub async fn update_ucastnik_rizeni(
+    tx: &Transaction<'_>,
+    item: UcastnikRizeni,
+    if_match: &IfMatch,
+) -> Result<u64> {
+    check_version::<UcastnikRizeni>(tx, &[item.id], if_match).await?;
+    let rows = tx
         .execute(
             "UPDATE ucastnik_rizeni SET jmeno = $2 WHERE id = $1",
             &[&item.id, &item.jmeno],
@@ -753,67 +1266,104 @@ pub async fn update_ucastnik_rizeni(pool: Pool, item: UcastnikRizeni) -> Result<
     Ok(rows)
 }
 
-pub async fn delete_ucastnik_rizeni(pool: Pool, id: i32) -> Result<u64> {
-    let client = pool.get().await?;
-    let rows = client
+pub async fn delete_ucastnik_rizeni(
+    tx: &Transaction<'_>,
+    id: i32,
+    if_match: &IfMatch,
+) -> Result<u64> {
+    check_version::<UcastnikRizeni>(tx, &[id], if_match).await?;
+    let rows = tx
         .execute("DELETE FROM ucastnik_rizeni WHERE id = $1", &[&id])
         .await?;
     Ok(rows)
 }
 
 // --- ListVlastnictvi ---
-pub async fn get_list_vlastnictvi(pool: Pool) -> Result<Vec<ListVlastnictvi>> {
-    let client = pool.get().await?;
-    let rows = client
-        .query(
-            "SELECT id, katastralni_uzemi_id, cislo_lv, vlastnicky_hash FROM list_vlastnictvi",
-            &[],
-        )
-        .await?;
-    Ok(rows
-        .iter()
-        .map(|row| ListVlastnictvi {
+impl Listable for ListVlastnictvi {
+    const TABLE: Table = Table {
+        name: "list_vlastnictvi",
+        columns: &[
+            ("id", ColumnType::Int),
+            ("katastralni_uzemi_id", ColumnType::Int),
+            ("cislo_lv", ColumnType::Int),
+            ("vlastnicky_hash", ColumnType::Text),
+        ],
+        key: &["id"],
+        nullable: &["vlastnicky_hash"],
+    };
+
+    fn from_row(row: &Row) -> Self {
+        ListVlastnictvi {
             id: row.get(0),
             katastralni_uzemi_id: row.get(1),
             cislo_lv: row.get(2),
             vlastnicky_hash: row.get(3),
-        })
-        .collect())
+        }
+    }
 }
 
-pub async fn create_list_vlastnictvi(pool: Pool, item: NewListVlastnictvi) -> Result<u64> {
-    let client = pool.get().await?;
-    let rows = client.execute(
-        "INSERT INTO list_vlastnictvi (katastralni_uzemi_id, cislo_lv, vlastnicky_hash) VALUES ($1, $2, $3)",
+pub async fn get_list_vlastnictvi(pool: Pool, query: &ListQuery) -> Result<Page<ListVlastnictvi>> {
+    list_rows(pool, query).await
+}
+
+pub async fn create_list_vlastnictvi(
+    tx: &Transaction<'_>,
+    item: NewListVlastnictvi,
+) -> Result<ListVlastnictvi> {
+    let row = tx.query_one(
+        "INSERT INTO list_vlastnictvi (katastralni_uzemi_id, cislo_lv, vlastnicky_hash) VALUES ($1, $2, $3) RETURNING id, katastralni_uzemi_id, cislo_lv, vlastnicky_hash",
         &[&item.katastralni_uzemi_id, &item.cislo_lv, &item.vlastnicky_hash]
     ).await?;
-    Ok(rows)
+    Ok(ListVlastnictvi::from_row(&row))
 }
 
-pub async fn update_list_vlastnictvi(pool: Pool, item: ListVlastnictvi) -> Result<u64> {
-    let client = pool.get().await?;
-    let rows = client.execute(
+pub async fn update_list_vlastnictvi(
+    tx: &Transaction<'_>,
+    item: ListVlastnictvi,
+    if_match: &IfMatch,
+) -> Result<u64> {
+    check_version::<ListVlastnictvi>(tx, &[item.id], if_match).await?;
+    let rows = tx.execute(
         "UPDATE list_vlastnictvi SET katastralni_uzemi_id = $2, cislo_lv = $3, vlastnicky_hash = $4 WHERE id = $1",
         &[&item.id, &item.katastralni_uzemi_id, &item.cislo_lv, &item.vlastnicky_hash]
     ).await?;
     Ok(rows)
 }
 
-pub async fn delete_list_vlastnictvi(pool: Pool, id: i32) -> Result<u64> {
-    let client = pool.get().await?;
-    let rows = client
+pub async fn delete_list_vlastnictvi(
+    tx: &Transaction<'_>,
+    id: i32,
+    if_match: &IfMatch,
+) -> Result<u64> {
+    check_version::<ListVlastnictvi>(tx, &[id], if_match).await?;
+    let rows = tx
         .execute("DELETE FROM list_vlastnictvi WHERE id = $1", &[&id])
         .await?;
     Ok(rows)
 }
 
 // --- ParcelaRow ---
-pub async fn get_parcela_row(pool: Pool) -> Result<Vec<ParcelaRow>> {
-    let client = pool.get().await?;
-    let rows = client.query("SELECT id, parcelni_cislo, cast_parcely, je_stavebni, vymera_metru_ctverecnich, ulice, cislo_popisne, katastralni_uzemi_id, bpej_id, list_vlastnictvi_id FROM parcela", &[]).await?;
-    Ok(rows
-        .iter()
-        .map(|row| ParcelaRow {
+impl Listable for ParcelaRow {
+    const TABLE: Table = Table {
+        name: "parcela",
+        columns: &[
+            ("id", ColumnType::Int),
+            ("parcelni_cislo", ColumnType::Int),
+            ("cast_parcely", ColumnType::Int),
+            ("je_stavebni", ColumnType::Bool),
+            ("vymera_metru_ctverecnich", ColumnType::Decimal),
+            ("ulice", ColumnType::Text),
+            ("cislo_popisne", ColumnType::Text),
+            ("katastralni_uzemi_id", ColumnType::Int),
+            ("bpej_id", ColumnType::Int),
+            ("list_vlastnictvi_id", ColumnType::Int),
+        ],
+        key: &["id"],
+        nullable: &["ulice", "cislo_popisne", "bpej_id"],
+    };
+
+    fn from_row(row: &Row) -> Self {
+        ParcelaRow {
             id: row.get(0),
             parcelni_cislo: row.get(1),
             cast_parcely: row.get(2),
@@ -824,117 +1374,143 @@ pub async fn get_parcela_row(pool: Pool) -> Result<Vec<ParcelaRow>> {
             katastralni_uzemi_id: row.get(7),
             bpej_id: row.get(8),
             list_vlastnictvi_id: row.get(9),
-        })
-        .collect())
+        }
+    }
 }
 
-pub async fn create_parcela_row(pool: Pool, item: NewParcelaRow) -> Result<u64> {
-    let client = pool.get().await?;
-    let rows = client.execute(
-        "INSERT INTO parcela (parcelni_cislo, cast_parcely, je_stavebni, vymera_metru_ctverecnich, ulice, cislo_popisne, katastralni_uzemi_id, bpej_id, list_vlastnictvi_id) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)",
+pub async fn get_parcela_row(pool: Pool, query: &ListQuery) -> Result<Page<ParcelaRow>> {
+    list_rows(pool, query).await
+}
+
+pub async fn create_parcela_row(tx: &Transaction<'_>, item: NewParcelaRow) -> Result<ParcelaRow> {
+    let row = tx.query_one(
+        "INSERT INTO parcela (parcelni_cislo, cast_parcely, je_stavebni, vymera_metru_ctverecnich, ulice, cislo_popisne, katastralni_uzemi_id, bpej_id, list_vlastnictvi_id) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9) RETURNING id, parcelni_cislo, cast_parcely, je_stavebni, vymera_metru_ctverecnich, ulice, cislo_popisne, katastralni_uzemi_id, bpej_id, list_vlastnictvi_id",
         &[&item.parcelni_cislo, &item.cast_parcely, &item.je_stavebni, &item.vymera_metru_ctverecnich, &item.ulice, &item.cislo_popisne, &item.katastralni_uzemi_id, &item.bpej_id, &item.list_vlastnictvi_id]
     ).await?;
-    Ok(rows)
+    Ok(ParcelaRow::from_row(&row))
 }
 
-pub async fn update_parcela_row(pool: Pool, item: ParcelaRow) -> Result<u64> {
-    let client = pool.get().await?;
-    let rows = client.execute(
+pub async fn update_parcela_row(
+    tx: &Transaction<'_>,
+    item: ParcelaRow,
+    if_match: &IfMatch,
+) -> Result<u64> {
+    check_version::<ParcelaRow>(tx, &[item.id], if_match).await?;
+    let rows = tx.execute(
         "UPDATE parcela SET parcelni_cislo = $2, cast_parcely = $3, je_stavebni = $4, vymera_metru_ctverecnich = $5, ulice = $6, cislo_popisne = $7, katastralni_uzemi_id = $8, bpej_id = $9, list_vlastnictvi_id = $10 WHERE id = $1",
         &[&item.id, &item.parcelni_cislo, &item.cast_parcely, &item.je_stavebni, &item.vymera_metru_ctverecnich, &item.ulice, &item.cislo_popisne, &item.katastralni_uzemi_id, &item.bpej_id, &item.list_vlastnictvi_id]
     ).await?;
     Ok(rows)
 }
 
-pub async fn delete_parcela_row(pool: Pool, id: i32) -> Result<u64> {
-    let client = pool.get().await?;
-    let rows = client
+pub async fn delete_parcela_row(tx: &Transaction<'_>, id: i32, if_match: &IfMatch) -> Result<u64> {
+    check_version::<ParcelaRow>(tx, &[id], if_match).await?;
+    let rows = tx
         .execute("DELETE FROM parcela WHERE id = $1", &[&id])
         .await?;
     Ok(rows)
 }
 
 // --- Rizeni ---
-pub async fn get_rizeni(pool: Pool) -> Result<Vec<Rizeni>> {
-    let client = pool.get().await?;
-    let rows = client
-        .query(
-            "SELECT id, rok, cislo_rizeni, typ_rizeni_id, predmet, poznamka FROM rizeni",
-            &[],
-        )
-        .await?;
-    Ok(rows
-        .iter()
-        .map(|row| Rizeni {
+impl Listable for Rizeni {
+    const TABLE: Table = Table {
+        name: "rizeni",
+        columns: &[
+            ("id", ColumnType::Int),
+            ("rok", ColumnType::Int),
+            ("cislo_rizeni", ColumnType::Int),
+            ("typ_rizeni_id", ColumnType::Int),
+            ("predmet", ColumnType::Text),
+            ("poznamka", ColumnType::Text),
+        ],
+        key: &["id"],
+        nullable: &["poznamka"],
+    };
+
+    fn from_row(row: &Row) -> Self {
+        Rizeni {
             id: row.get(0),
             rok: row.get(1),
             cislo_rizeni: row.get(2),
             typ_rizeni_id: row.get(3),
             predmet: row.get(4),
             poznamka: row.get(5),
-        })
-        .collect())
+        }
+    }
 }
 
-pub async fn create_rizeni(pool: Pool, item: NewRizeni) -> Result<u64> {
-    let client = pool.get().await?;
-    let rows = client.execute(
-        "INSERT INTO rizeni (rok, cislo_rizeni, typ_rizeni_id, predmet, poznamka) VALUES ($1, $2, $3, $4, $5)",
+pub async fn get_rizeni(pool: Pool, query: &ListQuery) -> Result<Page<Rizeni>> {
+    list_rows(pool, query).await
+}
+
+pub async fn create_rizeni(tx: &Transaction<'_>, item: NewRizeni) -> Result<Rizeni> {
+    let row = tx.query_one(
+        "INSERT INTO rizeni (rok, cislo_rizeni, typ_rizeni_id, predmet, poznamka) VALUES ($1, $2, $3, $4, $5) RETURNING id, rok, cislo_rizeni, typ_rizeni_id, predmet, poznamka",
         &[&item.rok, &item.cislo_rizeni, &item.typ_rizeni_id, &item.predmet, &item.poznamka]
     ).await?;
-    Ok(rows)
+    Ok(Rizeni::from_row(&row))
 }
 
-pub async fn update_rizeni(pool: Pool, item: Rizeni) -> Result<u64> {
-    let client = pool.get().await?;
-    let rows = client.execute(
+pub async fn update_rizeni(tx: &Transaction<'_>, item: Rizeni, if_match: &IfMatch) -> Result<u64> {
+    check_version::<Rizeni>(tx, &[item.id], if_match).await?;
+    let rows = tx.execute(
         "UPDATE rizeni SET rok = $2, cislo_rizeni = $3, typ_rizeni_id = $4, predmet = $5, poznamka = $6 WHERE id = $1",
         &[&item.id, &item.rok, &item.cislo_rizeni, &item.typ_rizeni_id, &item.predmet, &item.poznamka]
     ).await?;
     Ok(rows)
 }
 
-pub async fn delete_rizeni(pool: Pool, id: i32) -> Result<u64> {
-    let client = pool.get().await?;
-    let rows = client
+pub async fn delete_rizeni(tx: &Transaction<'_>, id: i32, if_match: &IfMatch) -> Result<u64> {
+    check_version::<Rizeni>(tx, &[id], if_match).await?;
+    let rows = tx
         .execute("DELETE FROM rizeni WHERE id = $1", &[&id])
         .await?;
     Ok(rows)
 }
 
 // --- Vlastnictvi ---
-pub async fn get_vlastnictvi(pool: Pool) -> Result<Vec<Vlastnictvi>> {
-    let client = pool.get().await?;
-    let rows = client
-        .query(
-            "SELECT parcela_id, majitel_id, podil_setin FROM vlastnictvi",
-            &[],
-        )
-        .await?;
-    Ok(rows
-        .iter()
-        .map(|row| Vlastnictvi {
+impl Listable for Vlastnictvi {
+    const TABLE: Table = Table {
+        name: "vlastnictvi",
+        columns: &[
+            ("parcela_id", ColumnType::Int),
+            ("majitel_id", ColumnType::Int),
+            ("podil_setin", ColumnType::Int),
+        ],
+        key: &["parcela_id", "majitel_id"],
+        nullable: &[],
+    };
+
+    fn from_row(row: &Row) -> Self {
+        Vlastnictvi {
             parcela_id: row.get(0),
             majitel_id: row.get(1),
             podil_setin: row.get(2),
-        })
-        .collect())
+        }
+    }
 }
 
-pub async fn create_vlastnictvi(pool: Pool, item: NewVlastnictvi) -> Result<u64> {
-    let client = pool.get().await?;
-    let rows = client
-        .execute(
-            "INSERT INTO vlastnictvi (parcela_id, majitel_id, podil_setin) VALUES ($1, $2, $3)",
+pub async fn get_vlastnictvi(pool: Pool, query: &ListQuery) -> Result<Page<Vlastnictvi>> {
+    list_rows(pool, query).await
+}
+
+pub async fn create_vlastnictvi(tx: &Transaction<'_>, item: NewVlastnictvi) -> Result<Vlastnictvi> {
+    let row = tx
+        .query_one(
+            "INSERT INTO vlastnictvi (parcela_id, majitel_id, podil_setin) VALUES ($1, $2, $3) RETURNING parcela_id, majitel_id, podil_setin",
             &[&item.parcela_id, &item.majitel_id, &item.podil_setin],
         )
         .await?;
-    Ok(rows)
+    Ok(Vlastnictvi::from_row(&row))
 }
 
-pub async fn update_vlastnictvi(pool: Pool, item: Vlastnictvi) -> Result<u64> {
-    let client = pool.get().await?;
-    let rows = client
+pub async fn update_vlastnictvi(
+    tx: &Transaction<'_>,
+    item: Vlastnictvi,
+    if_match: &IfMatch,
+) -> Result<u64> {
+    check_version::<Vlastnictvi>(tx, &[item.parcela_id, item.majitel_id], if_match).await?;
+    let rows = tx
         .execute(
             "UPDATE vlastnictvi SET podil_setin = $3 WHERE parcela_id = $1 AND majitel_id = $2",
             &[&item.parcela_id, &item.majitel_id, &item.podil_setin],
@@ -943,9 +1519,14 @@ pub async fn update_vlastnictvi(pool: Pool, item: Vlastnictvi) -> Result<u64> {
     Ok(rows)
 }
 
-pub async fn delete_vlastnictvi(pool: Pool, parcela_id: i32, majitel_id: i32) -> Result<u64> {
-    let client = pool.get().await?;
-    let rows = client
+pub async fn delete_vlastnictvi(
+    tx: &Transaction<'_>,
+    parcela_id: i32,
+    majitel_id: i32,
+    if_match: &IfMatch,
+) -> Result<u64> {
+    check_version::<Vlastnictvi>(tx, &[parcela_id, majitel_id], if_match).await?;
+    let rows = tx
         .execute(
             "DELETE FROM vlastnictvi WHERE parcela_id = $1 AND majitel_id = $2",
             &[&parcela_id, &majitel_id],
@@ -955,41 +1536,61 @@ pub async fn delete_vlastnictvi(pool: Pool, parcela_id: i32, majitel_id: i32) ->
 }
 
 // --- BremenoParcelaParcela ---
-pub async fn get_bremeno_parcela_parcela(pool: Pool) -> Result<Vec<BremenoParcelaParcela>> {
-    let client = pool.get().await?;
-    let rows = client
-        .query("SELECT parcela_id, parcela_povinna_id, popis, datum_zrizeni, datum_pravnich_ucinku FROM bremeno_parcela_parcela", &[])
-        .await?;
-    Ok(rows
-        .iter()
-        .map(|row| BremenoParcelaParcela {
+impl Listable for BremenoParcelaParcela {
+    const TABLE: Table = Table {
+        name: "bremeno_parcela_parcela",
+        columns: &[
+            ("parcela_id", ColumnType::Int),
+            ("parcela_povinna_id", ColumnType::Int),
+            ("popis", ColumnType::Text),
+            ("datum_zrizeni", ColumnType::Date),
+            ("datum_pravnich_ucinku", ColumnType::Date),
+        ],
+        key: &["parcela_id", "parcela_povinna_id"],
+        nullable: &[],
+    };
+
+    fn from_row(row: &Row) -> Self {
+        BremenoParcelaParcela {
             parcela_id: row.get(0),
             parcela_povinna_id: row.get(1),
             popis: row.get(2),
             datum_zrizeni: row.get(3),
             datum_pravnich_ucinku: row.get(4),
-        })
-        .collect())
+        }
+    }
 }
 
-pub async fn create_bremeno_parcela_parcela(
+pub async fn get_bremeno_parcela_parcela(
     pool: Pool,
+    query: &ListQuery,
+) -> Result<Page<BremenoParcelaParcela>> {
+    list_rows(pool, query).await
+}
+
+pub async fn create_bremeno_parcela_parcela(
+    tx: &Transaction<'_>,
     item: NewBremenoParcelaParcela,
-) -> Result<u64> {
-    let client = pool.get().await?;
-    let rows = client.execute(
-        "INSERT INTO bremeno_parcela_parcela (parcela_id, parcela_povinna_id, popis, datum_zrizeni, datum_pravnich_ucinku) VALUES ($1, $2, $3, $4, $5)",
+) -> Result<BremenoParcelaParcela> {
+    let row = tx.query_one(
+        "INSERT INTO bremeno_parcela_parcela (parcela_id, parcela_povinna_id, popis, datum_zrizeni, datum_pravnich_ucinku) VALUES ($1, $2, $3, $4, $5) RETURNING parcela_id, parcela_povinna_id, popis, datum_zrizeni, datum_pravnich_ucinku",
         &[&item.parcela_id, &item.parcela_povinna_id, &item.popis, &item.datum_zrizeni, &item.datum_pravnich_ucinku]
     ).await?;
-    Ok(rows)
+    Ok(BremenoParcelaParcela::from_row(&row))
 }
 
 pub async fn update_bremeno_parcela_parcela(
-    pool: Pool,
+    tx: &Transaction<'_>,
     item: BremenoParcelaParcela,
+    if_match: &IfMatch,
 ) -> Result<u64> {
-    let client = pool.get().await?;
-    let rows = client.execute(
+    check_version::<BremenoParcelaParcela>(
+        tx,
+        &[item.parcela_id, item.parcela_povinna_id],
+        if_match,
+    )
+    .await?;
+    let rows = tx.execute(
         "UPDATE bremeno_parcela_parcela SET popis = $3, datum_zrizeni = $4, datum_pravnich_ucinku = $5 WHERE parcela_id = $1 AND parcela_povinna_id = $2",
         &[&item.parcela_id, &item.parcela_povinna_id, &item.popis, &item.datum_zrizeni, &item.datum_pravnich_ucinku]
     ).await?;
@@ -997,12 +1598,13 @@ pub async fn update_bremeno_parcela_parcela(
 }
 
 pub async fn delete_bremeno_parcela_parcela(
-    pool: Pool,
+    tx: &Transaction<'_>,
     parcela_id: i32,
     parcela_povinna_id: i32,
+    if_match: &IfMatch,
 ) -> Result<u64> {
-    let client = pool.get().await?;
-    let rows = client
+    check_version::<BremenoParcelaParcela>(tx, &[parcela_id, parcela_povinna_id], if_match).await?;
+    let rows = tx
         .execute(
             "DELETE FROM bremeno_parcela_parcela WHERE parcela_id = $1 AND parcela_povinna_id = $2",
             &[&parcela_id, &parcela_povinna_id],
@@ -1012,41 +1614,61 @@ pub async fn delete_bremeno_parcela_parcela(
 }
 
 // --- BremenoParcelaMajitel ---
-pub async fn get_bremeno_parcela_majitel(pool: Pool) -> Result<Vec<BremenoParcelaMajitel>> {
-    let client = pool.get().await?;
-    let rows = client
-        .query("SELECT parcela_id, majitel_povinny_id, popis, datum_zrizeni, datum_pravnich_ucinku FROM bremeno_parcela_majitel", &[])
-        .await?;
-    Ok(rows
-        .iter()
-        .map(|row| BremenoParcelaMajitel {
+impl Listable for BremenoParcelaMajitel {
+    const TABLE: Table = Table {
+        name: "bremeno_parcela_majitel",
+        columns: &[
+            ("parcela_id", ColumnType::Int),
+            ("majitel_povinny_id", ColumnType::Int),
+            ("popis", ColumnType::Text),
+            ("datum_zrizeni", ColumnType::Date),
+            ("datum_pravnich_ucinku", ColumnType::Date),
+        ],
+        key: &["parcela_id", "majitel_povinny_id"],
+        nullable: &[],
+    };
+
+    fn from_row(row: &Row) -> Self {
+        BremenoParcelaMajitel {
             parcela_id: row.get(0),
             majitel_povinny_id: row.get(1),
             popis: row.get(2),
             datum_zrizeni: row.get(3),
             datum_pravnich_ucinku: row.get(4),
-        })
-        .collect())
+        }
+    }
 }
 
-pub async fn create_bremeno_parcela_majitel(
+pub async fn get_bremeno_parcela_majitel(
     pool: Pool,
+    query: &ListQuery,
+) -> Result<Page<BremenoParcelaMajitel>> {
+    list_rows(pool, query).await
+}
+
+pub async fn create_bremeno_parcela_majitel(
+    tx: &Transaction<'_>,
     item: NewBremenoParcelaMajitel,
-) -> Result<u64> {
-    let client = pool.get().await?;
-    let rows = client.execute(
-        "INSERT INTO bremeno_parcela_majitel (parcela_id, majitel_povinny_id, popis, datum_zrizeni, datum_pravnich_ucinku) VALUES ($1, $2, $3, $4, $5)",
+) -> Result<BremenoParcelaMajitel> {
+    let row = tx.query_one(
+        "INSERT INTO bremeno_parcela_majitel (parcela_id, majitel_povinny_id, popis, datum_zrizeni, datum_pravnich_ucinku) VALUES ($1, $2, $3, $4, $5) RETURNING parcela_id, majitel_povinny_id, popis, datum_zrizeni, datum_pravnich_ucinku",
         &[&item.parcela_id, &item.majitel_povinny_id, &item.popis, &item.datum_zrizeni, &item.datum_pravnich_ucinku]
     ).await?;
-    Ok(rows)
+    Ok(BremenoParcelaMajitel::from_row(&row))
 }
 
 pub async fn update_bremeno_parcela_majitel(
-    pool: Pool,
+    tx: &Transaction<'_>,
     item: BremenoParcelaMajitel,
+    if_match: &IfMatch,
 ) -> Result<u64> {
-    let client = pool.get().await?;
-    let rows = client.execute(
+    check_version::<BremenoParcelaMajitel>(
+        tx,
+        &[item.parcela_id, item.majitel_povinny_id],
+        if_match,
+    )
+    .await?;
+    let rows = tx.execute(
         "UPDATE bremeno_parcela_majitel SET popis = $3, datum_zrizeni = $4, datum_pravnich_ucinku = $5 WHERE parcela_id = $1 AND majitel_povinny_id = $2",
         &[&item.parcela_id, &item.majitel_povinny_id, &item.popis, &item.datum_zrizeni, &item.datum_pravnich_ucinku]
     ).await?;
@@ -1054,12 +1676,13 @@ pub async fn update_bremeno_parcela_majitel(
 }
 
 pub async fn delete_bremeno_parcela_majitel(
-    pool: Pool,
+    tx: &Transaction<'_>,
     parcela_id: i32,
     majitel_povinny_id: i32,
+    if_match: &IfMatch,
 ) -> Result<u64> {
-    let client = pool.get().await?;
-    let rows = client
+    check_version::<BremenoParcelaMajitel>(tx, &[parcela_id, majitel_povinny_id], if_match).await?;
+    let rows = tx
         .execute(
             "DELETE FROM bremeno_parcela_majitel WHERE parcela_id = $1 AND majitel_povinny_id = $2",
             &[&parcela_id, &majitel_povinny_id],
@@ -1069,34 +1692,47 @@ pub async fn delete_bremeno_parcela_majitel(
 }
 
 // --- Plomba ---
-pub async fn get_plomba(pool: Pool) -> Result<Vec<Plomba>> {
-    let client = pool.get().await?;
-    let rows = client
-        .query("SELECT rizeni_id, parcela_id FROM plomba", &[])
-        .await?;
-    Ok(rows
-        .iter()
-        .map(|row| Plomba {
+impl Listable for Plomba {
+    const TABLE: Table = Table {
+        name: "plomba",
+        columns: &[
+            ("rizeni_id", ColumnType::Int),
+            ("parcela_id", ColumnType::Int),
+        ],
+        key: &["rizeni_id", "parcela_id"],
+        nullable: &[],
+    };
+
+    fn from_row(row: &Row) -> Self {
+        Plomba {
             rizeni_id: row.get(0),
             parcela_id: row.get(1),
-        })
-        .collect())
+        }
+    }
 }
 
-pub async fn create_plomba(pool: Pool, item: NewPlomba) -> Result<u64> {
-    let client = pool.get().await?;
-    let rows = client
-        .execute(
-            "INSERT INTO plomba (rizeni_id, parcela_id) VALUES ($1, $2)",
+pub async fn get_plomba(pool: Pool, query: &ListQuery) -> Result<Page<Plomba>> {
+    list_rows(pool, query).await
+}
+
+pub async fn create_plomba(tx: &Transaction<'_>, item: NewPlomba) -> Result<Plomba> {
+    let row = tx
+        .query_one(
+            "INSERT INTO plomba (rizeni_id, parcela_id) VALUES ($1, $2) RETURNING rizeni_id, parcela_id",
             &[&item.rizeni_id, &item.parcela_id],
         )
         .await?;
-    Ok(rows)
+    Ok(Plomba::from_row(&row))
 }
 
-pub async fn delete_plomba(pool: Pool, rizeni_id: i32, parcela_id: i32) -> Result<u64> {
-    let client = pool.get().await?;
-    let rows = client
+pub async fn delete_plomba(
+    tx: &Transaction<'_>,
+    rizeni_id: i32,
+    parcela_id: i32,
+    if_match: &IfMatch,
+) -> Result<u64> {
+    check_version::<Plomba>(tx, &[rizeni_id, parcela_id], if_match).await?;
+    let rows = tx
         .execute(
             "DELETE FROM plomba WHERE rizeni_id = $1 AND parcela_id = $2",
             &[&rizeni_id, &parcela_id],
@@ -1106,38 +1742,54 @@ pub async fn delete_plomba(pool: Pool, rizeni_id: i32, parcela_id: i32) -> Resul
 }
 
 // --- RizeniOperaceRow ---
-pub async fn get_rizeni_operace_row(pool: Pool) -> Result<Vec<RizeniOperaceRow>> {
-    let client = pool.get().await?;
-    let rows = client
-        .query(
-            "SELECT rizeni_id, typ_operace_id, datum FROM rizeni_operace",
-            &[],
-        )
-        .await?;
-    Ok(rows
-        .iter()
-        .map(|row| RizeniOperaceRow {
+impl Listable for RizeniOperaceRow {
+    const TABLE: Table = Table {
+        name: "rizeni_operace",
+        columns: &[
+            ("rizeni_id", ColumnType::Int),
+            ("typ_operace_id", ColumnType::Int),
+            ("datum", ColumnType::Date),
+        ],
+        key: &["rizeni_id", "typ_operace_id"],
+        nullable: &[],
+    };
+
+    fn from_row(row: &Row) -> Self {
+        RizeniOperaceRow {
             rizeni_id: row.get(0),
             typ_operace_id: row.get(1),
             datum: row.get(2),
-        })
-        .collect())
+        }
+    }
 }
 
-pub async fn create_rizeni_operace_row(pool: Pool, item: NewRizeniOperaceRow) -> Result<u64> {
-    let client = pool.get().await?;
-    let rows = client
-        .execute(
-            "INSERT INTO rizeni_operace (rizeni_id, typ_operace_id, datum) VALUES ($1, $2, $3)",
+pub async fn get_rizeni_operace_row(
+    pool: Pool,
+    query: &ListQuery,
+) -> Result<Page<RizeniOperaceRow>> {
+    list_rows(pool, query).await
+}
+
+pub async fn create_rizeni_operace_row(
+    tx: &Transaction<'_>,
+    item: NewRizeniOperaceRow,
+) -> Result<RizeniOperaceRow> {
+    let row = tx
+        .query_one(
+            "INSERT INTO rizeni_operace (rizeni_id, typ_operace_id, datum) VALUES ($1, $2, $3) RETURNING rizeni_id, typ_operace_id, datum",
             &[&item.rizeni_id, &item.typ_operace_id, &item.datum],
         )
         .await?;
-    Ok(rows)
+    Ok(RizeniOperaceRow::from_row(&row))
 }
 
-pub async fn update_rizeni_operace_row(pool: Pool, item: RizeniOperaceRow) -> Result<u64> {
-    let client = pool.get().await?;
-    let rows = client
+pub async fn update_rizeni_operace_row(
+    tx: &Transaction<'_>,
+    item: RizeniOperaceRow,
+    if_match: &IfMatch,
+) -> Result<u64> {
+    check_version::<RizeniOperaceRow>(tx, &[item.rizeni_id, item.typ_operace_id], if_match).await?;
+    let rows = tx
         .execute(
             "UPDATE rizeni_operace SET datum = $3 WHERE rizeni_id = $1 AND typ_operace_id = $2",
             &[&item.rizeni_id, &item.typ_operace_id, &item.datum],
@@ -1147,12 +1799,13 @@ pub async fn update_rizeni_operace_row(pool: Pool, item: RizeniOperaceRow) -> Re
 }
 
 pub async fn delete_rizeni_operace_row(
-    pool: Pool,
+    tx: &Transaction<'_>,
     rizeni_id: i32,
     typ_operace_id: i32,
+    if_match: &IfMatch,
 ) -> Result<u64> {
-    let client = pool.get().await?;
-    let rows = client
+    check_version::<RizeniOperaceRow>(tx, &[rizeni_id, typ_operace_id], if_match).await?;
+    let rows = tx
         .execute(
             "DELETE FROM rizeni_operace WHERE rizeni_id = $1 AND typ_operace_id = $2",
             &[&rizeni_id, &typ_operace_id],
@@ -1162,47 +1815,397 @@ pub async fn delete_rizeni_operace_row(
 }
 
 // --- Ucast ---
-pub async fn get_ucast(pool: Pool) -> Result<Vec<Ucast>> {
-    let client = pool.get().await?;
-    let rows = client
-        .query(
-            "SELECT rizeni_id, ucastnik_rizeni_id, typ_ucastnika_id FROM ucast",
-            &[],
-        )
-        .await?;
-    Ok(rows
-        .iter()
-        .map(|row| Ucast {
+impl Listable for Ucast {
+    const TABLE: Table = Table {
+        name: "ucast",
+        columns: &[
+            ("rizeni_id", ColumnType::Int),
+            ("ucastnik_rizeni_id", ColumnType::Int),
+            ("typ_ucastnika_id", ColumnType::Int),
+        ],
+        key: &["rizeni_id", "ucastnik_rizeni_id", "typ_ucastnika_id"],
+        nullable: &[],
+    };
+
+    fn from_row(row: &Row) -> Self {
+        Ucast {
             rizeni_id: row.get(0),
             ucastnik_rizeni_id: row.get(1),
             typ_ucastnika_id: row.get(2),
-        })
-        .collect())
+        }
+    }
 }
 
-pub async fn create_ucast(pool: Pool, item: NewUcast) -> Result<u64> {
-    let client = pool.get().await?;
-    let rows = client.execute(
-        "INSERT INTO ucast (rizeni_id, ucastnik_rizeni_id, typ_ucastnika_id) VALUES ($1, $2, $3)",
+pub async fn get_ucast(pool: Pool, query: &ListQuery) -> Result<Page<Ucast>> {
+    list_rows(pool, query).await
+}
+
+pub async fn create_ucast(tx: &Transaction<'_>, item: NewUcast) -> Result<Ucast> {
+    let row = tx.query_one(
+        "INSERT INTO ucast (rizeni_id, ucastnik_rizeni_id, typ_ucastnika_id) VALUES ($1, $2, $3) RETURNING rizeni_id, ucastnik_rizeni_id, typ_ucastnika_id",
         &[&item.rizeni_id, &item.ucastnik_rizeni_id, &item.typ_ucastnika_id]
     ).await?;
-    Ok(rows)
+    Ok(Ucast::from_row(&row))
 }
 
 pub async fn delete_ucast(
-    pool: Pool,
+    tx: &Transaction<'_>,
     rizeni_id: i32,
     ucastnik_rizeni_id: i32,
     typ_ucastnika_id: i32,
+    if_match: &IfMatch,
+) -> Result<u64> {
+    check_version::<Ucast>(
+        tx,
+        &[rizeni_id, ucastnik_rizeni_id, typ_ucastnika_id],
+        if_match,
+    )
+    .await?;
+    let rows = tx.execute("DELETE FROM ucast WHERE rizeni_id = $1 AND ucastnik_rizeni_id = $2 AND typ_ucastnika_id = $3", &[&rizeni_id, &ucastnik_rizeni_id, &typ_ucastnika_id]).await?;
+    Ok(rows)
+}
+
+// --- NabyvaciTitul ---
+impl Listable for NabyvaciTitul {
+    const TABLE: Table = Table {
+        name: "nabyvaci_titul",
+        columns: &[
+            ("id", ColumnType::Int),
+            ("list_vlastnictvi_id", ColumnType::Int),
+            ("majitel_id", ColumnType::Int),
+            ("rizeni_id", ColumnType::Int),
+            ("druh", ColumnType::Text),
+            ("popis", ColumnType::Text),
+            ("datum_vyhotoveni", ColumnType::Date),
+            ("datum_pravnich_ucinku", ColumnType::Date),
+        ],
+        key: &["id"],
+        nullable: &[],
+    };
+
+    fn from_row(row: &Row) -> Self {
+        NabyvaciTitul {
+            id: row.get(0),
+            list_vlastnictvi_id: row.get(1),
+            majitel_id: row.get(2),
+            rizeni_id: row.get(3),
+            druh: row.get(4),
+            popis: row.get(5),
+            datum_vyhotoveni: row.get(6),
+            datum_pravnich_ucinku: row.get(7),
+        }
+    }
+}
+
+pub async fn get_nabyvaci_titul(pool: Pool, query: &ListQuery) -> Result<Page<NabyvaciTitul>> {
+    list_rows(pool, query).await
+}
+
+pub async fn create_nabyvaci_titul(
+    tx: &Transaction<'_>,
+    item: NewNabyvaciTitul,
+) -> Result<NabyvaciTitul> {
+    let row = tx
+        .query_one(
+            "INSERT INTO nabyvaci_titul (list_vlastnictvi_id, majitel_id, rizeni_id, druh, popis, datum_vyhotoveni, datum_pravnich_ucinku) VALUES ($1, $2, $3, $4, $5, $6, $7) RETURNING id, list_vlastnictvi_id, majitel_id, rizeni_id, druh, popis, datum_vyhotoveni, datum_pravnich_ucinku",
+            &[
+                &item.list_vlastnictvi_id,
+                &item.majitel_id,
+                &item.rizeni_id,
+                &item.druh,
+                &item.popis,
+                &item.datum_vyhotoveni,
+                &item.datum_pravnich_ucinku,
+            ],
+        )
+        .await?;
+    Ok(NabyvaciTitul::from_row(&row))
+}
+
+pub async fn update_nabyvaci_titul(
+    tx: &Transaction<'_>,
+    item: NabyvaciTitul,
+    if_match: &IfMatch,
+) -> Result<u64> {
+    check_version::<NabyvaciTitul>(tx, &[item.id], if_match).await?;
+    let rows = tx
+        .execute(
+            "UPDATE nabyvaci_titul SET list_vlastnictvi_id = $2, majitel_id = $3, rizeni_id = $4, druh = $5, popis = $6, datum_vyhotoveni = $7, datum_pravnich_ucinku = $8 WHERE id = $1",
+            &[
+                &item.id,
+                &item.list_vlastnictvi_id,
+                &item.majitel_id,
+                &item.rizeni_id,
+                &item.druh,
+                &item.popis,
+                &item.datum_vyhotoveni,
+                &item.datum_pravnich_ucinku,
+            ],
+        )
+        .await?;
+    Ok(rows)
+}
+
+pub async fn delete_nabyvaci_titul(
+    tx: &Transaction<'_>,
+    id: i32,
+    if_match: &IfMatch,
 ) -> Result<u64> {
+    check_version::<NabyvaciTitul>(tx, &[id], if_match).await?;
+    let rows = tx
+        .execute("DELETE FROM nabyvaci_titul WHERE id = $1", &[&id])
+        .await?;
+    Ok(rows)
+}
+
+// --- Users ---
+pub(crate) fn user_from_row(row: &tokio_postgres::Row) -> Result<User> {
+    let role: String = row.try_get("role")?;
+    Ok(User {
+        id: row.try_get("id")?,
+        username: row.try_get("username")?,
+        role: role.parse().map_err(AppError::Internal)?,
+        can_view_personal_data: row.try_get("can_view_personal_data")?,
+    })
+}
+
+/// Returns the user together with their bcrypt hash, used only for login.
+pub async fn get_user_credentials(pool: Pool, username: &str) -> Result<Option<(User, String)>> {
     let client = pool.get().await?;
-    let rows = client.execute("DELETE FROM ucast WHERE rizeni_id = $1 AND ucastnik_rizeni_id = $2 AND typ_ucastnika_id = $3", &[&rizeni_id, &ucastnik_rizeni_id, &typ_ucastnika_id]).await?;
+    let row = client
+        .query_opt(
+            "SELECT id, username, role, can_view_personal_data, password_hash FROM app_user WHERE username = $1",
+            &[&username],
+        )
+        .await?;
+    match row {
+        Some(row) => Ok(Some((user_from_row(&row)?, row.try_get("password_hash")?))),
+        None => Ok(None),
+    }
+}
+
+pub async fn get_users(pool: Pool) -> Result<Vec<User>> {
+    let client = pool.get().await?;
+    let rows = client
+        .query(
+            "SELECT id, username, role, can_view_personal_data FROM app_user ORDER BY id",
+            &[],
+        )
+        .await?;
+    rows.iter().map(user_from_row).collect()
+}
+
+pub async fn create_user(
+    pool: Pool,
+    actor: &User,
+    username: &str,
+    password_hash: &str,
+    role: Role,
+    can_view_personal_data: bool,
+) -> Result<u64> {
+    let mut client = pool.get().await?;
+    let tx = begin_audited(&mut client, actor).await?;
+    let rows = tx
+        .execute(
+            "INSERT INTO app_user (username, password_hash, role, can_view_personal_data) VALUES ($1, $2, $3, $4)",
+            &[&username, &password_hash, &role.as_str(), &can_view_personal_data],
+        )
+        .await?;
+    tx.commit().await?;
+    Ok(rows)
+}
+
+pub async fn update_user(
+    pool: Pool,
+    actor: &User,
+    id: i32,
+    password_hash: Option<&str>,
+    role: Option<Role>,
+    can_view_personal_data: Option<bool>,
+) -> Result<u64> {
+    let mut client = pool.get().await?;
+    let tx = begin_audited(&mut client, actor).await?;
+    let rows = tx
+        .execute(
+            "UPDATE app_user SET password_hash = COALESCE($2, password_hash), role = COALESCE($3, role), can_view_personal_data = COALESCE($4, can_view_personal_data) WHERE id = $1",
+            &[&id, &password_hash, &role.map(|r| r.as_str()), &can_view_personal_data],
+        )
+        .await?;
+    tx.commit().await?;
+    Ok(rows)
+}
+
+pub async fn delete_user(pool: Pool, actor: &User, id: i32) -> Result<u64> {
+    let mut client = pool.get().await?;
+    let tx = begin_audited(&mut client, actor).await?;
+    let rows = tx
+        .execute("DELETE FROM app_user WHERE id = $1", &[&id])
+        .await?;
+    tx.commit().await?;
     Ok(rows)
 }
 
+/// Creates the initial admin account, with access to personal data, when the
+/// user table is empty.
+pub async fn ensure_admin_user(pool: Pool, username: &str, password_hash: &str) -> Result<bool> {
+    let client = pool.get().await?;
+    let rows = client
+        .execute(
+            "INSERT INTO app_user (username, password_hash, role, can_view_personal_data) SELECT $1, $2, 'admin', true WHERE NOT EXISTS (SELECT 1 FROM app_user)",
+            &[&username, &password_hash],
+        )
+        .await?;
+    Ok(rows > 0)
+}
+
+// --- API tokens ---
+pub async fn get_api_tokens(pool: Pool) -> Result<Vec<ApiToken>> {
+    let client = pool.get().await?;
+    let rows = client
+        .query(
+            "SELECT t.id, t.name, t.user_id, u.username, t.created_at, t.last_used_at, t.revoked_at FROM api_token t JOIN app_user u ON u.id = t.user_id ORDER BY t.id",
+            &[],
+        )
+        .await?;
+    Ok(rows
+        .iter()
+        .map(|row| ApiToken {
+            id: row.get(0),
+            name: row.get(1),
+            user_id: row.get(2),
+            username: row.get(3),
+            created_at: row.get(4),
+            last_used_at: row.get(5),
+            revoked_at: row.get(6),
+        })
+        .collect())
+}
 
+/// Stores only the token's hash, returns the new token id.
+pub async fn create_api_token(
+    pool: Pool,
+    actor: &User,
+    item: &NewApiToken,
+    token_hash: &str,
+) -> Result<i32> {
+    let mut client = pool.get().await?;
+    let tx = begin_audited(&mut client, actor).await?;
+    let row = tx
+        .query_one(
+            "INSERT INTO api_token (name, user_id, token_hash) VALUES ($1, $2, $3) RETURNING id",
+            &[&item.name, &item.user_id, &token_hash],
+        )
+        .await?;
+    tx.commit().await?;
+    Ok(row.get(0))
+}
 
+pub async fn revoke_api_token(pool: Pool, actor: &User, id: i32) -> Result<u64> {
+    let mut client = pool.get().await?;
+    let tx = begin_audited(&mut client, actor).await?;
+    let rows = tx
+        .execute(
+            "UPDATE api_token SET revoked_at = now() WHERE id = $1 AND revoked_at IS NULL",
+            &[&id],
+        )
+        .await?;
+    tx.commit().await?;
+    Ok(rows)
+}
 
+/// Resolves a live token to its owner and records the use.
+pub async fn authenticate_api_token(pool: Pool, token_hash: &str) -> Result<Option<User>> {
+    let client = pool.get().await?;
+    let row = client
+        .query_opt(
+            "UPDATE api_token t SET last_used_at = now() FROM app_user u WHERE t.token_hash = $1 AND t.revoked_at IS NULL AND u.id = t.user_id RETURNING u.id, u.username, u.role, u.can_view_personal_data",
+            &[&token_hash],
+        )
+        .await?;
+    row.as_ref().map(user_from_row).transpose()
+}
 
+// --- Audit log ---
+pub async fn get_audit_log(pool: Pool, filter: &AuditFilter) -> Result<Page<AuditEntry>> {
+    let client = pool.get().await?;
 
+    let mut filters: Vec<(&str, &(dyn tokio_postgres::types::ToSql + Sync))> = Vec::new();
+    if let Some(entity) = &filter.entity {
+        filters.push(("entity =", entity));
+    }
+    if let Some(entity_key) = &filter.entity_key {
+        filters.push(("entity_key =", entity_key));
+    }
+    if let Some(user_id) = &filter.user_id {
+        filters.push(("user_id =", user_id));
+    }
+    if let Some(username) = &filter.username {
+        filters.push(("username =", username));
+    }
+    if let Some(from) = &filter.from {
+        filters.push(("at >=", from));
+    }
+    if let Some(to) = &filter.to {
+        filters.push(("at <", to));
+    }
+    let mut conditions = Vec::new();
+    let mut params = Vec::new();
+    for (condition, value) in filters {
+        params.push(value);
+        conditions.push(format!("{} ${}", condition, params.len()));
+    }
+    let where_clause = |conditions: &[String]| {
+        if conditions.is_empty() {
+            String::new()
+        } else {
+            format!(" WHERE {}", conditions.join(" AND "))
+        }
+    };
+
+    let total: i64 = client
+        .query_one(
+            &format!(
+                "SELECT count(*) FROM audit_log{}",
+                where_clause(&conditions)
+            ),
+            &params,
+        )
+        .await?
+        .get(0);
 
+    if let Some(after) = &filter.after {
+        params.push(after);
+        conditions.push(format!("id < ${}", params.len()));
+    }
+    params.push(&filter.limit);
+    let sql = format!(
+        "SELECT id, at, user_id, username, action, entity, entity_key, before, after \
+         FROM audit_log{} ORDER BY id DESC LIMIT ${}",
+        where_clause(&conditions),
+        params.len()
+    );
+    let rows = client.query(&sql, &params).await?;
+
+    let items: Vec<AuditEntry> = rows
+        .iter()
+        .map(|row| AuditEntry {
+            id: row.get(0),
+            at: row.get(1),
+            user_id: row.get(2),
+            username: row.get(3),
+            action: row.get(4),
+            entity: row.get(5),
+            entity_key: row.get(6),
+            before: row.get(7),
+            after: row.get(8),
+        })
+        .collect();
+    let next_cursor = (items.len() as i64 == filter.limit)
+        .then(|| items.last().map(|e| e.id.to_string()))
+        .flatten();
+    Ok(Page {
+        items,
+        total,
+        next_cursor,
+    })
+}
@@ -0,0 +1,48 @@
+use axum::{
+    Extension, Json,
+    extract::{Query, State},
+};
+use deadpool_postgres::Pool;
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+use crate::*;
+
+pub async fn api_token_handler(State(pool): State<Pool>) -> AppResult<Json<Vec<ApiToken>>> {
+    let result = get_api_tokens(pool).await?;
+    Ok(Json(result))
+}
+
+pub mod api_token_handler {
+    use super::*;
+
+    /// The plain token is only ever returned here; the server keeps its hash.
+    pub async fn create(
+        State(pool): State<Pool>,
+        Extension(actor): Extension<User>,
+        Json(item): Json<NewApiToken>,
+    ) -> AppResult<Json<Value>> {
+        item.validate()?;
+        let token = generate_api_token();
+        let id = create_api_token(pool, &actor, &item, &hash_api_token(&token)).await?;
+        Ok(Json(json!({ "id": id, "name": item.name, "token": token })))
+    }
+
+    #[derive(Debug, Deserialize)]
+    pub struct DeleteParams {
+        pub id: i32,
+    }
+
+    /// Revokes rather than deletes, so the token stays listed with its history.
+    pub async fn delete(
+        State(pool): State<Pool>,
+        Extension(actor): Extension<User>,
+        Query(params): Query<DeleteParams>,
+    ) -> AppResult<Json<Value>> {
+        let result = revoke_api_token(pool, &actor, params.id).await?;
+        if result == 0 {
+            return Err(AppError::not_found());
+        }
+        Ok(Json(json!({ "rows_affected": result })))
+    }
+}
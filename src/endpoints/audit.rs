@@ -0,0 +1,87 @@
+use axum::{
+    Json,
+    extract::{Query, State},
+    http::HeaderMap,
+};
+use chrono::{DateTime, Utc};
+use deadpool_postgres::Pool;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+use crate::*;
+
+#[derive(Debug, Deserialize)]
+pub struct AuditParams {
+    /// Table name, e.g. `vlastnictvi`
+    pub entity: Option<String>,
+    /// Key values in the table's key column order, comma separated, e.g. `12,7`
+    pub key: Option<String>,
+    pub user_id: Option<i32>,
+    pub username: Option<String>,
+    /// Inclusive lower bound, RFC 3339
+    pub from: Option<DateTime<Utc>>,
+    /// Exclusive upper bound, RFC 3339
+    pub to: Option<DateTime<Utc>>,
+    pub limit: Option<i64>,
+    pub after: Option<i64>,
+}
+
+impl AuditParams {
+    fn into_filter(self) -> Result<AuditFilter, String> {
+        let limit = self.limit.unwrap_or(DEFAULT_LIMIT);
+        if !(1..=MAX_LIMIT).contains(&limit) {
+            return Err(format!("limit must be between 1 and {}", MAX_LIMIT));
+        }
+
+        let key_columns = match &self.entity {
+            Some(entity) => {
+                Some(audited_key(entity).ok_or_else(|| format!("Unknown entity '{}'", entity))?)
+            }
+            None => None,
+        };
+        let entity_key = match (&self.key, key_columns) {
+            (None, _) => None,
+            (Some(_), None) => return Err("key requires entity".to_string()),
+            (Some(raw), Some(columns)) => {
+                let values: Vec<i32> = raw
+                    .split(',')
+                    .map(|v| v.trim().parse())
+                    .collect::<Result<_, _>>()
+                    .map_err(|_| format!("Invalid key '{}'", raw))?;
+                if values.len() != columns.len() {
+                    return Err(format!("key must have {} values", columns.len()));
+                }
+                let object: Map<String, Value> = columns
+                    .iter()
+                    .zip(values)
+                    .map(|(column, value)| (column.to_string(), Value::from(value)))
+                    .collect();
+                Some(Value::Object(object))
+            }
+        };
+
+        Ok(AuditFilter {
+            entity: self.entity,
+            entity_key,
+            user_id: self.user_id,
+            username: self.username,
+            from: self.from,
+            to: self.to,
+            limit,
+            after: self.after,
+        })
+    }
+}
+
+/// Who changed what and when, newest first. Paged like the list endpoints,
+/// with `X-Total-Count` and `X-Next-Cursor` (pass it back as `after`).
+pub async fn audit_handler(
+    State(pool): State<Pool>,
+    access: PersonalDataAccess,
+    Query(params): Query<AuditParams>,
+) -> AppResult<(HeaderMap, Json<Vec<AuditEntry>>)> {
+    let filter = params.into_filter().map_err(AppError::BadRequest)?;
+    let mut page = get_audit_log(pool, &filter).await?;
+    access.shape_all(&mut page.items);
+    Ok((page.headers(), Json(page.items)))
+}
@@ -1,77 +1,200 @@
 use axum::{
-    Extension,
-    extract::{Query, State},
-    http::{HeaderMap, HeaderValue, StatusCode},
-    response::IntoResponse,
+    Extension, Form, Json,
+    extract::{ConnectInfo, FromRequest, Query, Request, State},
+    http::{HeaderMap, HeaderValue, StatusCode, header::CONTENT_TYPE},
+    response::{IntoResponse, Response},
 };
 use deadpool_postgres::Pool;
-use serde::Deserialize;
-use rand::{distr::Alphanumeric, Rng};
-use std::time::Instant;
+use rand::{Rng, distr::Alphanumeric};
+use serde::{Deserialize, de::DeserializeOwned};
+use std::net::{IpAddr, SocketAddr};
+use tracing::warn;
 
-use crate::AppState;
+use crate::{
+    AppError, AppResult, AppState, SESSION_COOKIE, Session, get_user_credentials, session_cookie,
+};
+
+// Verified against when the username does not exist so that unknown users
+// take as long to reject as wrong passwords.
+const DUMMY_HASH: &str = "$2b$12$rgOkHM0IWEmHYTidLt2WmeQANUGlG1wJxwSeoFX/XPltU/8okgKW6";
 
 #[derive(Debug, Deserialize)]
 pub struct AuthenticateParams {
+    pub username: String,
     pub password: String,
 }
 
+/// Request body accepted as either JSON or `application/x-www-form-urlencoded`,
+/// chosen by the `Content-Type` header.
+pub struct JsonOrForm<T>(pub T);
+
+impl<S, T> FromRequest<S> for JsonOrForm<T>
+where
+    S: Send + Sync,
+    T: DeserializeOwned,
+{
+    type Rejection = AppError;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let is_form = req
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.starts_with("application/x-www-form-urlencoded"));
+
+        if is_form {
+            let Form(value) = Form::<T>::from_request(req, state)
+                .await
+                .map_err(|e| AppError::BadRequest(e.body_text()))?;
+            Ok(JsonOrForm(value))
+        } else {
+            let Json(value) = Json::<T>::from_request(req, state)
+                .await
+                .map_err(|e| AppError::BadRequest(e.body_text()))?;
+            Ok(JsonOrForm(value))
+        }
+    }
+}
+
+pub async fn post_authenticate(
+    State(pool): State<Pool>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    state: Extension<AppState>,
+    JsonOrForm(params): JsonOrForm<AuthenticateParams>,
+) -> Response {
+    throttled_authenticate(pool, &state, addr.ip(), params).await
+}
+
+/// Legacy login with credentials in the query string, only routed when the
+/// server runs with `--allow-get-auth`.
 pub async fn get_authenticate(
-    State(_pool): State<Pool>,
+    State(pool): State<Pool>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
     Query(params): Query<AuthenticateParams>,
     state: Extension<AppState>,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
+) -> Response {
+    throttled_authenticate(pool, &state, addr.ip(), params).await
+}
+
+/// Runs `authenticate` behind the login throttle. Throttled attempts are
+/// refused before any bcrypt work is done.
+///
+/// Log lines have a fixed `auth failure:` / `auth blocked:` / `auth lockout:`
+/// prefix with `ip=` first, e.g. for a fail2ban filter like
+/// `auth failure: ip=<HOST> `.
+async fn throttled_authenticate(
+    pool: Pool,
+    state: &AppState,
+    ip: IpAddr,
+    params: AuthenticateParams,
+) -> Response {
+    let throttle = &state.login_throttle;
+
+    if let Err(throttled) = throttle.check(ip) {
+        // Round up so clients never retry a moment too early
+        let wait = throttled.retry_after();
+        let retry_after = (wait.as_secs() + u64::from(wait.subsec_nanos() > 0)).max(1);
+        warn!(
+            "auth blocked: ip={} reason={} retry_after={}",
+            ip,
+            throttled.reason(),
+            retry_after
+        );
+        return AppError::TooManyRequests {
+            retry_after_secs: retry_after,
+        }
+        .into_response();
+    }
+
+    let username = params.username.clone();
+    match authenticate(pool, state, params).await {
+        Ok(response) => {
+            throttle.record_success(ip);
+            response.into_response()
+        }
+        Err(e @ AppError::Unauthorized(_)) => {
+            warn!("auth failure: ip={} user={:?}", ip, username);
+            if let Some(lockout) = throttle.record_failure(ip) {
+                warn!("auth lockout: ip={} duration={}", ip, lockout.as_secs());
+            }
+            e.into_response()
+        }
+        Err(e) => e.into_response(),
+    }
+}
+
+async fn authenticate(
+    pool: Pool,
+    state: &AppState,
+    params: AuthenticateParams,
+) -> AppResult<impl IntoResponse + use<>> {
+    let credentials = get_user_credentials(pool, &params.username).await?;
+
     let password = params.password.clone();
-    let hashed = state.password.clone();
+    let hashed = credentials
+        .as_ref()
+        .map(|(_, hash)| hash.clone())
+        .unwrap_or_else(|| DUMMY_HASH.to_string());
 
     let verify_res =
         tokio::task::spawn_blocking(move || bcrypt::verify(password.as_str(), hashed.as_str()))
-            .await
-            .map_err(|e| {
-                (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    format!("Task join error: {}", e),
-                )
-            })?
-            .map_err(|e| {
-                (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    format!("Bcrypt error: {}", e),
-                )
-            })?;
-
-    if verify_res {
-        // Generate random session ID
-        let session_id: String = rand::rng()
-            .sample_iter(&Alphanumeric)
-            .take(32)
-            .map(char::from)
-            .collect();
-
-        // Store session in AppState
-        {
-            let mut sessions = state.sessions.write().map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Lock error".to_string()))?;
-            sessions.insert(session_id.clone(), Instant::now());
-        }
+            .await?
+            .map_err(|e| AppError::Internal(format!("Bcrypt error: {}", e)))?;
 
-        let mut headers = HeaderMap::new();
-        // Set cookie with HttpOnly flag; path=/ so it's sent for all endpoints
-        // Max-Age set to 3600 seconds (1 hour)
-        let cookie_val = format!(
-            "katastr_session={}; Max-Age=3600; Path=/; HttpOnly",
-            session_id
-        );
-        headers.insert(
-            axum::http::header::SET_COOKIE,
-            HeaderValue::from_str(&cookie_val).map_err(|e| {
-                (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    format!("Header error: {}", e),
-                )
-            })?,
-        );
-        Ok((StatusCode::OK, headers))
-    } else {
-        Err((StatusCode::UNAUTHORIZED, "Invalid hash".to_string()))
+    let user = match credentials {
+        Some((user, _)) if verify_res => user,
+        _ => return Err(AppError::Unauthorized("Invalid credentials".to_string())),
+    };
+
+    // Generate random session ID
+    let session_id: String = rand::rng()
+        .sample_iter(&Alphanumeric)
+        .take(32)
+        .map(char::from)
+        .collect();
+
+    // Store session in the configured session store
+    state
+        .sessions
+        .insert(&session_id, Session::new(user))
+        .await
+        .map_err(|e| AppError::Internal(format!("Session store error: {}", e)))?;
+
+    let mut headers = HeaderMap::new();
+    // Set cookie with HttpOnly flag; path=/ so it's sent for all endpoints
+    // Max-Age matches the server-side absolute session lifetime
+    let cookie_val = format!(
+        "{}={}; Max-Age={}; Path=/; HttpOnly",
+        SESSION_COOKIE,
+        session_id,
+        state.session_max_age.as_secs()
+    );
+    headers.insert(
+        axum::http::header::SET_COOKIE,
+        HeaderValue::from_str(&cookie_val)
+            .map_err(|e| AppError::Internal(format!("Header error: {}", e)))?,
+    );
+    Ok((StatusCode::OK, headers))
+}
+
+pub async fn post_logout(
+    headers: HeaderMap,
+    state: Extension<AppState>,
+) -> AppResult<impl IntoResponse> {
+    if let Some(session_id) = session_cookie(&headers) {
+        state
+            .sessions
+            .remove(&session_id)
+            .await
+            .map_err(|e| AppError::Internal(format!("Session store error: {}", e)))?;
     }
+
+    // Expire the cookie on the client as well
+    let mut response_headers = HeaderMap::new();
+    response_headers.insert(
+        axum::http::header::SET_COOKIE,
+        HeaderValue::from_str(&format!("{}=; Max-Age=0; Path=/; HttpOnly", SESSION_COOKIE))
+            .map_err(|e| AppError::Internal(format!("Header error: {}", e)))?,
+    );
+    Ok((StatusCode::NO_CONTENT, response_headers))
 }
@@ -0,0 +1,347 @@
+use axum::{Extension, Json, extract::State};
+use deadpool_postgres::{Pool, Transaction};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+use crate::*;
+
+/// Largest number of operations in one batch; they all hold their row locks
+/// until the transaction ends.
+pub const MAX_BATCH_OPERATIONS: usize = 500;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BatchOp {
+    Create,
+    Update,
+    Delete,
+}
+
+/// One write of a batch.
+///
+/// Anywhere in `body` or `key`, `{"$ref": "name.field"}` stands for `field` of
+/// the row created or updated by the earlier operation whose `ref` is `name`.
+/// Every operation can also be referred to by its index, e.g. `{"$ref": "0.id"}`,
+/// so a `ref` may not be a number.
+#[derive(Debug, Deserialize)]
+pub struct BatchOperation {
+    pub op: BatchOp,
+    /// Table name, as in `/audit`, e.g. `rizeni` or `parcela`
+    pub entity: String,
+    #[serde(rename = "ref")]
+    pub reference: Option<String>,
+    /// The row to create, or the full row to update
+    #[serde(default)]
+    pub body: Value,
+    /// Key columns of the row to delete, e.g. `{"parcela_id": 1, "majitel_id": 2}`
+    #[serde(default)]
+    pub key: Value,
+    /// Expected row version for `update` and `delete`, as sent in `If-Match`
+    pub if_match: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BatchRequest {
+    pub operations: Vec<BatchOperation>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BatchResult {
+    pub op: BatchOp,
+    pub entity: String,
+    #[serde(rename = "ref", skip_serializing_if = "Option::is_none")]
+    pub reference: Option<String>,
+    /// The created row
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub item: Option<Value>,
+    pub rows_affected: u64,
+}
+
+/// Outcome of one operation: the row later operations may refer to, and the
+/// row shown to the client, masked as the endpoints would.
+struct Written {
+    row: Option<Value>,
+    item: Option<Value>,
+    rows_affected: u64,
+}
+
+fn parse<T: DeserializeOwned>(body: Value) -> AppResult<T> {
+    serde_json::from_value(body).map_err(|e| AppError::BadRequest(format!("Invalid body: {}", e)))
+}
+
+/// Key values of `table` out of an object holding its key columns.
+fn key_values(table: &Table, key: &Value) -> AppResult<Vec<i32>> {
+    table
+        .key
+        .iter()
+        .map(|column| {
+            key.get(column)
+                .and_then(Value::as_i64)
+                .and_then(|v| i32::try_from(v).ok())
+                .ok_or_else(|| AppError::BadRequest(format!("key requires integer {}", column)))
+        })
+        .collect()
+}
+
+/// Checks that a new `ref` is neither a number nor taken by an earlier operation.
+fn check_reference(name: &str, rows: &HashMap<String, Value>) -> AppResult<()> {
+    // Numbers refer to operations by index
+    if !name.is_empty() && name.chars().all(|c| c.is_ascii_digit()) {
+        return Err(AppError::BadRequest(format!(
+            "ref '{}' must not be a number",
+            name
+        )));
+    }
+    if rows.contains_key(name) {
+        return Err(AppError::BadRequest(format!("Duplicate ref '{}'", name)));
+    }
+    Ok(())
+}
+
+/// Replaces every `{"$ref": "name.field"}` in `value` by the referenced field.
+fn resolve(value: Value, rows: &HashMap<String, Value>) -> AppResult<Value> {
+    match value {
+        Value::Object(map) if map.len() == 1 && map.contains_key("$ref") => {
+            let path = map["$ref"].as_str().unwrap_or_default();
+            path.rsplit_once('.')
+                .and_then(|(name, field)| rows.get(name)?.get(field))
+                .cloned()
+                .ok_or_else(|| AppError::BadRequest(format!("Unresolved reference '{}'", path)))
+        }
+        Value::Object(map) => map
+            .into_iter()
+            .map(|(k, v)| Ok((k, resolve(v, rows)?)))
+            .collect::<AppResult<Map<_, _>>>()
+            .map(Value::Object),
+        Value::Array(items) => items
+            .into_iter()
+            .map(|v| resolve(v, rows))
+            .collect::<AppResult<Vec<_>>>()
+            .map(Value::Array),
+        other => Ok(other),
+    }
+}
+
+/// Link tables whose columns are all key columns have nothing to update.
+async fn no_update<T>(_tx: &Transaction<'_>, _item: T, _if_match: &IfMatch) -> AppResult<u64> {
+    Err(AppError::BadRequest(
+        "This entity cannot be updated; delete and create it instead".to_string(),
+    ))
+}
+
+macro_rules! batch_entities {
+    ($($struct:ident, $new_struct:ident, $create_fn:ident, $update_fn:ident);+ $(;)?) => {
+        /// Runs one operation on the entity whose table is `entity`, with the
+        /// same validation as the entity's own endpoints.
+        async fn write(
+            tx: &Transaction<'_>,
+            access: &PersonalDataAccess,
+            op: BatchOp,
+            entity: &str,
+            body: Value,
+            key: Value,
+            if_match: &IfMatch,
+        ) -> AppResult<Written> {
+            $(
+                if entity == <$struct as Listable>::TABLE.name {
+                    let written = match op {
+                        BatchOp::Create => {
+                            let item = parse::<$new_struct>(body)?.validated()?;
+                            let created = $create_fn(tx, item).await?;
+                            Written {
+                                row: Some(serde_json::to_value(&created)?),
+                                item: Some(serde_json::to_value(access.shape(created))?),
+                                rows_affected: 1,
+                            }
+                        }
+                        BatchOp::Update => {
+                            let table = <$struct as Listable>::TABLE;
+                            let item = parse::<$struct>(body)?.validated()?;
+                            let key = key_values(&table, &serde_json::to_value(&item)?)?;
+                            let rows_affected = $update_fn(tx, item, if_match).await?;
+                            // Later references see the row as stored, with the
+                            // columns the database derives
+                            let updated = get_by_key_in::<$struct>(tx, &key).await?;
+                            Written {
+                                row: updated.map(|u| serde_json::to_value(&u)).transpose()?,
+                                item: None,
+                                rows_affected,
+                            }
+                        }
+                        BatchOp::Delete => {
+                            let key = key_values(&<$struct as Listable>::TABLE, &key)?;
+                            Written {
+                                row: None,
+                                item: None,
+                                rows_affected: delete_by_key::<$struct>(tx, &key, if_match).await?,
+                            }
+                        }
+                    };
+                    if written.rows_affected == 0 {
+                        return Err(AppError::not_found());
+                    }
+                    return Ok(written);
+                }
+            )+
+            Err(AppError::BadRequest(format!("Unknown entity '{}'", entity)))
+        }
+    };
+}
+
+batch_entities!(
+    Majitel, NewMajitel, create_majitel, update_majitel;
+    Kraj, NewKraj, create_kraj, update_kraj;
+    Okres, NewOkres, create_okres, update_okres;
+    Obec, NewObec, create_obec, update_obec;
+    KatastralniUzemi, NewKatastralniUzemi, create_katastralni_uzemi, update_katastralni_uzemi;
+    Bpej, NewBpej, create_bpej, update_bpej;
+    TypRizeni, NewTypRizeni, create_typ_rizeni, update_typ_rizeni;
+    TypOperace, NewTypOperace, create_typ_operace, update_typ_operace;
+    TypUcastnika, NewTypUcastnika, create_typ_ucastnika, update_typ_ucastnika;
+    UcastnikRizeni, NewUcastnikRizeni, create_ucastnik_rizeni, update_ucastnik_rizeni;
+    ListVlastnictvi, NewListVlastnictvi, create_list_vlastnictvi, update_list_vlastnictvi;
+    ParcelaRow, NewParcelaRow, create_parcela_row, update_parcela_row;
+    Rizeni, NewRizeni, create_rizeni, update_rizeni;
+    Vlastnictvi, NewVlastnictvi, create_vlastnictvi, update_vlastnictvi;
+    BremenoParcelaParcela, NewBremenoParcelaParcela, create_bremeno_parcela_parcela, update_bremeno_parcela_parcela;
+    BremenoParcelaMajitel, NewBremenoParcelaMajitel, create_bremeno_parcela_majitel, update_bremeno_parcela_majitel;
+    Plomba, NewPlomba, create_plomba, no_update;
+    RizeniOperaceRow, NewRizeniOperaceRow, create_rizeni_operace_row, update_rizeni_operace_row;
+    Ucast, NewUcast, create_ucast, no_update;
+    NabyvaciTitul, NewNabyvaciTitul, create_nabyvaci_titul, update_nabyvaci_titul;
+);
+
+/// Runs create, update and delete operations across the CRUD entities in order,
+/// in one transaction: either all of them take effect or none does. A failing
+/// operation is reported with its index as `operation`.
+pub async fn batch_handler(
+    State(pool): State<Pool>,
+    Extension(actor): Extension<User>,
+    access: PersonalDataAccess,
+    Json(request): Json<BatchRequest>,
+) -> AppResult<Json<Vec<BatchResult>>> {
+    if request.operations.len() > MAX_BATCH_OPERATIONS {
+        return Err(AppError::BadRequest(format!(
+            "A batch may have at most {} operations",
+            MAX_BATCH_OPERATIONS
+        )));
+    }
+
+    let mut client = pool.get().await?;
+    let tx = begin_audited(&mut client, &actor).await?;
+    let mut rows: HashMap<String, Value> = HashMap::new();
+    let mut results = Vec::with_capacity(request.operations.len());
+
+    for (index, operation) in request.operations.into_iter().enumerate() {
+        let result: AppResult<BatchResult> = async {
+            // The batch route itself only needs a clerk; code lists need more
+            if actor.role < write_role(&format!("/{}", operation.entity)) {
+                return Err(AppError::Forbidden("Insufficient role".to_string()));
+            }
+            if let Some(name) = &operation.reference {
+                check_reference(name, &rows)?;
+            }
+            let if_match = match &operation.if_match {
+                Some(tags) => IfMatch::parse([tags.as_str()]).map_err(AppError::BadRequest)?,
+                None => IfMatch::Absent,
+            };
+            let body = resolve(operation.body, &rows)?;
+            let key = resolve(operation.key, &rows)?;
+
+            let written = write(
+                &tx,
+                &access,
+                operation.op,
+                &operation.entity,
+                body,
+                key,
+                &if_match,
+            )
+            .await?;
+
+            if let Some(row) = written.row {
+                if let Some(name) = &operation.reference {
+                    rows.insert(name.clone(), row.clone());
+                }
+                rows.insert(index.to_string(), row);
+            }
+            Ok(BatchResult {
+                op: operation.op,
+                entity: operation.entity,
+                reference: operation.reference,
+                item: written.item,
+                rows_affected: written.rows_affected,
+            })
+        }
+        .await;
+
+        // Returning early drops the transaction, rolling back every earlier operation
+        results.push(result.map_err(|e| AppError::Batch {
+            operation: index,
+            error: Box::new(e),
+        })?);
+    }
+
+    tx.commit().await?;
+    Ok(Json(results))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn rows() -> HashMap<String, Value> {
+        let rizeni = json!({ "id": 7, "rok": 2024 });
+        HashMap::from([
+            ("rizeni".to_string(), rizeni.clone()),
+            ("0".to_string(), rizeni),
+        ])
+    }
+
+    fn bad_request(result: AppResult<impl std::fmt::Debug>) -> String {
+        match result {
+            Err(AppError::BadRequest(message)) => message,
+            other => panic!("expected a bad request, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn resolves_references_anywhere_in_the_value() {
+        let body = json!({
+            "rizeni_id": { "$ref": "rizeni.id" },
+            "roky": [{ "$ref": "0.rok" }, 2023],
+            "poznamka": { "$ref": "rizeni.id", "jina": 1 },
+        });
+        let resolved = resolve(body, &rows()).unwrap();
+        assert_eq!(
+            resolved,
+            json!({
+                "rizeni_id": 7,
+                "roky": [2024, 2023],
+                "poznamka": { "$ref": "rizeni.id", "jina": 1 },
+            })
+        );
+    }
+
+    #[test]
+    fn reports_unresolved_references() {
+        for path in ["majitel.id", "rizeni.cislo", "rizeni", "1.id"] {
+            let message = bad_request(resolve(json!({ "$ref": path }), &rows()));
+            assert_eq!(message, format!("Unresolved reference '{}'", path));
+        }
+        let message = bad_request(resolve(json!({ "$ref": 5 }), &rows()));
+        assert_eq!(message, "Unresolved reference ''");
+    }
+
+    #[test]
+    fn rejects_numeric_and_duplicate_refs() {
+        let message = bad_request(check_reference("12", &rows()));
+        assert_eq!(message, "ref '12' must not be a number");
+        let message = bad_request(check_reference("rizeni", &rows()));
+        assert_eq!(message, "Duplicate ref 'rizeni'");
+        assert!(check_reference("rizeni2", &rows()).is_ok());
+        assert!(check_reference("", &rows()).is_ok());
+    }
+}
@@ -0,0 +1,395 @@
+use axum::{
+    Extension, Json,
+    body::Body,
+    extract::{Path, Query, State},
+    http::{HeaderMap, header::CONTENT_TYPE},
+};
+use deadpool_postgres::{Pool, Transaction};
+use futures_util::StreamExt;
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use serde_json::Value;
+
+use crate::*;
+
+/// Largest request body accepted, in bytes.
+pub const MAX_BULK_BODY_BYTES: usize = 256 * 1024 * 1024;
+
+/// Rows sent to the database per `COPY`. When a chunk fails there, its rows
+/// are inserted one by one instead, so only the offending ones are rejected.
+const CHUNK_ROWS: usize = 1000;
+
+/// Rejected rows listed in a report, the first ones in processing order;
+/// `rejected` counts all of them.
+const MAX_REPORTED_ERRORS: usize = 1000;
+
+#[derive(Debug, Deserialize)]
+pub struct BulkParams {
+    /// Import nothing if any row is rejected
+    #[serde(default)]
+    pub atomic: bool,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct BulkReport {
+    pub inserted: u64,
+    pub rejected: usize,
+    pub errors: Vec<RowError>,
+}
+
+/// Newline delimited JSON is read as it arrives; anything else must be a JSON array.
+fn is_ndjson(headers: &HeaderMap) -> bool {
+    headers
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| {
+            [
+                "application/x-ndjson",
+                "application/ndjson",
+                "application/jsonl",
+            ]
+            .iter()
+            .any(|t| v.starts_with(t))
+        })
+}
+
+fn too_large() -> AppError {
+    AppError::PayloadTooLarge(format!(
+        "Request body exceeds {} bytes",
+        MAX_BULK_BODY_BYTES
+    ))
+}
+
+/// Deserializes, normalizes and validates one record as a `New*` model `N`
+/// for `T`'s table.
+fn prepare<T: Listable, N: DeserializeOwned + Serialize + Validate>(
+    record: Value,
+) -> AppResult<InsertRow> {
+    let item = serde_json::from_value::<N>(record)
+        .map_err(|e| AppError::BadRequest(format!("Invalid record: {}", e)))?
+        .validated()?;
+    match serde_json::to_value(item)? {
+        Value::Object(object) => T::TABLE.insert_row(&object).map_err(AppError::BadRequest),
+        _ => Err(AppError::Internal(
+            "Model did not serialize to an object".to_string(),
+        )),
+    }
+}
+
+struct Import<'a, 't> {
+    tx: &'a Transaction<'t>,
+    table: &'static Table,
+    rows: Vec<usize>,
+    chunk: Vec<InsertRow>,
+    /// Row, parcel and owner of each share inserted, kept when importing
+    /// `vlastnictvi` so `reject_unbalanced` can take them out again
+    shares: Option<Vec<(usize, i32, i32)>>,
+    report: BulkReport,
+}
+
+impl<'a, 't> Import<'a, 't> {
+    fn new(tx: &'a Transaction<'t>, table: &'static Table) -> Self {
+        Import {
+            tx,
+            table,
+            rows: Vec::with_capacity(CHUNK_ROWS),
+            chunk: Vec::with_capacity(CHUNK_ROWS),
+            shares: (table.name == Vlastnictvi::TABLE.name).then(Vec::new),
+            report: BulkReport::default(),
+        }
+    }
+
+    fn reject(&mut self, row: usize, error: AppError) {
+        self.report.rejected += 1;
+        if self.report.errors.len() < MAX_REPORTED_ERRORS {
+            self.report.errors.push(RowError { row, error });
+        }
+    }
+
+    fn inserted(&mut self, row: usize, record: &InsertRow) {
+        self.report.inserted += 1;
+        if let Some(shares) = &mut self.shares
+            && let (Some(parcela_id), Some(majitel_id)) = (
+                record.int(self.table, "parcela_id"),
+                record.int(self.table, "majitel_id"),
+            )
+        {
+            shares.push((row, parcela_id, majitel_id));
+        }
+    }
+
+    async fn add(&mut self, row: usize, record: AppResult<InsertRow>) -> AppResult<()> {
+        match record {
+            Ok(record) => {
+                self.rows.push(row);
+                self.chunk.push(record);
+                if self.chunk.len() >= CHUNK_ROWS {
+                    self.flush().await?;
+                }
+            }
+            Err(error) => self.reject(row, error),
+        }
+        Ok(())
+    }
+
+    /// Copies the pending chunk. Rows the database rejects (constraint
+    /// violations, bad values) are reported; any other error ends the import.
+    async fn flush(&mut self) -> AppResult<()> {
+        if self.chunk.is_empty() {
+            return Ok(());
+        }
+        let rows = std::mem::take(&mut self.rows);
+        let chunk = std::mem::take(&mut self.chunk);
+
+        self.tx.batch_execute("SAVEPOINT bulk_chunk").await?;
+        match copy_rows(self.tx, self.table, &chunk).await {
+            Ok(_) => {
+                self.tx
+                    .batch_execute("RELEASE SAVEPOINT bulk_chunk")
+                    .await?;
+                for (row, record) in rows.into_iter().zip(&chunk) {
+                    self.inserted(row, record);
+                }
+                return Ok(());
+            }
+            Err(e) if e.status().is_server_error() => return Err(e),
+            Err(_) => {
+                self.tx
+                    .batch_execute("ROLLBACK TO SAVEPOINT bulk_chunk")
+                    .await?
+            }
+        }
+
+        for (row, record) in rows.into_iter().zip(&chunk) {
+            self.tx.batch_execute("SAVEPOINT bulk_row").await?;
+            match insert_row(self.tx, self.table, record).await {
+                Ok(()) => {
+                    self.tx.batch_execute("RELEASE SAVEPOINT bulk_row").await?;
+                    self.inserted(row, record);
+                }
+                Err(e) if e.status().is_server_error() => return Err(e),
+                Err(e) => {
+                    self.tx
+                        .batch_execute("ROLLBACK TO SAVEPOINT bulk_row")
+                        .await?;
+                    self.reject(row, e);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Ownership shares are only checked at commit, which would fail the
+    /// whole import. So parcels whose shares no longer sum to 100 lose the
+    /// shares this import gave them, and each of those rows is rejected.
+    async fn reject_unbalanced(&mut self) -> AppResult<()> {
+        let Some(shares) = self.shares.take() else {
+            return Ok(());
+        };
+        let mut parcely: Vec<i32> = shares.iter().map(|(_, p, _)| *p).collect();
+        parcely.sort_unstable();
+        parcely.dedup();
+
+        for (parcela_id, message) in podily_necele(self.tx, &parcely).await? {
+            let rows: Vec<(usize, i32)> = shares
+                .iter()
+                .filter(|(_, p, _)| *p == parcela_id)
+                .map(|(row, _, majitel_id)| (*row, *majitel_id))
+                .collect();
+            let majitele: Vec<i32> = rows.iter().map(|(_, m)| *m).collect();
+            self.report.inserted -= delete_podily(self.tx, parcela_id, &majitele).await?;
+            for (row, _) in rows {
+                self.reject(
+                    row,
+                    AppError::Unprocessable {
+                        code: "rejected",
+                        message: message.clone(),
+                        field: Some("podil_setin".to_string()),
+                        constraint: Some("podily_celek".to_string()),
+                    },
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Takes the next complete line, newline included, off the front of `buffer`.
+/// Once the body is `finished`, the rest is the last line, which need not end
+/// with a newline.
+fn next_line(buffer: &mut Vec<u8>, finished: bool) -> Option<Vec<u8>> {
+    match buffer.iter().position(|b| *b == b'\n') {
+        Some(end) => Some(buffer.drain(..=end).collect()),
+        None if finished && !buffer.is_empty() => Some(std::mem::take(buffer)),
+        None => None,
+    }
+}
+
+/// Imports the records of `body` into `T`'s table within `tx`.
+async fn import<T: Listable, N: DeserializeOwned + Serialize + Validate>(
+    tx: &Transaction<'_>,
+    body: Body,
+    ndjson: bool,
+) -> AppResult<BulkReport> {
+    let mut import = Import::new(tx, &T::TABLE);
+
+    if ndjson {
+        let mut stream = body.into_data_stream();
+        let mut buffer: Vec<u8> = Vec::new();
+        let mut received = 0;
+        let mut row = 0;
+        let mut finished = false;
+        while !finished {
+            match stream.next().await {
+                Some(chunk) => {
+                    let chunk = chunk.map_err(|e| AppError::BadRequest(e.to_string()))?;
+                    received += chunk.len();
+                    if received > MAX_BULK_BODY_BYTES {
+                        return Err(too_large());
+                    }
+                    buffer.extend_from_slice(&chunk);
+                }
+                None => finished = true,
+            }
+            while let Some(line) = next_line(&mut buffer, finished) {
+                if line.trim_ascii().is_empty() {
+                    continue;
+                }
+                let record = serde_json::from_slice(&line)
+                    .map_err(|e| AppError::BadRequest(format!("Invalid JSON: {}", e)))
+                    .and_then(prepare::<T, N>);
+                import.add(row, record).await?;
+                row += 1;
+            }
+        }
+    } else {
+        let bytes = axum::body::to_bytes(body, MAX_BULK_BODY_BYTES)
+            .await
+            .map_err(|_| too_large())?;
+        let records: Vec<Value> = serde_json::from_slice(&bytes)
+            .map_err(|e| AppError::BadRequest(format!("Expected a JSON array: {}", e)))?;
+        for (row, record) in records.into_iter().enumerate() {
+            import.add(row, prepare::<T, N>(record)).await?;
+        }
+    }
+
+    import.flush().await?;
+    import.reject_unbalanced().await?;
+    Ok(import.report)
+}
+
+macro_rules! bulk_entities {
+    ($($struct:ident, $new_struct:ident);+ $(;)?) => {
+        async fn import_entity(
+            tx: &Transaction<'_>,
+            entity: &str,
+            body: Body,
+            ndjson: bool,
+        ) -> AppResult<BulkReport> {
+            $(
+                if entity == <$struct as Listable>::TABLE.name {
+                    return import::<$struct, $new_struct>(tx, body, ndjson).await;
+                }
+            )+
+            Err(AppError::BadRequest(format!("Unknown entity '{}'", entity)))
+        }
+    };
+}
+
+bulk_entities!(
+    Majitel, NewMajitel;
+    Kraj, NewKraj;
+    Okres, NewOkres;
+    Obec, NewObec;
+    KatastralniUzemi, NewKatastralniUzemi;
+    Bpej, NewBpej;
+    TypRizeni, NewTypRizeni;
+    TypOperace, NewTypOperace;
+    TypUcastnika, NewTypUcastnika;
+    UcastnikRizeni, NewUcastnikRizeni;
+    ListVlastnictvi, NewListVlastnictvi;
+    ParcelaRow, NewParcelaRow;
+    Rizeni, NewRizeni;
+    Vlastnictvi, NewVlastnictvi;
+    BremenoParcelaParcela, NewBremenoParcelaParcela;
+    BremenoParcelaMajitel, NewBremenoParcelaMajitel;
+    Plomba, NewPlomba;
+    RizeniOperaceRow, NewRizeniOperaceRow;
+    Ucast, NewUcast;
+    NabyvaciTitul, NewNabyvaciTitul;
+);
+
+/// Inserts many rows of one entity (named by its table, as in `/batch`) with
+/// `COPY`. The body is a JSON array of `New*` records, or one record per line
+/// with `Content-Type: application/x-ndjson`. Records go through the same
+/// validation as single creates; rejected ones are reported by their 0-based
+/// position. Imported `vlastnictvi` shares are checked per parcel once all rows
+/// are in: a parcel whose shares do not sum to 100 has all its rows rejected.
+/// With `atomic=true`, any rejection fails the whole import with 422.
+pub async fn bulk_handler(
+    State(pool): State<Pool>,
+    Extension(actor): Extension<User>,
+    Path(entity): Path<String>,
+    Query(params): Query<BulkParams>,
+    headers: HeaderMap,
+    body: Body,
+) -> AppResult<Json<BulkReport>> {
+    if actor.role < write_role(&format!("/{}", entity)) {
+        return Err(AppError::Forbidden("Insufficient role".to_string()));
+    }
+
+    let mut client = pool.get().await?;
+    let tx = begin_audited(&mut client, &actor).await?;
+    let report = import_entity(&tx, &entity, body, is_ndjson(&headers)).await?;
+    if params.atomic && report.rejected > 0 {
+        // Dropping the transaction rolls back the rows already copied
+        return Err(AppError::RowsRejected {
+            rejected: report.rejected,
+            errors: report.errors,
+        });
+    }
+    tx.commit().await?;
+    Ok(Json(report))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(chunks: &[&str]) -> Vec<String> {
+        let mut buffer = Vec::new();
+        let mut lines = Vec::new();
+        for (i, chunk) in chunks.iter().enumerate() {
+            buffer.extend_from_slice(chunk.as_bytes());
+            let finished = i + 1 == chunks.len();
+            while let Some(line) = next_line(&mut buffer, finished) {
+                lines.push(String::from_utf8(line).unwrap());
+            }
+        }
+        assert!(buffer.is_empty());
+        lines
+    }
+
+    #[test]
+    fn splits_lines_across_chunks() {
+        assert_eq!(
+            lines(&["{\"id\": 1}\n{\"i", "d\": 2}\n", "\n{\"id\": 3}\n", ""]),
+            ["{\"id\": 1}\n", "{\"id\": 2}\n", "\n", "{\"id\": 3}\n"]
+        );
+    }
+
+    #[test]
+    fn keeps_a_last_line_without_newline() {
+        assert_eq!(
+            lines(&["{\"id\": 1}\n{\"id\"", ": 2}", ""]),
+            ["{\"id\": 1}\n", "{\"id\": 2}"]
+        );
+        assert_eq!(lines(&["", ""]), Vec::<String>::new());
+    }
+
+    #[test]
+    fn waits_for_the_rest_of_an_unfinished_line() {
+        let mut buffer = b"{\"id\": 1}".to_vec();
+        assert_eq!(next_line(&mut buffer, false), None);
+        assert_eq!(buffer, b"{\"id\": 1}");
+    }
+}
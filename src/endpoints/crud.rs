@@ -1,39 +1,103 @@
 use axum::{
-    Json,
-    extract::{Query, State},
-    http::StatusCode,
+    Extension, Json,
+    extract::{Path, Query, State},
+    http::{HeaderMap, HeaderValue, StatusCode, Uri, header::LOCATION},
 };
 use deadpool_postgres::Pool;
 use serde::Deserialize;
-use serde_json::{Value, json};
+use serde_json::{Map, Value, json};
 
 use crate::*;
 
 // --- Generic CRUD Handlers ---
+// List handlers take the paging, sorting and filtering parameters described on `ListQuery`.
+
+/// `Location` of a newly created row: the collection path followed by its key,
+/// matching the `get_one` route.
+fn location(uri: &Uri, key: &[i32]) -> HeaderMap {
+    let mut path = uri.path().trim_end_matches('/').to_string();
+    for k in key {
+        path.push_str(&format!("/{}", k));
+    }
+    let mut headers = HeaderMap::new();
+    if let Ok(value) = HeaderValue::from_str(&path) {
+        headers.insert(LOCATION, value);
+    }
+    headers
+}
 
 macro_rules! crud_handlers {
     ($name:ident, $struct:ident, $new_struct:ident, $get_fn:ident, $create_fn:ident, $update_fn:ident, $delete_fn:ident) => {
-        pub async fn $name(State(pool): State<Pool>) -> Result<Json<Vec<$struct>>, String> {
-            let result = $get_fn(pool).await.map_err(|e| format!("Database error: {}", e))?;
-            Ok(Json(result))
+        pub async fn $name(
+            State(pool): State<Pool>,
+            access: PersonalDataAccess,
+            Query(params): Query<Vec<(String, String)>>,
+        ) -> AppResult<(HeaderMap, Json<Vec<$struct>>)> {
+            let query = ListQuery::parse(&<$struct as Listable>::TABLE, &params).map_err(AppError::BadRequest)?;
+            access.check_query::<$struct>(&query)?;
+            let mut page = $get_fn(pool, &query).await?;
+            access.shape_all(&mut page.items);
+            Ok((page.headers(), Json(page.items)))
         }
 
         pub mod $name {
             use super::*;
+            pub async fn get_one(
+                State(pool): State<Pool>,
+                access: PersonalDataAccess,
+                Path(id): Path<i32>,
+            ) -> AppResult<(HeaderMap, Json<$struct>)> {
+                let (item, version) = get_versioned::<$struct>(pool, &[id]).await?.ok_or_else(AppError::not_found)?;
+                Ok((etag_header(&version), Json(access.shape(item))))
+            }
+
             pub async fn create(
                 State(pool): State<Pool>,
+                Extension(actor): Extension<User>,
+                access: PersonalDataAccess,
+                uri: Uri,
                 Json(item): Json<$new_struct>,
-            ) -> Result<Json<Value>, String> {
-                let result = $create_fn(pool, item).await.map_err(|e| format!("Database error: {}", e))?;
-                Ok(Json(json!({ "rows_affected": result })))
+            ) -> AppResult<(StatusCode, HeaderMap, Json<$struct>)> {
+                let item = item.validated()?;
+                let mut client = pool.get().await?;
+                let tx = begin_audited(&mut client, &actor).await?;
+                let created = $create_fn(&tx, item).await?;
+                tx.commit().await?;
+                Ok((StatusCode::CREATED, location(&uri, &[created.id]), Json(access.shape(created))))
             }
 
             pub async fn update(
                 State(pool): State<Pool>,
+                Extension(actor): Extension<User>,
+                if_match: IfMatch,
                 Json(item): Json<$struct>,
-            ) -> Result<Json<Value>, String> {
-                let result = $update_fn(pool, item).await.map_err(|e| format!("Database error: {}", e))?;
-                Ok(Json(json!({ "rows_affected": result })))
+            ) -> AppResult<(HeaderMap, Json<Value>)> {
+                let item = item.validated()?;
+                let key = [item.id];
+                let mut client = pool.get().await?;
+                let tx = begin_audited(&mut client, &actor).await?;
+                if $update_fn(&tx, item, &if_match).await? == 0 {
+                    return Err(AppError::not_found());
+                }
+                let version = version_in::<$struct>(&tx, &key).await?.ok_or_else(AppError::not_found)?;
+                tx.commit().await?;
+                Ok((etag_header(&version), Json(json!({ "rows_affected": 1 }))))
+            }
+
+            /// Changes only the fields present in the body; `null` clears an optional field.
+            pub async fn patch(
+                State(pool): State<Pool>,
+                Extension(actor): Extension<User>,
+                access: PersonalDataAccess,
+                Path(id): Path<i32>,
+                if_match: IfMatch,
+                Json(body): Json<Map<String, Value>>,
+            ) -> AppResult<(HeaderMap, Json<$struct>)> {
+                let mut patch = Patch::parse(&<$struct as Listable>::TABLE, &body).map_err(AppError::BadRequest)?;
+                <$struct as Validate>::normalize_patch(&mut patch);
+                let item = patch_by_key::<$struct>(pool, &actor, &[id], &patch, &if_match).await?;
+                let (item, version) = item.ok_or_else(AppError::not_found)?;
+                Ok((etag_header(&version), Json(access.shape(item))))
             }
 
             #[derive(Debug, Deserialize)]
@@ -43,11 +107,16 @@ macro_rules! crud_handlers {
 
             pub async fn delete(
                 State(pool): State<Pool>,
+                Extension(actor): Extension<User>,
+                if_match: IfMatch,
                 Query(params): Query<DeleteParams>,
-            ) -> Result<Json<Value>, (StatusCode, String)> {
-                let result = $delete_fn(pool, params.id).await.map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+            ) -> AppResult<Json<Value>> {
+                let mut client = pool.get().await?;
+                let tx = begin_audited(&mut client, &actor).await?;
+                let result = $delete_fn(&tx, params.id, &if_match).await?;
+                tx.commit().await?;
                 if result == 0 {
-                    return Err((StatusCode::NOT_FOUND, "Item not found".to_string()));
+                    return Err(AppError::not_found());
                 }
                 Ok(Json(json!({ "rows_affected": result })))
             }
@@ -58,27 +127,76 @@ macro_rules! crud_handlers {
 // Handlers for tables with composite keys need special handling or a different macro
 macro_rules! crud_handlers_composite_2 {
     ($name:ident, $struct:ident, $new_struct:ident, $get_fn:ident, $create_fn:ident, $update_fn:ident, $delete_fn:ident, $key1:ident, $key2:ident) => {
-        pub async fn $name(State(pool): State<Pool>) -> Result<Json<Vec<$struct>>, String> {
-            let result = $get_fn(pool).await.map_err(|e| format!("Database error: {}", e))?;
-            Ok(Json(result))
+        pub async fn $name(
+            State(pool): State<Pool>,
+            access: PersonalDataAccess,
+            Query(params): Query<Vec<(String, String)>>,
+        ) -> AppResult<(HeaderMap, Json<Vec<$struct>>)> {
+            let query = ListQuery::parse(&<$struct as Listable>::TABLE, &params).map_err(AppError::BadRequest)?;
+            access.check_query::<$struct>(&query)?;
+            let mut page = $get_fn(pool, &query).await?;
+            access.shape_all(&mut page.items);
+            Ok((page.headers(), Json(page.items)))
         }
 
         pub mod $name {
             use super::*;
+            pub async fn get_one(
+                State(pool): State<Pool>,
+                access: PersonalDataAccess,
+                Path(($key1, $key2)): Path<(i32, i32)>,
+            ) -> AppResult<(HeaderMap, Json<$struct>)> {
+                let (item, version) = get_versioned::<$struct>(pool, &[$key1, $key2]).await?.ok_or_else(AppError::not_found)?;
+                Ok((etag_header(&version), Json(access.shape(item))))
+            }
+
             pub async fn create(
                 State(pool): State<Pool>,
+                Extension(actor): Extension<User>,
+                access: PersonalDataAccess,
+                uri: Uri,
                 Json(item): Json<$new_struct>,
-            ) -> Result<Json<Value>, String> {
-                let result = $create_fn(pool, item).await.map_err(|e| format!("Database error: {}", e))?;
-                Ok(Json(json!({ "rows_affected": result })))
+            ) -> AppResult<(StatusCode, HeaderMap, Json<$struct>)> {
+                let item = item.validated()?;
+                let mut client = pool.get().await?;
+                let tx = begin_audited(&mut client, &actor).await?;
+                let created = $create_fn(&tx, item).await?;
+                tx.commit().await?;
+                Ok((StatusCode::CREATED, location(&uri, &[created.$key1, created.$key2]), Json(access.shape(created))))
             }
 
             pub async fn update(
                 State(pool): State<Pool>,
+                Extension(actor): Extension<User>,
+                if_match: IfMatch,
                 Json(item): Json<$struct>,
-            ) -> Result<Json<Value>, String> {
-                let result = $update_fn(pool, item).await.map_err(|e| format!("Database error: {}", e))?;
-                Ok(Json(json!({ "rows_affected": result })))
+            ) -> AppResult<(HeaderMap, Json<Value>)> {
+                let item = item.validated()?;
+                let key = [item.$key1, item.$key2];
+                let mut client = pool.get().await?;
+                let tx = begin_audited(&mut client, &actor).await?;
+                if $update_fn(&tx, item, &if_match).await? == 0 {
+                    return Err(AppError::not_found());
+                }
+                let version = version_in::<$struct>(&tx, &key).await?.ok_or_else(AppError::not_found)?;
+                tx.commit().await?;
+                Ok((etag_header(&version), Json(json!({ "rows_affected": 1 }))))
+            }
+
+            /// Changes only the fields present in the body; `null` clears an optional field.
+            pub async fn patch(
+                State(pool): State<Pool>,
+                Extension(actor): Extension<User>,
+                access: PersonalDataAccess,
+                Path(($key1, $key2)): Path<(i32, i32)>,
+                if_match: IfMatch,
+                Json(body): Json<Map<String, Value>>,
+            ) -> AppResult<(HeaderMap, Json<$struct>)> {
+                let mut patch = Patch::parse(&<$struct as Listable>::TABLE, &body).map_err(AppError::BadRequest)?;
+                <$struct as Validate>::normalize_patch(&mut patch);
+                let item = patch_by_key::<$struct>(pool, &actor, &[$key1, $key2], &patch, &if_match).await?;
+                let (item, version) = item.ok_or_else(AppError::not_found)?;
+                Ok((etag_header(&version), Json(access.shape(item))))
             }
 
             #[derive(Debug, Deserialize)]
@@ -89,11 +207,16 @@ macro_rules! crud_handlers_composite_2 {
 
             pub async fn delete(
                 State(pool): State<Pool>,
+                Extension(actor): Extension<User>,
+                if_match: IfMatch,
                 Query(params): Query<DeleteParams>,
-            ) -> Result<Json<Value>, (StatusCode, String)> {
-                let result = $delete_fn(pool, params.$key1, params.$key2).await.map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+            ) -> AppResult<Json<Value>> {
+                let mut client = pool.get().await?;
+                let tx = begin_audited(&mut client, &actor).await?;
+                let result = $delete_fn(&tx, params.$key1, params.$key2, &if_match).await?;
+                tx.commit().await?;
                 if result == 0 {
-                    return Err((StatusCode::NOT_FOUND, "Item not found".to_string()));
+                    return Err(AppError::not_found());
                 }
                 Ok(Json(json!({ "rows_affected": result })))
             }
@@ -103,19 +226,42 @@ macro_rules! crud_handlers_composite_2 {
 
 macro_rules! crud_handlers_composite_3 {
     ($name:ident, $struct:ident, $new_struct:ident, $get_fn:ident, $create_fn:ident, $delete_fn:ident, $key1:ident, $key2:ident, $key3:ident) => {
-        pub async fn $name(State(pool): State<Pool>) -> Result<Json<Vec<$struct>>, String> {
-            let result = $get_fn(pool).await.map_err(|e| format!("Database error: {}", e))?;
-            Ok(Json(result))
+        pub async fn $name(
+            State(pool): State<Pool>,
+            access: PersonalDataAccess,
+            Query(params): Query<Vec<(String, String)>>,
+        ) -> AppResult<(HeaderMap, Json<Vec<$struct>>)> {
+            let query = ListQuery::parse(&<$struct as Listable>::TABLE, &params).map_err(AppError::BadRequest)?;
+            access.check_query::<$struct>(&query)?;
+            let mut page = $get_fn(pool, &query).await?;
+            access.shape_all(&mut page.items);
+            Ok((page.headers(), Json(page.items)))
         }
 
         pub mod $name {
             use super::*;
+            pub async fn get_one(
+                State(pool): State<Pool>,
+                access: PersonalDataAccess,
+                Path(($key1, $key2, $key3)): Path<(i32, i32, i32)>,
+            ) -> AppResult<(HeaderMap, Json<$struct>)> {
+                let (item, version) = get_versioned::<$struct>(pool, &[$key1, $key2, $key3]).await?.ok_or_else(AppError::not_found)?;
+                Ok((etag_header(&version), Json(access.shape(item))))
+            }
+
             pub async fn create(
                 State(pool): State<Pool>,
+                Extension(actor): Extension<User>,
+                access: PersonalDataAccess,
+                uri: Uri,
                 Json(item): Json<$new_struct>,
-            ) -> Result<Json<Value>, String> {
-                let result = $create_fn(pool, item).await.map_err(|e| format!("Database error: {}", e))?;
-                Ok(Json(json!({ "rows_affected": result })))
+            ) -> AppResult<(StatusCode, HeaderMap, Json<$struct>)> {
+                let item = item.validated()?;
+                let mut client = pool.get().await?;
+                let tx = begin_audited(&mut client, &actor).await?;
+                let created = $create_fn(&tx, item).await?;
+                tx.commit().await?;
+                Ok((StatusCode::CREATED, location(&uri, &[created.$key1, created.$key2, created.$key3]), Json(access.shape(created))))
             }
 
             #[derive(Debug, Deserialize)]
@@ -127,11 +273,16 @@ macro_rules! crud_handlers_composite_3 {
 
             pub async fn delete(
                 State(pool): State<Pool>,
+                Extension(actor): Extension<User>,
+                if_match: IfMatch,
                 Query(params): Query<DeleteParams>,
-            ) -> Result<Json<Value>, (StatusCode, String)> {
-                let result = $delete_fn(pool, params.$key1, params.$key2, params.$key3).await.map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+            ) -> AppResult<Json<Value>> {
+                let mut client = pool.get().await?;
+                let tx = begin_audited(&mut client, &actor).await?;
+                let result = $delete_fn(&tx, params.$key1, params.$key2, params.$key3, &if_match).await?;
+                tx.commit().await?;
                 if result == 0 {
-                    return Err((StatusCode::NOT_FOUND, "Item not found".to_string()));
+                    return Err(AppError::not_found());
                 }
                 Ok(Json(json!({ "rows_affected": result })))
             }
@@ -142,19 +293,42 @@ macro_rules! crud_handlers_composite_3 {
 // Plomba only has create and delete
 macro_rules! crud_handlers_plomba {
     ($name:ident, $struct:ident, $new_struct:ident, $get_fn:ident, $create_fn:ident, $delete_fn:ident, $key1:ident, $key2:ident) => {
-        pub async fn $name(State(pool): State<Pool>) -> Result<Json<Vec<$struct>>, String> {
-            let result = $get_fn(pool).await.map_err(|e| format!("Database error: {}", e))?;
-            Ok(Json(result))
+        pub async fn $name(
+            State(pool): State<Pool>,
+            access: PersonalDataAccess,
+            Query(params): Query<Vec<(String, String)>>,
+        ) -> AppResult<(HeaderMap, Json<Vec<$struct>>)> {
+            let query = ListQuery::parse(&<$struct as Listable>::TABLE, &params).map_err(AppError::BadRequest)?;
+            access.check_query::<$struct>(&query)?;
+            let mut page = $get_fn(pool, &query).await?;
+            access.shape_all(&mut page.items);
+            Ok((page.headers(), Json(page.items)))
         }
 
         pub mod $name {
             use super::*;
+            pub async fn get_one(
+                State(pool): State<Pool>,
+                access: PersonalDataAccess,
+                Path(($key1, $key2)): Path<(i32, i32)>,
+            ) -> AppResult<(HeaderMap, Json<$struct>)> {
+                let (item, version) = get_versioned::<$struct>(pool, &[$key1, $key2]).await?.ok_or_else(AppError::not_found)?;
+                Ok((etag_header(&version), Json(access.shape(item))))
+            }
+
             pub async fn create(
                 State(pool): State<Pool>,
+                Extension(actor): Extension<User>,
+                access: PersonalDataAccess,
+                uri: Uri,
                 Json(item): Json<$new_struct>,
-            ) -> Result<Json<Value>, String> {
-                let result = $create_fn(pool, item).await.map_err(|e| format!("Database error: {}", e))?;
-                Ok(Json(json!({ "rows_affected": result })))
+            ) -> AppResult<(StatusCode, HeaderMap, Json<$struct>)> {
+                let item = item.validated()?;
+                let mut client = pool.get().await?;
+                let tx = begin_audited(&mut client, &actor).await?;
+                let created = $create_fn(&tx, item).await?;
+                tx.commit().await?;
+                Ok((StatusCode::CREATED, location(&uri, &[created.$key1, created.$key2]), Json(access.shape(created))))
             }
 
             #[derive(Debug, Deserialize)]
@@ -165,11 +339,16 @@ macro_rules! crud_handlers_plomba {
 
             pub async fn delete(
                 State(pool): State<Pool>,
+                Extension(actor): Extension<User>,
+                if_match: IfMatch,
                 Query(params): Query<DeleteParams>,
-            ) -> Result<Json<Value>, (StatusCode, String)> {
-                let result = $delete_fn(pool, params.$key1, params.$key2).await.map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+            ) -> AppResult<Json<Value>> {
+                let mut client = pool.get().await?;
+                let tx = begin_audited(&mut client, &actor).await?;
+                let result = $delete_fn(&tx, params.$key1, params.$key2, &if_match).await?;
+                tx.commit().await?;
                 if result == 0 {
-                    return Err((StatusCode::NOT_FOUND, "Item not found".to_string()));
+                    return Err(AppError::not_found());
                 }
                 Ok(Json(json!({ "rows_affected": result })))
             }
@@ -296,6 +475,16 @@ crud_handlers!(
     delete_majitel
 );
 
+crud_handlers!(
+    nabyvaci_titul_handler,
+    NabyvaciTitul,
+    NewNabyvaciTitul,
+    get_nabyvaci_titul,
+    create_nabyvaci_titul,
+    update_nabyvaci_titul,
+    delete_nabyvaci_titul
+);
+
 crud_handlers_composite_2!(
     vlastnictvi_handler,
     Vlastnictvi,
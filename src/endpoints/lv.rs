@@ -1,9 +1,10 @@
 use axum::{
     Json,
     extract::{Query, State},
-    http::StatusCode,
-    response::IntoResponse,
+    http::{HeaderValue, header::CONTENT_TYPE},
+    response::{IntoResponse, Response},
 };
+use chrono::NaiveDate;
 use deadpool_postgres::Pool;
 use serde::Deserialize;
 use serde_json::json;
@@ -11,28 +12,65 @@ use tokio::try_join;
 
 use crate::*;
 
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LvFormat {
+    #[default]
+    Json,
+    /// Printable page, see `LvDocument::to_html`
+    Html,
+    /// A4 document, see `LvDocument::to_pdf`
+    Pdf,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct LvParams {
     pub katastralni_uzemi: String,
     pub cislo_lv: i32,
+    #[serde(default)]
+    pub format: LvFormat,
+    /// Reconstruct the LV as it stood at the end of this day. Parts D and E are
+    /// not historical: D shows the current details of the řízení behind the
+    /// seals of that day, and E the current acquisition titles that had taken
+    /// effect by then.
+    pub as_of: Option<NaiveDate>,
+}
+
+/// The query of one LV part, read from the history when `as_of` is given;
+/// the `_as_of` functions take the date as a third parameter.
+fn part_query(columns: &str, function: &str, as_of: Option<NaiveDate>) -> String {
+    match as_of {
+        Some(_) => format!("SELECT {} FROM {}_as_of($1, $2, $3);", columns, function),
+        None => format!("SELECT {} FROM {}($1, $2);", columns, function),
+    }
 }
 
 pub async fn get_lv_data(
     State(pool): State<Pool>,
+    access: PersonalDataAccess,
     Query(params): Query<LvParams>,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
+) -> AppResult<impl IntoResponse> {
     let katastralni_uzemi = params.katastralni_uzemi;
     let cislo_lv = params.cislo_lv;
+    let as_of = params.as_of;
+    let param_count = if as_of.is_some() { 3 } else { 2 };
 
     let pool_a = pool.clone();
     let katastralni_uzemi_a = katastralni_uzemi.clone();
     let task_a = async move {
         let start = std::time::Instant::now();
         let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] =
-            &[&katastralni_uzemi_a, &cislo_lv];
-        let res =
-            query_part_a(pool_a, "SELECT jmeno, prijmeni, bydliste, podil_setin FROM fn_get_lv_part_a($1, $2);", params)
-                .await;
+            &[&katastralni_uzemi_a, &cislo_lv, &as_of];
+        let res = query_part_a(
+            pool_a,
+            &part_query(
+                "jmeno, prijmeni, bydliste, podil_setin",
+                "fn_get_lv_part_a",
+                as_of,
+            ),
+            &params[..param_count],
+        )
+        .await;
         res.map(|v| (v, start.elapsed()))
     };
 
@@ -41,10 +79,17 @@ pub async fn get_lv_data(
     let task_b = async move {
         let start = std::time::Instant::now();
         let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] =
-            &[&katastralni_uzemi_b, &cislo_lv];
-        let res =
-            query_part_b(pool_b, "SELECT parcelni_cislo, je_stavebni, ulice, cislo_popisne, nazev_ku FROM fn_get_lv_part_b($1, $2);", params)
-                .await;
+            &[&katastralni_uzemi_b, &cislo_lv, &as_of];
+        let res = query_part_b(
+            pool_b,
+            &part_query(
+                "parcelni_cislo, je_stavebni, ulice, cislo_popisne, nazev_ku",
+                "fn_get_lv_part_b",
+                as_of,
+            ),
+            &params[..param_count],
+        )
+        .await;
         res.map(|v| (v, start.elapsed()))
     };
 
@@ -53,11 +98,11 @@ pub async fn get_lv_data(
     let task_b_parcela = async move {
         let start = std::time::Instant::now();
         let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] =
-            &[&katastralni_uzemi_b_parcela, &cislo_lv];
+            &[&katastralni_uzemi_b_parcela, &cislo_lv, &as_of];
         let res = query_part_b_parcela(
             pool_b_parcela,
-            "SELECT popis, datum_zrizeni, datum_pravnich_ucinku, je_stavebni_opravnena, parcelni_cislo_opravnena, cast_parcely_opravnena, je_stavebni_povinna, parcelni_cislo_povinna, cast_parcely_povinna FROM fn_get_lv_part_b_parcela($1, $2);",
-            params,
+            &part_query("popis, datum_zrizeni, datum_pravnich_ucinku, je_stavebni_opravnena, parcelni_cislo_opravnena, cast_parcely_opravnena, je_stavebni_povinna, parcelni_cislo_povinna, cast_parcely_povinna", "fn_get_lv_part_b_parcela", as_of),
+            &params[..param_count],
         )
         .await;
         res.map(|v| (v, start.elapsed()))
@@ -68,11 +113,11 @@ pub async fn get_lv_data(
     let task_b_majitel = async move {
         let start = std::time::Instant::now();
         let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] =
-            &[&katastralni_uzemi_b_majitel, &cislo_lv];
+            &[&katastralni_uzemi_b_majitel, &cislo_lv, &as_of];
         let res = query_part_b_majitel(
             pool_b_majitel,
-            "SELECT popis, datum_zrizeni, datum_pravnich_ucinku, je_stavebni_opravnena, parcelni_cislo_opravnena, cast_parcely_opravnena, jmeno_povinny, prijmeni_povinny, titul_povinny, rodne_cislo_povinny, ico_povinny FROM fn_get_lv_part_b_majitel($1, $2);",
-            params,
+            &part_query("popis, datum_zrizeni, datum_pravnich_ucinku, je_stavebni_opravnena, parcelni_cislo_opravnena, cast_parcely_opravnena, jmeno_povinny, prijmeni_povinny, titul_povinny, rodne_cislo_povinny, ico_povinny", "fn_get_lv_part_b_majitel", as_of),
+            &params[..param_count],
         )
         .await;
         res.map(|v| (v, start.elapsed()))
@@ -83,9 +128,9 @@ pub async fn get_lv_data(
     let task_c = async move {
         let start = std::time::Instant::now();
         let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] =
-            &[&katastralni_uzemi_c, &cislo_lv];
+            &[&katastralni_uzemi_c, &cislo_lv, &as_of];
         let res =
-            query_part_c(pool_c, "SELECT popis, datum_zrizeni, datum_pravnich_ucinku, je_stavebni_opravnena, parcelni_cislo_opravnena, cast_parcely_opravnena, je_stavebni_povinna, parcelni_cislo_povinna, cast_parcely_povinna FROM fn_get_lv_part_c($1, $2);", params)
+            query_part_c(pool_c, &part_query("popis, datum_zrizeni, datum_pravnich_ucinku, je_stavebni_opravnena, parcelni_cislo_opravnena, cast_parcely_opravnena, je_stavebni_povinna, parcelni_cislo_povinna, cast_parcely_povinna", "fn_get_lv_part_c", as_of), &params[..param_count])
                 .await;
         res.map(|v| (v, start.elapsed()))
     };
@@ -95,9 +140,21 @@ pub async fn get_lv_data(
     let task_d = async move {
         let start = std::time::Instant::now();
         let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] =
-            &[&katastralni_uzemi_d, &cislo_lv];
+            &[&katastralni_uzemi_d, &cislo_lv, &as_of];
         let res =
-            query_part_d(pool_d, "SELECT je_stavebni, parcelni_cislo, cast_parcely, nazev_katastralniho_uzemi, typ_rizeni_zkratka, cislo_rizeni, rok_rizeni FROM fn_get_lv_part_d($1, $2);", params)
+            query_part_d(pool_d, &part_query("je_stavebni, parcelni_cislo, cast_parcely, nazev_katastralniho_uzemi, typ_rizeni_zkratka, cislo_rizeni, rok_rizeni", "fn_get_lv_part_d", as_of), &params[..param_count])
+                .await;
+        res.map(|v| (v, start.elapsed()))
+    };
+
+    let pool_e = pool.clone();
+    let katastralni_uzemi_e = katastralni_uzemi.clone();
+    let task_e = async move {
+        let start = std::time::Instant::now();
+        let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] =
+            &[&katastralni_uzemi_e, &cislo_lv, &as_of];
+        let res =
+            query_part_e(pool_e, &part_query("druh, popis, datum_vyhotoveni, datum_pravnich_ucinku, typ_rizeni_zkratka, cislo_rizeni, rok_rizeni, jmeno, prijmeni", "fn_get_lv_part_e", as_of), &params[..param_count])
                 .await;
         res.map(|v| (v, start.elapsed()))
     };
@@ -107,21 +164,29 @@ pub async fn get_lv_data(
     let task_f = async move {
         let start = std::time::Instant::now();
         let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] =
-            &[&katastralni_uzemi_f, &cislo_lv];
-        let res =
-            query_part_f(pool_f, "SELECT je_stavebni, parcelni_cislo, cast_parcely, hodnota FROM fn_get_lv_part_f($1, $2);", params)
-                .await;
+            &[&katastralni_uzemi_f, &cislo_lv, &as_of];
+        let res = query_part_f(
+            pool_f,
+            &part_query(
+                "je_stavebni, parcelni_cislo, cast_parcely, hodnota",
+                "fn_get_lv_part_f",
+                as_of,
+            ),
+            &params[..param_count],
+        )
+        .await;
         res.map(|v| (v, start.elapsed()))
     };
 
     // Use try_join! to run them concurrently
     let (
-        (part_a, t_a),
+        (mut part_a, t_a),
         (part_b, t_b),
         (part_b_parcela, t_bp),
-        (part_b_majitel, t_bm),
+        (mut part_b_majitel, t_bm),
         (part_c, t_c),
         (part_d, t_d),
+        (part_e, t_e),
         (part_f, t_f),
     ) = try_join!(
         task_a,
@@ -130,41 +195,81 @@ pub async fn get_lv_data(
         task_b_majitel,
         task_c,
         task_d,
+        task_e,
         task_f
-    )
-    .map_err(|e| {
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            format!("Database error: {}", e),
-        )
-    })?;
+    )?;
 
     if part_a.is_empty() {
-        return Err((StatusCode::NOT_FOUND, "LV not found".to_string()));
+        return Err(AppError::NotFound("LV not found".to_string()));
     }
 
-    let response_body = json!({
-        "part_a": part_a,
-        "part_b": part_b,
-        "part_b_parcela": part_b_parcela,
-        "part_b_majitel": part_b_majitel,
-        "part_c": part_c,
-        "part_d": part_d,
-        "part_f": part_f,
-    });
+    access.shape_all(&mut part_a);
+    access.shape_all(&mut part_b_majitel);
+
+    let render_start = std::time::Instant::now();
+    let mut response: Response = match params.format {
+        LvFormat::Json => Json(json!({
+            "part_a": part_a,
+            "part_b": part_b,
+            "part_b_parcela": part_b_parcela,
+            "part_b_majitel": part_b_majitel,
+            "part_c": part_c,
+            "part_d": part_d,
+            "part_e": part_e,
+            "part_f": part_f,
+        }))
+        .into_response(),
+        format => {
+            let document = LvDocument {
+                katastralni_uzemi,
+                cislo_lv,
+                vyhotoveno: chrono::Local::now(),
+                as_of,
+                part_a,
+                part_b,
+                part_b_parcela,
+                part_b_majitel,
+                part_c,
+                part_d,
+                part_e,
+                part_f,
+            };
+            if format == LvFormat::Pdf {
+                let mut response = document.to_pdf().into_response();
+                response
+                    .headers_mut()
+                    .insert(CONTENT_TYPE, HeaderValue::from_static("application/pdf"));
+                response.headers_mut().insert(
+                    axum::http::header::CONTENT_DISPOSITION,
+                    HeaderValue::from_str(&format!("inline; filename=\"lv-{}.pdf\"", cislo_lv))
+                        .unwrap(),
+                );
+                response
+            } else {
+                let mut response = document.to_html().into_response();
+                response.headers_mut().insert(
+                    CONTENT_TYPE,
+                    HeaderValue::from_static("text/html; charset=utf-8"),
+                );
+                response
+            }
+        }
+    };
+    let t_render = render_start.elapsed();
 
     let timing = format!(
-        "part_a;dur={:.2}, part_b;dur={:.2}, part_b_parcela;dur={:.2}, part_b_majitel;dur={:.2}, part_c;dur={:.2}, part_d;dur={:.2}, part_f;dur={:.2}",
+        "part_a;dur={:.2}, part_b;dur={:.2}, part_b_parcela;dur={:.2}, part_b_majitel;dur={:.2}, part_c;dur={:.2}, part_d;dur={:.2}, part_e;dur={:.2}, part_f;dur={:.2}, render;dur={:.2}",
         t_a.as_secs_f64() * 1000.0,
         t_b.as_secs_f64() * 1000.0,
         t_bp.as_secs_f64() * 1000.0,
         t_bm.as_secs_f64() * 1000.0,
         t_c.as_secs_f64() * 1000.0,
         t_d.as_secs_f64() * 1000.0,
-        t_f.as_secs_f64() * 1000.0
+        t_e.as_secs_f64() * 1000.0,
+        t_f.as_secs_f64() * 1000.0,
+        t_render.as_secs_f64() * 1000.0
     );
 
-    let mut response = Json(response_body).into_response();
     response.headers_mut().insert(
         axum::http::header::HeaderName::from_static("server-timing"),
         axum::http::HeaderValue::from_str(&timing).unwrap(),
@@ -0,0 +1,56 @@
+use axum::{
+    Json,
+    extract::{Query, State},
+};
+use deadpool_postgres::Pool;
+use serde::{Deserialize, Serialize};
+
+use crate::*;
+
+#[derive(Debug, Deserialize)]
+pub struct LvBalanceParams {
+    pub katastralni_uzemi: Option<String>,
+    pub cislo_lv: Option<i32>,
+}
+
+/// An LV with at least one parcel out of balance.
+#[derive(Debug, Serialize)]
+pub struct NevyrovnanyLv {
+    pub list_vlastnictvi_id: i32,
+    pub katastralni_uzemi: String,
+    pub cislo_lv: i32,
+    pub parcels: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LvBalanceReport {
+    pub lvs: Vec<NevyrovnanyLv>,
+    pub parcels: Vec<NevyrovnanaParcela>,
+}
+
+/// Lists the parcels, and the LVs they are on, whose owners' shares do not add
+/// up to 100.
+pub async fn get_lv_balance(
+    State(pool): State<Pool>,
+    Query(params): Query<LvBalanceParams>,
+) -> AppResult<Json<LvBalanceReport>> {
+    let parcels =
+        query_nevyrovnane_parcely(pool, params.katastralni_uzemi.as_deref(), params.cislo_lv)
+            .await?;
+
+    // Parcels come ordered by LV
+    let mut lvs: Vec<NevyrovnanyLv> = Vec::new();
+    for parcela in &parcels {
+        match lvs.last_mut() {
+            Some(lv) if lv.list_vlastnictvi_id == parcela.list_vlastnictvi_id => lv.parcels += 1,
+            _ => lvs.push(NevyrovnanyLv {
+                list_vlastnictvi_id: parcela.list_vlastnictvi_id,
+                katastralni_uzemi: parcela.katastralni_uzemi.clone(),
+                cislo_lv: parcela.cislo_lv,
+                parcels: 1,
+            }),
+        }
+    }
+
+    Ok(Json(LvBalanceReport { lvs, parcels }))
+}
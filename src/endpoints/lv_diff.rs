@@ -0,0 +1,330 @@
+use axum::{
+    Json,
+    extract::{Query, State},
+};
+use chrono::NaiveDate;
+use deadpool_postgres::Pool;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use tokio::try_join;
+
+use crate::*;
+
+#[derive(Debug, Deserialize)]
+pub struct LvDiffParams {
+    pub katastralni_uzemi: String,
+    pub cislo_lv: i32,
+    /// Compare the LV as it stood at the end of this day...
+    pub from: Option<NaiveDate>,
+    /// ...with its state at the end of this one
+    pub to: Option<NaiveDate>,
+    /// Instead of `from`, the state right after this řízení, i.e. at the end
+    /// of the day of its last operation
+    pub from_rizeni: Option<i32>,
+    /// Instead of `to`, the state right after this řízení
+    pub to_rizeni: Option<i32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct Owner {
+    pub majitel_id: i32,
+    pub jmeno: String,
+    pub prijmeni: String,
+}
+
+/// A share that changed on a parcel that was on the LV at both dates; a share
+/// missing on one side is `None` there.
+#[derive(Debug, Serialize)]
+pub struct ShareChange {
+    pub parcela: ParcelaNaLv,
+    pub majitel_id: i32,
+    pub jmeno: String,
+    pub prijmeni: String,
+    pub podil_from: Option<i32>,
+    pub podil_to: Option<i32>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PartADiff {
+    pub owners_added: Vec<Owner>,
+    pub owners_removed: Vec<Owner>,
+    pub shares_changed: Vec<ShareChange>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PartBDiff {
+    pub parcels_added: Vec<ParcelaNaLv>,
+    pub parcels_removed: Vec<ParcelaNaLv>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PartCDiff {
+    pub created: Vec<PartC>,
+    pub cancelled: Vec<PartC>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PartDDiff {
+    pub set: Vec<PartD>,
+    pub lifted: Vec<PartD>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LvDiff {
+    pub katastralni_uzemi: String,
+    pub cislo_lv: i32,
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from_rizeni: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to_rizeni: Option<i32>,
+    pub part_a: PartADiff,
+    pub part_b: PartBDiff,
+    pub part_c: PartCDiff,
+    pub part_d: PartDDiff,
+}
+
+/// What the diff compares of one state of an LV.
+struct LvState {
+    parcely: Vec<ParcelaNaLv>,
+    podily: Vec<PodilNaLv>,
+    part_c: Vec<PartC>,
+    part_d: Vec<PartD>,
+}
+
+async fn load_state(
+    pool: &Pool,
+    katastralni_uzemi: &str,
+    cislo_lv: i32,
+    as_of: NaiveDate,
+) -> AppResult<LvState> {
+    let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] =
+        &[&katastralni_uzemi, &cislo_lv, &as_of];
+    let (parcely, podily, part_c, part_d) = try_join!(
+        query_parcely_na_lv(
+            pool.clone(),
+            "SELECT parcela_id, je_stavebni, parcelni_cislo, cast_parcely FROM fn_get_lv_parcely_as_of($1, $2, $3);",
+            params,
+        ),
+        query_podily_na_lv(
+            pool.clone(),
+            "SELECT parcela_id, majitel_id, jmeno, prijmeni, podil_setin FROM fn_get_lv_podily_as_of($1, $2, $3);",
+            params,
+        ),
+        query_part_c(
+            pool.clone(),
+            "SELECT popis, datum_zrizeni, datum_pravnich_ucinku, je_stavebni_opravnena, parcelni_cislo_opravnena, cast_parcely_opravnena, je_stavebni_povinna, parcelni_cislo_povinna, cast_parcely_povinna FROM fn_get_lv_part_c_as_of($1, $2, $3);",
+            params,
+        ),
+        query_part_d(
+            pool.clone(),
+            "SELECT je_stavebni, parcelni_cislo, cast_parcely, nazev_katastralniho_uzemi, typ_rizeni_zkratka, cislo_rizeni, rok_rizeni FROM fn_get_lv_part_d_as_of($1, $2, $3);",
+            params,
+        ),
+    )?;
+    Ok(LvState {
+        parcely,
+        podily,
+        part_c,
+        part_d,
+    })
+}
+
+/// The date one side of the diff stands at, given either directly or as the
+/// řízení whose result to show.
+async fn resolve_date(
+    pool: &Pool,
+    name: &str,
+    date: Option<NaiveDate>,
+    rizeni_id: Option<i32>,
+) -> AppResult<NaiveDate> {
+    let rizeni_id = match (date, rizeni_id) {
+        (Some(date), None) => return Ok(date),
+        (None, Some(id)) => id,
+        _ => {
+            return Err(AppError::BadRequest(format!(
+                "Exactly one of '{name}' and '{name}_rizeni' must be provided"
+            )));
+        }
+    };
+
+    let client = pool.get().await?;
+    let row = client
+        .query_opt(
+            "SELECT max(o.datum) FROM rizeni r LEFT JOIN rizeni_operace o ON o.rizeni_id = r.id WHERE r.id = $1 GROUP BY r.id;",
+            &[&rizeni_id],
+        )
+        .await?;
+    match row {
+        Some(row) => row.try_get::<_, Option<NaiveDate>>(0)?.ok_or_else(|| {
+            AppError::BadRequest(format!("Rizeni {rizeni_id} has no operations yet"))
+        }),
+        None => Err(AppError::NotFound(format!("Rizeni {rizeni_id} not found"))),
+    }
+}
+
+/// Rows only in `to`, then rows only in `from`.
+fn added_removed<T: PartialEq + Clone>(from: &[T], to: &[T]) -> (Vec<T>, Vec<T>) {
+    (
+        to.iter().filter(|r| !from.contains(r)).cloned().collect(),
+        from.iter().filter(|r| !to.contains(r)).cloned().collect(),
+    )
+}
+
+fn owners(podily: &[PodilNaLv]) -> BTreeMap<i32, Owner> {
+    podily
+        .iter()
+        .map(|p| {
+            let owner = Owner {
+                majitel_id: p.majitel_id,
+                jmeno: p.jmeno.clone(),
+                prijmeni: p.prijmeni.clone(),
+            };
+            (p.majitel_id, owner)
+        })
+        .collect()
+}
+
+/// A share at `from` and at `to`, keyed by parcel and owner.
+type SharePair<'a> = (Option<&'a PodilNaLv>, Option<&'a PodilNaLv>);
+
+fn diff_part_a(from: &LvState, to: &LvState) -> PartADiff {
+    let owners_from = owners(&from.podily);
+    let owners_to = owners(&to.podily);
+    let owners_added = owners_to
+        .iter()
+        .filter(|(id, _)| !owners_from.contains_key(id))
+        .map(|(_, o)| o.clone())
+        .collect();
+    let owners_removed = owners_from
+        .iter()
+        .filter(|(id, _)| !owners_to.contains_key(id))
+        .map(|(_, o)| o.clone())
+        .collect();
+
+    // Shares of parcels that left or joined the LV show up in part B instead
+    let parcely_from: HashSet<i32> = from.parcely.iter().map(|p| p.parcela_id).collect();
+    let parcely: HashMap<i32, &ParcelaNaLv> = to
+        .parcely
+        .iter()
+        .filter(|p| parcely_from.contains(&p.parcela_id))
+        .map(|p| (p.parcela_id, p))
+        .collect();
+    let mut shares: BTreeMap<(i32, i32), SharePair> = BTreeMap::new();
+    for podil in from
+        .podily
+        .iter()
+        .filter(|p| parcely.contains_key(&p.parcela_id))
+    {
+        shares
+            .entry((podil.parcela_id, podil.majitel_id))
+            .or_default()
+            .0 = Some(podil);
+    }
+    for podil in to
+        .podily
+        .iter()
+        .filter(|p| parcely.contains_key(&p.parcela_id))
+    {
+        shares
+            .entry((podil.parcela_id, podil.majitel_id))
+            .or_default()
+            .1 = Some(podil);
+    }
+    let mut shares_changed: Vec<ShareChange> = shares
+        .into_iter()
+        .filter_map(|((parcela_id, majitel_id), (before, after))| {
+            let podil_from = before.map(|p| p.podil_setin);
+            let podil_to = after.map(|p| p.podil_setin);
+            if podil_from == podil_to {
+                return None;
+            }
+            let named = after.or(before)?;
+            Some(ShareChange {
+                parcela: parcely[&parcela_id].clone(),
+                majitel_id,
+                jmeno: named.jmeno.clone(),
+                prijmeni: named.prijmeni.clone(),
+                podil_from,
+                podil_to,
+            })
+        })
+        .collect();
+    shares_changed.sort_by_key(|c| {
+        (
+            !c.parcela.je_stavebni,
+            c.parcela.parcelni_cislo,
+            c.parcela.cast_parcely,
+            c.majitel_id,
+        )
+    });
+
+    PartADiff {
+        owners_added,
+        owners_removed,
+        shares_changed,
+    }
+}
+
+fn diff_part_b(from: &LvState, to: &LvState) -> PartBDiff {
+    let ids_from: HashSet<i32> = from.parcely.iter().map(|p| p.parcela_id).collect();
+    let ids_to: HashSet<i32> = to.parcely.iter().map(|p| p.parcela_id).collect();
+    PartBDiff {
+        parcels_added: to
+            .parcely
+            .iter()
+            .filter(|p| !ids_from.contains(&p.parcela_id))
+            .cloned()
+            .collect(),
+        parcels_removed: from
+            .parcely
+            .iter()
+            .filter(|p| !ids_to.contains(&p.parcela_id))
+            .cloned()
+            .collect(),
+    }
+}
+
+/// Changes to parts A to D of an LV between two dates or two řízení, as
+/// reconstructed from the ownership history (see `as_of` on `/lv`).
+pub async fn get_lv_diff(
+    State(pool): State<Pool>,
+    Query(params): Query<LvDiffParams>,
+) -> AppResult<Json<LvDiff>> {
+    let (from_date, to_date) = try_join!(
+        resolve_date(&pool, "from", params.from, params.from_rizeni),
+        resolve_date(&pool, "to", params.to, params.to_rizeni),
+    )?;
+    if from_date > to_date {
+        return Err(AppError::BadRequest(
+            "from must not be after to".to_string(),
+        ));
+    }
+
+    let (from, to) = try_join!(
+        load_state(&pool, &params.katastralni_uzemi, params.cislo_lv, from_date),
+        load_state(&pool, &params.katastralni_uzemi, params.cislo_lv, to_date),
+    )?;
+    if from.parcely.is_empty() && to.parcely.is_empty() {
+        return Err(AppError::NotFound("LV not found".to_string()));
+    }
+
+    let part_a = diff_part_a(&from, &to);
+    let part_b = diff_part_b(&from, &to);
+    let (created, cancelled) = added_removed(&from.part_c, &to.part_c);
+    let (set, lifted) = added_removed(&from.part_d, &to.part_d);
+
+    Ok(Json(LvDiff {
+        katastralni_uzemi: params.katastralni_uzemi,
+        cislo_lv: params.cislo_lv,
+        from: from_date,
+        to: to_date,
+        from_rizeni: params.from_rizeni,
+        to_rizeni: params.to_rizeni,
+        part_a,
+        part_b,
+        part_c: PartCDiff { created, cancelled },
+        part_d: PartDDiff { set, lifted },
+    }))
+}
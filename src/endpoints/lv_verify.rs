@@ -0,0 +1,43 @@
+use axum::{
+    Json,
+    extract::{Query, State},
+};
+use deadpool_postgres::Pool;
+use serde::{Deserialize, Serialize};
+
+use crate::*;
+
+#[derive(Debug, Deserialize)]
+pub struct LvVerifyParams {
+    pub katastralni_uzemi: Option<String>,
+    pub cislo_lv: Option<i32>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LvVerifyReport {
+    pub checked: usize,
+    /// LVs whose stored hash is missing or differs from the computed one
+    pub mismatches: Vec<VlastnickyHash>,
+}
+
+/// Recomputes `vlastnicky_hash` for every LV, or the ones matching the
+/// filters, and reports those whose stored value does not match.
+pub async fn get_lv_verify(
+    State(pool): State<Pool>,
+    Query(params): Query<LvVerifyParams>,
+) -> AppResult<Json<LvVerifyReport>> {
+    let filtered = params.katastralni_uzemi.is_some() || params.cislo_lv.is_some();
+    let hashes =
+        query_vlastnicky_hash(pool, params.katastralni_uzemi.as_deref(), params.cislo_lv).await?;
+    if filtered && hashes.is_empty() {
+        return Err(AppError::NotFound("LV not found".to_string()));
+    }
+
+    Ok(Json(LvVerifyReport {
+        checked: hashes.len(),
+        mismatches: hashes
+            .into_iter()
+            .filter(|h| h.stored.as_deref() != Some(h.computed.as_str()))
+            .collect(),
+    }))
+}
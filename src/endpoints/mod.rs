@@ -1,13 +1,29 @@
+pub mod api_token;
+pub mod audit;
 pub mod auth;
+pub mod batch;
+pub mod bulk;
 pub mod crud;
 pub mod health;
 pub mod lv;
+pub mod lv_balance;
+pub mod lv_diff;
+pub mod lv_verify;
 pub mod parcela;
 pub mod rizeni;
+pub mod user;
 
+pub use api_token::*;
+pub use audit::*;
 pub use auth::*;
+pub use batch::*;
+pub use bulk::*;
 pub use crud::*;
 pub use health::*;
 pub use lv::*;
+pub use lv_balance::*;
+pub use lv_diff::*;
+pub use lv_verify::*;
 pub use parcela::*;
 pub use rizeni::*;
+pub use user::*;
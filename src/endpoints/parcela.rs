@@ -1,7 +1,6 @@
 use axum::{
     Json,
     extract::{Query, State},
-    http::StatusCode,
 };
 use deadpool_postgres::Pool;
 use serde::Deserialize;
@@ -15,44 +14,46 @@ pub struct ParcelaParams {
     pub parcelni_cislo: i32,
     pub cast_parcely: i32,
     pub je_stavebni: bool,
+    /// Look the parcel up as it stood at the end of this day
+    pub as_of: Option<chrono::NaiveDate>,
 }
 
 pub async fn get_parceala_data(
     State(pool): State<Pool>,
     Query(params): Query<ParcelaParams>,
-) -> Result<Json<Value>, (StatusCode, String)> {
+) -> AppResult<Json<Value>> {
     let katastralni_uzemi = params.katastralni_uzemi;
     let parcelni_cislo = params.parcelni_cislo;
     let cast_parcely = params.cast_parcely;
     let je_stavebni = params.je_stavebni;
+    let as_of = params.as_of;
     let task = async move {
         let params: &[&(dyn tokio_postgres::types::ToSql + Sync)] = &[
             &katastralni_uzemi,
             &je_stavebni,
             &parcelni_cislo,
             &cast_parcely,
+            &as_of,
         ];
-        query_parcela(
-            pool,
-            "SELECT je_stavebni, parcelni_cislo, cast_parcely, vymera_metru_ctverecnich, ulice, cislo_popisne, hodnota, cislo_lv FROM fn_get_parcela($1, $2, $3, $4);",
-            params,
-        )
-        .await
+        match as_of {
+            Some(_) => query_parcela(
+                pool,
+                "SELECT je_stavebni, parcelni_cislo, cast_parcely, vymera_metru_ctverecnich, ulice, cislo_popisne, hodnota, cislo_lv FROM fn_get_parcela_as_of($1, $2, $3, $4, $5);",
+                params,
+            )
+            .await,
+            None => query_parcela(
+                pool,
+                "SELECT je_stavebni, parcelni_cislo, cast_parcely, vymera_metru_ctverecnich, ulice, cislo_popisne, hodnota, cislo_lv FROM fn_get_parcela($1, $2, $3, $4);",
+                &params[..4],
+            )
+            .await,
+        }
     };
 
-    let result = task.await.map_err(|e| {
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            format!("Database error: {}", e),
-        )
-    })?;
+    let result = task.await?;
     if result.is_empty() {
-        return Err((StatusCode::NOT_FOUND, "Parcela not found".to_string()));
+        return Err(AppError::NotFound("Parcela not found".to_string()));
     }
-    Ok(Json(serde_json::to_value(result).map_err(|e| {
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            format!("Serialization error: {}", e),
-        )
-    })?))
+    Ok(Json(serde_json::to_value(result)?))
 }
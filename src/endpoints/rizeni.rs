@@ -1,7 +1,6 @@
 use axum::{
     Json,
     extract::{Query, State},
-    http::StatusCode,
     response::IntoResponse,
 };
 use deadpool_postgres::Pool;
@@ -22,16 +21,11 @@ pub struct RizeniParams {
 pub async fn get_spravni_rizeni(
     State(pool): State<Pool>,
     Query(params): Query<RizeniParams>,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
+) -> AppResult<impl IntoResponse> {
     let rizeni_id = if let Some(id) = params.id {
         id
     } else if let (Some(typ), Some(cislo), Some(rok)) = (params.typ, params.cislo, params.rok) {
-        let client = pool.get().await.map_err(|e| {
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                format!("Database error: {}", e),
-            )
-        })?;
+        let client = pool.get().await?;
 
         // 1. Get ID
         let row = client
@@ -39,26 +33,14 @@ pub async fn get_spravni_rizeni(
                 "SELECT * FROM fn_get_rizeni_id($1, $2, $3);",
                 &[&typ, &cislo, &rok],
             )
-            .await
-            .map_err(|e| {
-                (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    format!("Database error: {}", e),
-                )
-            })?;
+            .await?;
 
         match row {
-            Some(row) => row.try_get(0).map_err(|e| {
-                (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    format!("Row parse error: {}", e),
-                )
-            })?,
-            None => return Err((StatusCode::NOT_FOUND, "Rizeni not found".to_string())),
+            Some(row) => row.try_get(0)?,
+            None => return Err(AppError::NotFound("Rizeni not found".to_string())),
         }
     } else {
-        return Err((
-            StatusCode::BAD_REQUEST,
+        return Err(AppError::BadRequest(
             "Missing parameters: either 'id' or 'typ', 'cislo', 'rok' must be provided".to_string(),
         ));
     };
@@ -102,19 +84,11 @@ pub async fn get_spravni_rizeni(
         res.map(|v| (v, start.elapsed()))
     };
 
-    let ((predmet, t_predmet), (ucastnici, t_ucastnici), (operace, t_operace)) = try_join!(task_predmet, task_ucastnici, task_operace)
-        .map_err(|e| {
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                format!("Database error: {}", e),
-            )
-        })?;
+    let ((predmet, t_predmet), (ucastnici, t_ucastnici), (operace, t_operace)) =
+        try_join!(task_predmet, task_ucastnici, task_operace)?;
 
     if predmet.is_empty() && ucastnici.is_empty() && operace.is_empty() {
-        return Err((
-            StatusCode::NOT_FOUND,
-            "Rizeni details not found".to_string(),
-        ));
+        return Err(AppError::NotFound("Rizeni details not found".to_string()));
     }
 
     let response_body = json!({
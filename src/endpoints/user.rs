@@ -0,0 +1,98 @@
+use axum::{
+    Extension, Json,
+    extract::{Query, State},
+};
+use deadpool_postgres::Pool;
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+use crate::*;
+
+async fn hash_password(password: String) -> AppResult<String> {
+    tokio::task::spawn_blocking(move || bcrypt::hash(password, bcrypt::DEFAULT_COST))
+        .await?
+        .map_err(|e| AppError::Internal(format!("Bcrypt error: {}", e)))
+}
+
+async fn drop_user_sessions(state: &AppState, user_id: i32) -> AppResult<()> {
+    state
+        .sessions
+        .remove_user(user_id)
+        .await
+        .map_err(|e| AppError::Internal(format!("Session store error: {}", e)))
+}
+
+pub async fn user_handler(State(pool): State<Pool>) -> AppResult<Json<Vec<User>>> {
+    let result = get_users(pool).await?;
+    Ok(Json(result))
+}
+
+pub mod user_handler {
+    use super::*;
+
+    pub async fn create(
+        State(pool): State<Pool>,
+        Extension(actor): Extension<User>,
+        Json(item): Json<NewUser>,
+    ) -> AppResult<Json<Value>> {
+        item.validate()?;
+        let hash = hash_password(item.password).await?;
+        let result = create_user(
+            pool,
+            &actor,
+            &item.username,
+            &hash,
+            item.role,
+            item.can_view_personal_data,
+        )
+        .await?;
+        Ok(Json(json!({ "rows_affected": result })))
+    }
+
+    pub async fn update(
+        State(pool): State<Pool>,
+        state: Extension<AppState>,
+        Extension(actor): Extension<User>,
+        Json(item): Json<UpdateUser>,
+    ) -> AppResult<Json<Value>> {
+        item.validate()?;
+        let hash = match item.password {
+            Some(password) => Some(hash_password(password).await?),
+            None => None,
+        };
+        let result = update_user(
+            pool,
+            &actor,
+            item.id,
+            hash.as_deref(),
+            item.role,
+            item.can_view_personal_data,
+        )
+        .await?;
+        if result == 0 {
+            return Err(AppError::not_found());
+        }
+        // Existing sessions carry the old role and permissions, force a fresh login
+        drop_user_sessions(&state, item.id).await?;
+        Ok(Json(json!({ "rows_affected": result })))
+    }
+
+    #[derive(Debug, Deserialize)]
+    pub struct DeleteParams {
+        pub id: i32,
+    }
+
+    pub async fn delete(
+        State(pool): State<Pool>,
+        state: Extension<AppState>,
+        Extension(actor): Extension<User>,
+        Query(params): Query<DeleteParams>,
+    ) -> AppResult<Json<Value>> {
+        let result = delete_user(pool, &actor, params.id).await?;
+        if result == 0 {
+            return Err(AppError::not_found());
+        }
+        drop_user_sessions(&state, params.id).await?;
+        Ok(Json(json!({ "rows_affected": result })))
+    }
+}
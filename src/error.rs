@@ -0,0 +1,314 @@
+use axum::{
+    Json,
+    http::{StatusCode, header::RETRY_AFTER},
+    response::{IntoResponse, Response},
+};
+use serde::{Serialize, Serializer};
+use serde_json::json;
+use tokio_postgres::error::{DbError, SqlState};
+use tracing::error;
+
+use crate::validation::FieldError;
+
+pub type AppResult<T> = Result<T, AppError>;
+
+/// Error type shared by the database layer and the endpoints.
+///
+/// Every variant renders as `{"code", "message", "field", "constraint"}` with
+/// a matching status. Database errors are classified by SQLSTATE; their raw
+/// text is only logged, never sent to the client.
+#[derive(Debug)]
+pub enum AppError {
+    BadRequest(String),
+    Unauthorized(String),
+    Forbidden(String),
+    NotFound(String),
+    /// 409: the request clashes with existing data (unique key, row still referenced)
+    Conflict {
+        code: &'static str,
+        message: String,
+        field: Option<String>,
+        constraint: Option<String>,
+    },
+    /// 422: well-formed input the data model rejects (missing reference, check, not null)
+    Unprocessable {
+        code: &'static str,
+        message: String,
+        field: Option<String>,
+        constraint: Option<String>,
+    },
+    /// 422: the request failed the checks in `Validate`, with every offending field
+    Validation(Vec<FieldError>),
+    /// 412: the row changed since the version named in `If-Match`
+    PreconditionFailed(String),
+    PayloadTooLarge(String),
+    /// 422: an all-or-nothing import had rejected rows, the first of which are listed
+    RowsRejected {
+        rejected: usize,
+        errors: Vec<RowError>,
+    },
+    TooManyRequests {
+        retry_after_secs: u64,
+    },
+    /// 503: the database is unreachable or asked us to retry; the detail is logged
+    Unavailable(String),
+    /// 500: anything unexpected; the detail is logged
+    Internal(String),
+    /// One operation of a `/batch` request failed; renders as that error with
+    /// the operation's index added
+    Batch {
+        operation: usize,
+        error: Box<AppError>,
+    },
+}
+
+impl AppError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            AppError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            AppError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            AppError::Forbidden(_) => StatusCode::FORBIDDEN,
+            AppError::NotFound(_) => StatusCode::NOT_FOUND,
+            AppError::Conflict { .. } => StatusCode::CONFLICT,
+            AppError::Unprocessable { .. }
+            | AppError::Validation(_)
+            | AppError::RowsRejected { .. } => StatusCode::UNPROCESSABLE_ENTITY,
+            AppError::PreconditionFailed(_) => StatusCode::PRECONDITION_FAILED,
+            AppError::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            AppError::TooManyRequests { .. } => StatusCode::TOO_MANY_REQUESTS,
+            AppError::Unavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            AppError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            AppError::Batch { error, .. } => error.status(),
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::BadRequest(_) => "bad_request",
+            AppError::Unauthorized(_) => "unauthorized",
+            AppError::Forbidden(_) => "forbidden",
+            AppError::NotFound(_) => "not_found",
+            AppError::Conflict { code, .. } | AppError::Unprocessable { code, .. } => code,
+            AppError::Validation(_) => "validation_failed",
+            AppError::PreconditionFailed(_) => "precondition_failed",
+            AppError::PayloadTooLarge(_) => "payload_too_large",
+            AppError::RowsRejected { .. } => "rows_rejected",
+            AppError::TooManyRequests { .. } => "too_many_requests",
+            AppError::Unavailable(_) => "service_unavailable",
+            AppError::Internal(_) => "internal_error",
+            AppError::Batch { error, .. } => error.code(),
+        }
+    }
+
+    /// The message shown to clients.
+    pub fn message(&self) -> String {
+        match self {
+            AppError::BadRequest(m)
+            | AppError::Unauthorized(m)
+            | AppError::Forbidden(m)
+            | AppError::NotFound(m)
+            | AppError::PreconditionFailed(m)
+            | AppError::PayloadTooLarge(m)
+            | AppError::Conflict { message: m, .. }
+            | AppError::Unprocessable { message: m, .. } => m.clone(),
+            AppError::Validation(_) => "Input validation failed".to_string(),
+            AppError::RowsRejected { rejected, .. } => {
+                format!("{} rows were rejected; nothing was imported", rejected)
+            }
+            AppError::TooManyRequests { .. } => "Too many requests".to_string(),
+            AppError::Unavailable(_) => "Service temporarily unavailable".to_string(),
+            AppError::Internal(_) => "Internal server error".to_string(),
+            AppError::Batch { error, .. } => error.message(),
+        }
+    }
+
+    pub fn not_found() -> Self {
+        AppError::NotFound("Item not found".to_string())
+    }
+}
+
+impl std::fmt::Display for AppError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AppError::Unavailable(detail) | AppError::Internal(detail) => f.write_str(detail),
+            AppError::Batch { operation, error } => write!(f, "operation {}: {}", operation, error),
+            other => f.write_str(&other.message()),
+        }
+    }
+}
+
+impl std::error::Error for AppError {}
+
+impl AppError {
+    /// The JSON body of the error response.
+    pub fn body(&self) -> serde_json::Value {
+        let (field, constraint) = match self {
+            AppError::Conflict {
+                field, constraint, ..
+            }
+            | AppError::Unprocessable {
+                field, constraint, ..
+            } => (field.clone(), constraint.clone()),
+            _ => (None, None),
+        };
+
+        let mut body = json!({
+            "code": self.code(),
+            "message": self.message(),
+            "field": field,
+            "constraint": constraint,
+        });
+        match self {
+            AppError::Validation(errors) => body["errors"] = json!(errors),
+            AppError::RowsRejected { errors, .. } => body["errors"] = json!(errors),
+            AppError::Batch { operation, error } => {
+                body = error.body();
+                body["operation"] = json!(operation);
+            }
+            _ => {}
+        }
+        body
+    }
+}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        // Only server errors carry a detail worth logging; `Display` shows it
+        if self.status().is_server_error() {
+            error!("{}", self);
+        }
+
+        let mut response = (self.status(), Json(self.body())).into_response();
+        if let AppError::TooManyRequests { retry_after_secs } = self {
+            response
+                .headers_mut()
+                .insert(RETRY_AFTER, retry_after_secs.into());
+        }
+        response
+    }
+}
+
+/// Why one row of a bulk import was rejected, rendered as the error's body
+/// with the row's index added.
+#[derive(Debug)]
+pub struct RowError {
+    pub row: usize,
+    pub error: AppError,
+}
+
+impl Serialize for RowError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut body = self.error.body();
+        body["row"] = json!(self.row);
+        body.serialize(serializer)
+    }
+}
+
+/// Column list out of a detail like `Key (parcela_id, majitel_id)=(1, 2) already exists.`
+fn key_columns(db: &DbError) -> Option<String> {
+    let detail = db.detail()?;
+    let start = detail.find("Key (")? + "Key (".len();
+    let end = start + detail[start..].find(")=")?;
+    Some(detail[start..end].to_string())
+}
+
+impl From<tokio_postgres::Error> for AppError {
+    fn from(e: tokio_postgres::Error) -> Self {
+        let Some(db) = e.as_db_error() else {
+            if e.is_closed() {
+                return AppError::Unavailable(format!("Database connection closed: {}", e));
+            }
+            return AppError::Internal(format!("Database error: {}", e));
+        };
+
+        let field = db.column().map(str::to_string).or_else(|| key_columns(db));
+        let constraint = db.constraint().map(str::to_string);
+        let code = db.code();
+
+        if *code == SqlState::UNIQUE_VIOLATION || *code == SqlState::EXCLUSION_VIOLATION {
+            AppError::Conflict {
+                code: "unique_violation",
+                message: "A record with the same key already exists".to_string(),
+                field,
+                constraint,
+            }
+        } else if *code == SqlState::FOREIGN_KEY_VIOLATION {
+            // The same SQLSTATE covers both directions of a foreign key
+            if db
+                .detail()
+                .is_some_and(|d| d.contains("is still referenced"))
+            {
+                AppError::Conflict {
+                    code: "still_referenced",
+                    message: "The record is still referenced by other records".to_string(),
+                    field,
+                    constraint,
+                }
+            } else {
+                AppError::Unprocessable {
+                    code: "foreign_key_violation",
+                    message: "A referenced record does not exist".to_string(),
+                    field,
+                    constraint,
+                }
+            }
+        } else if *code == SqlState::NOT_NULL_VIOLATION {
+            AppError::Unprocessable {
+                code: "not_null_violation",
+                message: "A required field is missing".to_string(),
+                field,
+                constraint,
+            }
+        } else if *code == SqlState::CHECK_VIOLATION {
+            AppError::Unprocessable {
+                code: "check_violation",
+                message: "A field value is not allowed".to_string(),
+                field,
+                constraint,
+            }
+        } else if *code == SqlState::RAISE_EXCEPTION {
+            // Raised deliberately by our own triggers and functions, worded for users
+            AppError::Unprocessable {
+                code: "rejected",
+                message: db.message().to_string(),
+                field,
+                constraint,
+            }
+        } else if *code == SqlState::NO_DATA_FOUND {
+            AppError::not_found()
+        } else if code.code().starts_with("22") {
+            // Data exceptions: malformed or out of range values
+            AppError::BadRequest("Invalid input value".to_string())
+        } else if *code == SqlState::T_R_SERIALIZATION_FAILURE
+            || *code == SqlState::T_R_DEADLOCK_DETECTED
+            || code.code().starts_with("08")
+            || code.code().starts_with("53")
+            || code.code().starts_with("57P")
+        {
+            AppError::Unavailable(format!("Database error: {}", e))
+        } else {
+            AppError::Internal(format!("Database error: {}", e))
+        }
+    }
+}
+
+impl From<deadpool_postgres::PoolError> for AppError {
+    fn from(e: deadpool_postgres::PoolError) -> Self {
+        match e {
+            deadpool_postgres::PoolError::Backend(e) => e.into(),
+            other => AppError::Unavailable(format!("Database pool error: {}", other)),
+        }
+    }
+}
+
+impl From<serde_json::Error> for AppError {
+    fn from(e: serde_json::Error) -> Self {
+        AppError::Internal(format!("Serialization error: {}", e))
+    }
+}
+
+impl From<tokio::task::JoinError> for AppError {
+    fn from(e: tokio::task::JoinError) -> Self {
+        AppError::Internal(format!("Task join error: {}", e))
+    }
+}
@@ -0,0 +1,182 @@
+use chrono::NaiveDate;
+
+use crate::models::Pohlavi;
+
+/// Birth numbers with a 9-digit form and no check digit were issued until 1953.
+const LAST_NINE_DIGIT_YEAR: i32 = 1953;
+
+/// The +20 month offset for exhausted daily series is used since 2004.
+const FIRST_OFFSET_YEAR: i32 = 2004;
+
+/// A parsed Czech birth number (rodné číslo).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RodneCislo {
+    digits: String,
+    pub datum_narozeni: NaiveDate,
+    pub pohlavi: Pohlavi,
+}
+
+impl RodneCislo {
+    /// Accepts `YYMMDD/SSS[C]` with or without the slash and surrounding spaces.
+    ///
+    /// Checks the modulo-11 rule for 10-digit numbers (including the legacy
+    /// remainder 10 with check digit 0) and that the encoded date exists; women
+    /// have 50 added to the month, and since 2004 either sex may have another 20.
+    pub fn parse(raw: &str) -> Result<Self, String> {
+        let compact: String = raw.chars().filter(|c| !c.is_whitespace()).collect();
+        let digits = match compact.split_once('/') {
+            Some((date, serial)) if date.len() == 6 => format!("{}{}", date, serial),
+            Some(_) => return Err("slash must follow the first six digits".to_string()),
+            None => compact,
+        };
+        if !digits.chars().all(|c| c.is_ascii_digit()) {
+            return Err("must contain only digits and an optional slash".to_string());
+        }
+        if digits.len() != 9 && digits.len() != 10 {
+            return Err("must have 9 or 10 digits".to_string());
+        }
+
+        let number = |range: std::ops::Range<usize>| digits[range].parse::<u32>().unwrap_or(0);
+        let yy = number(0..2) as i32;
+        let year = if digits.len() == 9 {
+            if 1900 + yy > LAST_NINE_DIGIT_YEAR {
+                return Err("9-digit form is only valid for births before 1954".to_string());
+            }
+            1900 + yy
+        } else {
+            let value: u64 = digits.parse().unwrap_or(0);
+            let head = value / 10;
+            let check = value % 10;
+            if !(value.is_multiple_of(11) || (head % 11 == 10 && check == 0)) {
+                return Err("fails the modulo 11 check".to_string());
+            }
+            if yy >= 54 { 1900 + yy } else { 2000 + yy }
+        };
+
+        let mut month = number(2..4);
+        let pohlavi = if month > 50 {
+            month -= 50;
+            Pohlavi::Zena
+        } else {
+            Pohlavi::Muz
+        };
+        if month > 20 {
+            if year < FIRST_OFFSET_YEAR {
+                return Err("month offset +20 is only used since 2004".to_string());
+            }
+            month -= 20;
+        }
+        let datum_narozeni = NaiveDate::from_ymd_opt(year, month, number(4..6))
+            .ok_or_else(|| "does not encode a valid birth date".to_string())?;
+        if datum_narozeni > chrono::Local::now().date_naive() {
+            return Err("encodes a birth date in the future".to_string());
+        }
+
+        Ok(RodneCislo {
+            digits,
+            datum_narozeni,
+            pohlavi,
+        })
+    }
+
+    /// Canonical stored form, `YYMMDD/SSSC` (or `YYMMDD/SSS`).
+    pub fn canonical(&self) -> String {
+        format!("{}/{}", &self.digits[..6], &self.digits[6..])
+    }
+}
+
+/// Validates an IČO (company identification number) and returns its canonical
+/// 8-digit form. The last digit is a check digit over weights 8 down to 2.
+pub fn parse_ico(raw: &str) -> Result<String, String> {
+    let digits: String = raw.chars().filter(|c| !c.is_whitespace()).collect();
+    if digits.len() != 8 || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return Err("must have exactly 8 digits".to_string());
+    }
+
+    let values: Vec<u32> = digits.chars().filter_map(|c| c.to_digit(10)).collect();
+    let sum: u32 = values[..7]
+        .iter()
+        .zip((2..=8).rev())
+        .map(|(digit, weight)| digit * weight)
+        .sum();
+    let expected = (11 - sum % 11) % 10;
+    if values[7] != expected {
+        return Err("fails the check digit".to_string());
+    }
+    Ok(digits)
+}
+
+/// Birth date and sex encoded in a stored birth number, if it parses.
+pub fn birth_details(rodne_cislo: Option<&str>) -> (Option<NaiveDate>, Option<Pohlavi>) {
+    match rodne_cislo.map(RodneCislo::parse) {
+        Some(Ok(rc)) => (Some(rc.datum_narozeni), Some(rc.pohlavi)),
+        _ => (None, None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Pohlavi::{Muz, Zena};
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn parses_valid_birth_numbers() {
+        let cases = [
+            ("780101/1251", date(1978, 1, 1), Muz, "780101/1251"),
+            (" 7801011251 ", date(1978, 1, 1), Muz, "780101/1251"),
+            ("785101/1234", date(1978, 1, 1), Zena, "785101/1234"),
+            ("052101/1238", date(2005, 1, 1), Muz, "052101/1238"),
+            ("530101/123", date(1953, 1, 1), Muz, "530101/123"),
+            // Remainder 10 takes check digit 0
+            ("780101/0020", date(1978, 1, 1), Muz, "780101/0020"),
+        ];
+        for (raw, datum_narozeni, pohlavi, canonical) in cases {
+            let rc = RodneCislo::parse(raw).unwrap_or_else(|e| panic!("{}: {}", raw, e));
+            assert_eq!(rc.datum_narozeni, datum_narozeni, "{}", raw);
+            assert_eq!(rc.pohlavi, pohlavi, "{}", raw);
+            assert_eq!(rc.canonical(), canonical, "{}", raw);
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_birth_numbers() {
+        let cases = [
+            ("780101/1250", "modulo 11"),
+            ("902101/1241", "+20"),
+            ("540101/123", "before 1954"),
+            ("781301/1250", "valid birth date"),
+            ("500101/1235", "future"),
+            ("7801/011251", "slash"),
+            ("78010112a1", "only digits"),
+            ("78010112", "9 or 10 digits"),
+        ];
+        for (raw, error) in cases {
+            let e = RodneCislo::parse(raw).expect_err(raw);
+            assert!(e.contains(error), "{}: {}", raw, e);
+        }
+    }
+
+    #[test]
+    fn checks_ico() {
+        let cases = [
+            ("25596641", Ok("25596641")),
+            ("2559 6641", Ok("25596641")),
+            ("45600007", Ok("45600007")),
+            ("25596642", Err("fails the check digit")),
+            ("2559664", Err("must have exactly 8 digits")),
+            ("2559664x", Err("must have exactly 8 digits")),
+        ];
+        for (raw, expected) in cases {
+            assert_eq!(
+                parse_ico(raw),
+                expected.map(str::to_string).map_err(str::to_string),
+                "{}",
+                raw
+            );
+        }
+    }
+}
@@ -1,11 +1,32 @@
-#![feature(duration_millis_float)]
-
 pub mod db;
 pub mod endpoints;
+pub mod error;
+pub mod identifiers;
+pub mod listing;
 pub mod middleware;
 pub mod models;
+pub mod pdf;
+pub mod privacy;
+pub mod schema;
+pub mod session;
+pub mod throttle;
+pub mod token;
+pub mod validation;
+pub mod versioning;
+pub mod vypis;
 
 pub use db::*;
 pub use endpoints::*;
+pub use error::*;
+pub use identifiers::*;
+pub use listing::*;
 pub use middleware::*;
-pub use models::*;
\ No newline at end of file
+pub use models::*;
+pub use privacy::*;
+pub use schema::*;
+pub use session::*;
+pub use throttle::*;
+pub use token::*;
+pub use validation::*;
+pub use versioning::*;
+pub use vypis::*;
@@ -0,0 +1,678 @@
+use axum::http::{HeaderMap, HeaderValue};
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use serde_json::{Map, Value};
+use tokio_postgres::Row;
+use tokio_postgres::types::{ToSql, Type};
+
+use crate::versioning::VERSION_COLUMN;
+
+/// Page size used when the client does not ask for one.
+pub const DEFAULT_LIMIT: i64 = 100;
+/// Largest page a client may request.
+pub const MAX_LIMIT: i64 = 1000;
+
+pub const TOTAL_COUNT_HEADER: &str = "x-total-count";
+pub const NEXT_CURSOR_HEADER: &str = "x-next-cursor";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnType {
+    Int,
+    Text,
+    Bool,
+    Date,
+    Decimal,
+}
+
+impl ColumnType {
+    /// The Postgres type of such columns, as binary `COPY` needs it spelled out.
+    pub fn pg_type(&self) -> Type {
+        match self {
+            ColumnType::Int => Type::INT4,
+            ColumnType::Text => Type::TEXT,
+            ColumnType::Bool => Type::BOOL,
+            ColumnType::Date => Type::DATE,
+            ColumnType::Decimal => Type::NUMERIC,
+        }
+    }
+}
+
+/// Columns of a table exposed through the generic CRUD endpoints, in the order
+/// they are selected. All key columns are `INT`.
+#[derive(Debug)]
+pub struct Table {
+    pub name: &'static str,
+    pub columns: &'static [(&'static str, ColumnType)],
+    pub key: &'static [&'static str],
+    /// Columns that accept `NULL`
+    pub nullable: &'static [&'static str],
+}
+
+impl Table {
+    fn column(&self, name: &str) -> Option<(&'static str, ColumnType)> {
+        self.columns.iter().copied().find(|(c, _)| *c == name)
+    }
+
+    /// `k1 = $n AND k2 = $n+1 ...` over the key columns, numbering from `first_param`.
+    pub fn key_condition(&self, first_param: usize) -> String {
+        self.key
+            .iter()
+            .enumerate()
+            .map(|(i, k)| format!("{} = ${}", k, first_param + i))
+            .collect::<Vec<_>>()
+            .join(" AND ")
+    }
+
+    pub fn select_list(&self) -> String {
+        self.columns
+            .iter()
+            .map(|(c, _)| *c)
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// `select_list` followed by the row version, which `from_row` ignores.
+    pub fn versioned_select_list(&self) -> String {
+        format!("{}, {}", self.select_list(), VERSION_COLUMN)
+    }
+
+    /// The row version selected by `versioned_select_list`.
+    pub fn version(&self, row: &Row) -> String {
+        row.get(self.columns.len())
+    }
+
+    /// Columns an insert supplies: all of them, except a lone `id` key, which
+    /// the database generates.
+    pub fn insert_columns(&self) -> Vec<(&'static str, ColumnType)> {
+        self.columns
+            .iter()
+            .copied()
+            .filter(|(c, _)| self.key != ["id"] || *c != "id")
+            .collect()
+    }
+
+    /// Converts an object holding the `insert_columns` (as a serialized `New*`
+    /// model does) into values for them.
+    pub fn insert_row(&self, object: &Map<String, Value>) -> Result<InsertRow, String> {
+        let values = self
+            .insert_columns()
+            .into_iter()
+            .map(|(column, ty)| {
+                let value = match object.get(column) {
+                    None | Some(Value::Null) => None,
+                    Some(value) => Some(SqlValue::from_json(ty, column, value)?),
+                };
+                Ok((ty, value))
+            })
+            .collect::<Result<_, String>>()?;
+        Ok(InsertRow { values })
+    }
+}
+
+/// One row for `Table::insert_columns`, in their order; `None` is `NULL`.
+#[derive(Debug, Clone)]
+pub struct InsertRow {
+    values: Vec<(ColumnType, Option<SqlValue>)>,
+}
+
+impl InsertRow {
+    /// The value of `table`'s `INT` column `column`, if set.
+    pub fn int(&self, table: &Table, column: &str) -> Option<i32> {
+        let index = table
+            .insert_columns()
+            .iter()
+            .position(|(c, _)| *c == column)?;
+        match self.values[index].1 {
+            Some(SqlValue::Int(value)) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn params(&self) -> Vec<&(dyn ToSql + Sync)> {
+        self.values
+            .iter()
+            .map(|(ty, value)| match value {
+                Some(value) => value.as_sql(),
+                // NULLs are typed too, since binary COPY checks each value against its column
+                None => match ty {
+                    ColumnType::Int => &None::<i32> as &(dyn ToSql + Sync),
+                    ColumnType::Text => &None::<&str>,
+                    ColumnType::Bool => &None::<bool>,
+                    ColumnType::Date => &None::<NaiveDate>,
+                    ColumnType::Decimal => &None::<Decimal>,
+                },
+            })
+            .collect()
+    }
+}
+
+/// A model that can be listed with `db::list_rows` and fetched with `db::get_by_key`.
+pub trait Listable: Sized {
+    const TABLE: Table;
+
+    /// Builds the model from a row selected with `TABLE.select_list()`.
+    fn from_row(row: &Row) -> Self;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FilterOp {
+    Eq,
+    Gt,
+    Gte,
+    Lt,
+    Lte,
+}
+
+impl FilterOp {
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "eq" => Some(FilterOp::Eq),
+            "gt" => Some(FilterOp::Gt),
+            "gte" => Some(FilterOp::Gte),
+            "lt" => Some(FilterOp::Lt),
+            "lte" => Some(FilterOp::Lte),
+            _ => None,
+        }
+    }
+
+    fn sql(&self) -> &'static str {
+        match self {
+            FilterOp::Eq => "=",
+            FilterOp::Gt => ">",
+            FilterOp::Gte => ">=",
+            FilterOp::Lt => "<",
+            FilterOp::Lte => "<=",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum SqlValue {
+    Int(i32),
+    Text(String),
+    Bool(bool),
+    Date(NaiveDate),
+    Decimal(Decimal),
+}
+
+impl SqlValue {
+    fn parse(ty: ColumnType, column: &str, raw: &str) -> Result<Self, String> {
+        let invalid = || format!("Invalid value '{}' for column {}", raw, column);
+        Ok(match ty {
+            ColumnType::Int => SqlValue::Int(raw.parse().map_err(|_| invalid())?),
+            ColumnType::Text => SqlValue::Text(raw.to_string()),
+            ColumnType::Bool => SqlValue::Bool(raw.parse().map_err(|_| invalid())?),
+            ColumnType::Date => SqlValue::Date(raw.parse().map_err(|_| invalid())?),
+            ColumnType::Decimal => SqlValue::Decimal(raw.parse().map_err(|_| invalid())?),
+        })
+    }
+
+    fn from_json(ty: ColumnType, column: &str, value: &Value) -> Result<Self, String> {
+        let invalid = || format!("Invalid value {} for column {}", value, column);
+        Ok(match (ty, value) {
+            (ColumnType::Int, Value::Number(n)) => SqlValue::Int(
+                n.as_i64()
+                    .and_then(|v| i32::try_from(v).ok())
+                    .ok_or_else(invalid)?,
+            ),
+            (ColumnType::Text, Value::String(v)) => SqlValue::Text(v.clone()),
+            (ColumnType::Bool, Value::Bool(v)) => SqlValue::Bool(*v),
+            (ColumnType::Date, Value::String(v)) => {
+                SqlValue::Date(v.parse().map_err(|_| invalid())?)
+            }
+            // Decimals arrive as strings when serialized by us, but accept plain numbers too
+            (ColumnType::Decimal, Value::String(v)) => {
+                SqlValue::Decimal(v.parse().map_err(|_| invalid())?)
+            }
+            (ColumnType::Decimal, Value::Number(n)) => {
+                SqlValue::Decimal(n.to_string().parse().map_err(|_| invalid())?)
+            }
+            _ => return Err(invalid()),
+        })
+    }
+
+    fn as_sql(&self) -> &(dyn ToSql + Sync) {
+        match self {
+            SqlValue::Int(v) => v,
+            SqlValue::Text(v) => v,
+            SqlValue::Bool(v) => v,
+            SqlValue::Date(v) => v,
+            SqlValue::Decimal(v) => v,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Filter {
+    column: &'static str,
+    op: FilterOp,
+    value: SqlValue,
+}
+
+/// Validated `limit`/`offset`/`after`/`order_by`/filter parameters of a list request.
+///
+/// * `limit` (default 100, at most 1000) and `offset` page through the result.
+/// * `after=<key>` continues after the given primary key (comma separated for
+///   composite keys) and only works with the default key order; the key to
+///   continue from is returned in `X-Next-Cursor`.
+/// * `order_by=rok,-cislo_rizeni` sorts by the listed columns, `-` for descending.
+/// * `<column>=<value>` filters by equality, `<column>.gt`/`.gte`/`.lt`/`.lte`
+///   by range, e.g. `rok.gte=2020&rok.lt=2024`. Ranges are not allowed on text
+///   and boolean columns.
+#[derive(Debug, Clone)]
+pub struct ListQuery {
+    pub limit: i64,
+    pub offset: i64,
+    after: Option<Vec<i32>>,
+    order_by: Vec<(&'static str, bool)>,
+    filters: Vec<Filter>,
+}
+
+impl ListQuery {
+    pub fn parse(table: &Table, params: &[(String, String)]) -> Result<Self, String> {
+        let mut query = ListQuery {
+            limit: DEFAULT_LIMIT,
+            offset: 0,
+            after: None,
+            order_by: Vec::new(),
+            filters: Vec::new(),
+        };
+
+        for (key, raw) in params {
+            match key.as_str() {
+                "limit" => {
+                    query.limit = raw
+                        .parse()
+                        .ok()
+                        .filter(|l| (1..=MAX_LIMIT).contains(l))
+                        .ok_or_else(|| format!("limit must be between 1 and {}", MAX_LIMIT))?;
+                }
+                "offset" => {
+                    query.offset = raw
+                        .parse()
+                        .ok()
+                        .filter(|o: &i64| *o >= 0)
+                        .ok_or_else(|| "offset must be a non-negative integer".to_string())?;
+                }
+                "after" => {
+                    let values = raw
+                        .split(',')
+                        .map(|v| v.trim().parse::<i32>())
+                        .collect::<Result<Vec<_>, _>>()
+                        .map_err(|_| format!("Invalid cursor '{}'", raw))?;
+                    if values.len() != table.key.len() {
+                        return Err(format!(
+                            "Cursor must contain {} value(s): {}",
+                            table.key.len(),
+                            table.key.join(",")
+                        ));
+                    }
+                    query.after = Some(values);
+                }
+                "order_by" => {
+                    for part in raw.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+                        let (name, desc) = match part.strip_prefix('-') {
+                            Some(name) => (name, true),
+                            None => (part, false),
+                        };
+                        let (column, _) = table
+                            .column(name)
+                            .ok_or_else(|| format!("Unknown column '{}' in order_by", name))?;
+                        query.order_by.push((column, desc));
+                    }
+                }
+                _ => {
+                    let (name, op) = match key.split_once('.') {
+                        Some((name, op)) => (
+                            name,
+                            FilterOp::parse(op)
+                                .ok_or_else(|| format!("Unknown filter operator '{}'", op))?,
+                        ),
+                        None => (key.as_str(), FilterOp::Eq),
+                    };
+                    let (column, ty) = table
+                        .column(name)
+                        .ok_or_else(|| format!("Unknown query parameter '{}'", name))?;
+                    if op != FilterOp::Eq && matches!(ty, ColumnType::Text | ColumnType::Bool) {
+                        return Err(format!("Column {} only supports equality filters", column));
+                    }
+                    query.filters.push(Filter {
+                        column,
+                        op,
+                        value: SqlValue::parse(ty, column, raw)?,
+                    });
+                }
+            }
+        }
+
+        if query.after.is_some() && (!query.order_by.is_empty() || query.offset != 0) {
+            return Err("after cannot be combined with order_by or offset".to_string());
+        }
+
+        Ok(query)
+    }
+
+    /// Whether the query filters or sorts by `column`.
+    pub fn references(&self, column: &str) -> bool {
+        self.filters.iter().any(|f| f.column == column)
+            || self.order_by.iter().any(|(c, _)| *c == column)
+    }
+
+    /// Cursors are only handed out for the default key order, where they are stable.
+    pub fn uses_key_order(&self) -> bool {
+        self.order_by.is_empty()
+    }
+
+    fn where_clause<'a>(&'a self, params: &mut Vec<&'a (dyn ToSql + Sync)>) -> String {
+        let mut conditions = Vec::new();
+        for filter in &self.filters {
+            params.push(filter.value.as_sql());
+            conditions.push(format!(
+                "{} {} ${}",
+                filter.column,
+                filter.op.sql(),
+                params.len()
+            ));
+        }
+        if conditions.is_empty() {
+            String::new()
+        } else {
+            format!(" WHERE {}", conditions.join(" AND "))
+        }
+    }
+
+    /// `SELECT count(*)` over the filtered table, ignoring paging.
+    pub fn count_sql<'a>(&'a self, table: &Table) -> (String, Vec<&'a (dyn ToSql + Sync)>) {
+        let mut params = Vec::new();
+        let where_clause = self.where_clause(&mut params);
+        (
+            format!("SELECT count(*) FROM {}{}", table.name, where_clause),
+            params,
+        )
+    }
+
+    /// The page itself. Rows are always ordered by the key last so that
+    /// paging is deterministic.
+    pub fn select_sql<'a>(&'a self, table: &Table) -> (String, Vec<&'a (dyn ToSql + Sync)>) {
+        let mut params = Vec::new();
+        let mut sql = format!(
+            "SELECT {} FROM {}{}",
+            table.select_list(),
+            table.name,
+            self.where_clause(&mut params)
+        );
+
+        if let Some(after) = &self.after {
+            let placeholders: Vec<String> = after
+                .iter()
+                .map(|v| {
+                    params.push(v);
+                    format!("${}", params.len())
+                })
+                .collect();
+            sql.push_str(if self.filters.is_empty() {
+                " WHERE "
+            } else {
+                " AND "
+            });
+            sql.push_str(&format!(
+                "({}) > ({})",
+                table.key.join(", "),
+                placeholders.join(", ")
+            ));
+        }
+
+        let mut order: Vec<String> = self
+            .order_by
+            .iter()
+            .map(|(c, desc)| format!("{}{}", c, if *desc { " DESC" } else { "" }))
+            .collect();
+        order.extend(
+            table
+                .key
+                .iter()
+                .filter(|k| !self.order_by.iter().any(|(c, _)| c == *k))
+                .map(|k| k.to_string()),
+        );
+        sql.push_str(&format!(" ORDER BY {}", order.join(", ")));
+
+        params.push(&self.limit);
+        sql.push_str(&format!(" LIMIT ${}", params.len()));
+        params.push(&self.offset);
+        sql.push_str(&format!(" OFFSET ${}", params.len()));
+
+        (sql, params)
+    }
+}
+
+/// Partial update parsed from a PATCH body. Fields absent from the JSON object
+/// are left untouched; an explicit `null` clears a nullable column.
+#[derive(Debug, Clone)]
+pub struct Patch {
+    changes: Vec<(&'static str, Option<SqlValue>)>,
+}
+
+impl Patch {
+    pub fn parse(table: &Table, object: &Map<String, Value>) -> Result<Self, String> {
+        let mut changes = Vec::new();
+        for (name, value) in object {
+            let (column, ty) = table
+                .column(name)
+                .ok_or_else(|| format!("Unknown field '{}'", name))?;
+            if table.key.contains(&column) {
+                return Err(format!("Key field {} cannot be changed", column));
+            }
+            let value = match value {
+                Value::Null if table.nullable.contains(&column) => None,
+                Value::Null => return Err(format!("Field {} cannot be null", column)),
+                value => Some(SqlValue::from_json(ty, column, value)?),
+            };
+            changes.push((column, value));
+        }
+        Ok(Patch { changes })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Rewrites the new value of a text column, if the patch sets one; `f`
+    /// returning `None` keeps the value as given.
+    pub fn map_text(&mut self, column: &str, f: impl Fn(&str) -> Option<String>) {
+        for (name, value) in &mut self.changes {
+            if *name == column
+                && let Some(SqlValue::Text(text)) = value
+                && let Some(mapped) = f(text)
+            {
+                *text = mapped;
+            }
+        }
+    }
+
+    /// `UPDATE ... RETURNING` the whole row, so the caller gets the result of
+    /// the change. Must not be called on an empty patch.
+    pub fn update_sql<'a>(
+        &'a self,
+        table: &Table,
+        key: &'a [i32],
+    ) -> (String, Vec<&'a (dyn ToSql + Sync)>) {
+        let mut params: Vec<&'a (dyn ToSql + Sync)> = Vec::new();
+        let assignments: Vec<String> = self
+            .changes
+            .iter()
+            .map(|(column, value)| match value {
+                Some(value) => {
+                    params.push(value.as_sql());
+                    format!("{} = ${}", column, params.len())
+                }
+                None => format!("{} = NULL", column),
+            })
+            .collect();
+        let condition = table.key_condition(params.len() + 1);
+        params.extend(key.iter().map(|k| k as &(dyn ToSql + Sync)));
+        (
+            format!(
+                "UPDATE {} SET {} WHERE {} RETURNING {}",
+                table.name,
+                assignments.join(", "),
+                condition,
+                table.versioned_select_list()
+            ),
+            params,
+        )
+    }
+}
+
+/// One page of a list endpoint plus what the client needs to fetch the rest.
+#[derive(Debug, Clone)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub total: i64,
+    pub next_cursor: Option<String>,
+}
+
+impl<T> Page<T> {
+    /// `X-Total-Count` and, when there may be more rows, `X-Next-Cursor`.
+    pub fn headers(&self) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(TOTAL_COUNT_HEADER, HeaderValue::from(self.total));
+        if let Some(cursor) = self
+            .next_cursor
+            .as_deref()
+            .and_then(|c| HeaderValue::from_str(c).ok())
+        {
+            headers.insert(NEXT_CURSOR_HEADER, cursor);
+        }
+        headers
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RIZENI: Table = Table {
+        name: "rizeni",
+        columns: &[
+            ("id", ColumnType::Int),
+            ("rok", ColumnType::Int),
+            ("cislo_rizeni", ColumnType::Int),
+            ("predmet", ColumnType::Text),
+        ],
+        key: &["id"],
+        nullable: &["predmet"],
+    };
+
+    fn parse(params: &[(&str, &str)]) -> Result<ListQuery, String> {
+        let params: Vec<(String, String)> = params
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        ListQuery::parse(&RIZENI, &params)
+    }
+
+    fn select(params: &[(&str, &str)]) -> (String, usize) {
+        let query = parse(params).unwrap();
+        let (sql, params) = query.select_sql(&RIZENI);
+        (sql, params.len())
+    }
+
+    #[test]
+    fn defaults_to_the_first_page_in_key_order() {
+        let query = parse(&[]).unwrap();
+        assert_eq!((query.limit, query.offset), (DEFAULT_LIMIT, 0));
+        assert!(query.uses_key_order());
+        assert_eq!(
+            select(&[]),
+            (
+                "SELECT id, rok, cislo_rizeni, predmet FROM rizeni ORDER BY id LIMIT $1 OFFSET $2"
+                    .to_string(),
+                2
+            )
+        );
+    }
+
+    #[test]
+    fn bounds_limit_and_offset() {
+        assert_eq!(parse(&[("limit", "1000")]).unwrap().limit, MAX_LIMIT);
+        for limit in ["0", "1001", "-1", "ten"] {
+            assert!(parse(&[("limit", limit)]).is_err(), "limit={}", limit);
+        }
+        assert_eq!(parse(&[("offset", "20")]).unwrap().offset, 20);
+        assert!(parse(&[("offset", "-1")]).is_err());
+    }
+
+    #[test]
+    fn sorts_by_the_listed_columns_then_the_key() {
+        assert_eq!(
+            select(&[("order_by", "-rok, cislo_rizeni")]).0,
+            "SELECT id, rok, cislo_rizeni, predmet FROM rizeni ORDER BY rok DESC, cislo_rizeni, id LIMIT $1 OFFSET $2"
+        );
+        assert!(
+            select(&[("order_by", "-id")])
+                .0
+                .contains("ORDER BY id DESC LIMIT")
+        );
+        assert!(parse(&[("order_by", "datum")]).is_err());
+    }
+
+    #[test]
+    fn filters_by_equality_and_range() {
+        let (sql, params) = select(&[("rok.gte", "2020"), ("rok.lt", "2024"), ("predmet", "x")]);
+        assert!(
+            sql.contains(" WHERE rok >= $1 AND rok < $2 AND predmet = $3 ORDER BY id LIMIT $4")
+        );
+        assert_eq!(params, 5);
+
+        assert!(parse(&[("rok.like", "2020")]).is_err());
+        assert!(parse(&[("predmet.gt", "a")]).is_err());
+        assert!(parse(&[("rok", "loni")]).is_err());
+        assert!(parse(&[("datum", "2020-01-01")]).is_err());
+    }
+
+    #[test]
+    fn continues_after_a_cursor() {
+        let (sql, params) = select(&[("rok", "2020"), ("after", "41")]);
+        assert!(sql.contains(" WHERE rok = $1 AND (id) > ($2) ORDER BY id LIMIT $3 OFFSET $4"));
+        assert_eq!(params, 4);
+
+        assert!(parse(&[("after", "1,2")]).is_err());
+        assert!(parse(&[("after", "x")]).is_err());
+        assert!(parse(&[("after", "1"), ("order_by", "rok")]).is_err());
+        assert!(parse(&[("after", "1"), ("offset", "10")]).is_err());
+    }
+
+    fn parse_patch(body: Value) -> Result<Patch, String> {
+        match body {
+            Value::Object(object) => Patch::parse(&RIZENI, &object),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn patches_only_the_fields_present() {
+        let patch = parse_patch(serde_json::json!({"rok": 2024})).unwrap();
+        let (sql, params) = patch.update_sql(&RIZENI, &[7]);
+        assert!(sql.starts_with("UPDATE rizeni SET rok = $1 WHERE id = $2 RETURNING "));
+        assert_eq!(params.len(), 2);
+        assert!(parse_patch(serde_json::json!({})).unwrap().is_empty());
+    }
+
+    #[test]
+    fn null_clears_only_nullable_fields() {
+        let patch = parse_patch(serde_json::json!({"predmet": null, "rok": 2024})).unwrap();
+        let (sql, params) = patch.update_sql(&RIZENI, &[7]);
+        assert!(sql.starts_with("UPDATE rizeni SET predmet = NULL, rok = $1 WHERE id = $2 "));
+        assert_eq!(params.len(), 2);
+
+        let error = parse_patch(serde_json::json!({"rok": null})).unwrap_err();
+        assert_eq!(error, "Field rok cannot be null");
+    }
+
+    #[test]
+    fn rejects_keys_unknown_fields_and_bad_values() {
+        assert!(parse_patch(serde_json::json!({"id": 8})).is_err());
+        assert!(parse_patch(serde_json::json!({"datum": "2024-01-01"})).is_err());
+        assert!(parse_patch(serde_json::json!({"rok": "2024"})).is_err());
+    }
+}
@@ -1,18 +1,26 @@
 use anyhow::Result;
-use axum::{Extension, Router, middleware, routing::get};
-use clap::Parser;
+use axum::{
+    Extension, Router, middleware,
+    routing::{get, post},
+};
+use clap::{Parser, ValueEnum};
 use deadpool_postgres::{Config, ManagerConfig, RecyclingMethod, Runtime};
 use katastr_server::{
-    AppState, bpej_handler, bremeno_parcela_majitel_handler, bremeno_parcela_parcela_handler,
-    get_authenticate, get_health, get_lv_data, get_parceala_data, get_spravni_rizeni,
-    katastralni_uzemi_handler, kraj_handler, list_vlastnictvi_handler, majitel_handler,
-    obec_handler, okres_handler, parcela_row_handler, plomba_handler, require_auth_cookie,
-    rizeni_handler, rizeni_operace_row_handler, track_latency, typ_operace_handler,
-    typ_rizeni_handler, typ_ucastnika_handler, ucast_handler, ucastnik_rizeni_handler,
-    vlastnictvi_handler,
+    AppState, LoginThrottle, LoginThrottleConfig, MemorySessionStore, PostgresSessionStore,
+    SessionStore, api_token_handler, audit_handler, batch_handler, bpej_handler,
+    bremeno_parcela_majitel_handler, bremeno_parcela_parcela_handler, bulk_handler,
+    ensure_admin_user, ensure_schema, get_authenticate, get_health, get_lv_balance, get_lv_data,
+    get_lv_diff, get_lv_verify, get_parceala_data, get_spravni_rizeni, katastralni_uzemi_handler,
+    kraj_handler, list_vlastnictvi_handler, majitel_handler, nabyvaci_titul_handler, obec_handler,
+    okres_handler, parcela_row_handler, plomba_handler, post_authenticate, post_logout,
+    require_auth_cookie, rizeni_handler, rizeni_operace_row_handler, spawn_session_sweeper,
+    track_latency, typ_operace_handler, typ_rizeni_handler, typ_ucastnika_handler, ucast_handler,
+    ucastnik_rizeni_handler, user_handler, vlastnictvi_handler,
 };
 use mimalloc::MiMalloc;
 use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
 use tokio_postgres::NoTls;
 use tower_http::compression::CompressionLayer;
 use tracing::info;
@@ -26,9 +34,28 @@ struct Args {
     #[arg(long, default_value_t = false)]
     no_print: bool,
 
+    /// Password of the initial admin account, created when no users exist yet
     #[arg(short, long, default_value = "")]
     server_password: String,
 
+    /// Also accept `GET /auth?username=..&password=..` (leaks credentials into access logs)
+    #[arg(long, default_value_t = false)]
+    allow_get_auth: bool,
+
+    /// Require a valid session for GET requests as well
+    #[arg(long, default_value_t = false)]
+    require_auth_for_reads: bool,
+
+    /// Path that stays readable without a session when reads require auth, e.g. `/lv`;
+    /// matched exactly, or with a trailing `/*` every path below it, e.g. `/majitel/*`
+    /// (repeatable; `/health`, `/auth` and `/logout` are always public)
+    #[arg(long = "public-read-path")]
+    public_read_paths: Vec<String>,
+
+    /// Username of the initial admin account
+    #[arg(long, default_value = "admin")]
+    admin_username: String,
+
     #[arg(long, default_value_t = 3000)]
     server_port: u16,
 
@@ -45,10 +72,49 @@ struct Args {
     db_password: String,
     #[arg(long, default_value_t = 5432)]
     db_port: u16,
+
+    /// Seconds of inactivity after which a session expires
+    #[arg(long, default_value_t = 1800)]
+    session_idle_timeout: u64,
+
+    /// Seconds after login after which a session expires regardless of activity
+    #[arg(long, default_value_t = 3600)]
+    session_max_age: u64,
+
+    /// Where sessions are kept: `memory` (lost on restart) or `postgres` (shared by all instances)
+    #[arg(long, value_enum, default_value_t = SessionBackend::Memory)]
+    session_store: SessionBackend,
+
+    /// How often (in seconds) expired sessions are purged
+    #[arg(long, default_value_t = 60)]
+    session_sweep_interval: u64,
+
+    /// Login attempts allowed per client IP per minute
+    #[arg(long, default_value_t = 10)]
+    auth_attempts_per_ip: u32,
+
+    /// Login attempts allowed per minute across all clients
+    #[arg(long, default_value_t = 120)]
+    auth_attempts_global: u32,
+
+    /// Consecutive failed logins after which a client IP is locked out
+    #[arg(long, default_value_t = 10)]
+    auth_lockout_threshold: u32,
+
+    /// Seconds a locked out client IP has to wait
+    #[arg(long, default_value_t = 900)]
+    auth_lockout_duration: u64,
+}
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum SessionBackend {
+    Memory,
+    Postgres,
 }
+
 fn main() {
     let rt = tokio::runtime::Builder::new_multi_thread()
         .enable_io()
+        .enable_time()
         .worker_threads(num_cpus::get())
         .build()
         .unwrap();
@@ -82,32 +148,89 @@ async fn run() -> Result<()> {
     cfg.host = Some(args.db_host.clone());
     cfg.port = Some(args.db_port);
     cfg.password = Some(args.db_password.to_string());
-    cfg.pool = Some(deadpool_postgres::PoolConfig::new(num_cpus::get() / 2));
+    cfg.pool = Some(deadpool_postgres::PoolConfig::new(
+        (num_cpus::get() / 2).max(1),
+    ));
     cfg.manager = Some(ManagerConfig {
         recycling_method: RecyclingMethod::Fast,
     });
     let pool = cfg.create_pool(Some(Runtime::Tokio1), NoTls)?;
-    let password;
-    match args.server_password.is_empty() {
+    let password = match args.server_password.is_empty() {
         true => {
             // Default password hash (cost 12)
-            password = "$2b$12$rgOkHM0IWEmHYTidLt2WmeQANUGlG1wJxwSeoFX/XPltU/8okgKW6".to_string();
+            "$2b$12$rgOkHM0IWEmHYTidLt2WmeQANUGlG1wJxwSeoFX/XPltU/8okgKW6".to_string()
         }
         false => {
             // User provided password: use DEFAULT_COST (12)
-            password = bcrypt::hash(args.server_password, bcrypt::DEFAULT_COST)?;
+            bcrypt::hash(args.server_password, bcrypt::DEFAULT_COST)?
         }
+    };
+
+    ensure_schema(pool.clone()).await?;
+    if ensure_admin_user(pool.clone(), &args.admin_username, &password).await? {
+        info!("Created initial admin user '{}'", args.admin_username);
     }
 
+    let idle_timeout = Duration::from_secs(args.session_idle_timeout);
+    let max_age = Duration::from_secs(args.session_max_age);
+    let sessions: Arc<dyn SessionStore> = match args.session_store {
+        SessionBackend::Memory => Arc::new(MemorySessionStore::new(idle_timeout, max_age)),
+        SessionBackend::Postgres => Arc::new(PostgresSessionStore::new(
+            pool.clone(),
+            idle_timeout,
+            max_age,
+        )),
+    };
+    info!("Using {:?} session store", args.session_store);
+
     let state = AppState {
-        password: password.to_string(),
         no_print: args.no_print,
-        sessions: std::sync::Arc::new(std::sync::RwLock::new(std::collections::HashMap::new())),
+        sessions: sessions.clone(),
+        session_max_age: max_age,
+        require_auth_for_reads: args.require_auth_for_reads,
+        public_read_paths: Arc::new(args.public_read_paths.clone()),
+        login_throttle: Arc::new(LoginThrottle::new(LoginThrottleConfig {
+            per_ip_per_minute: args.auth_attempts_per_ip,
+            global_per_minute: args.auth_attempts_global,
+            lockout_threshold: args.auth_lockout_threshold.max(1),
+            lockout_duration: Duration::from_secs(args.auth_lockout_duration),
+        })),
     };
+    spawn_session_sweeper(
+        sessions,
+        Duration::from_secs(args.session_sweep_interval.max(1)),
+    );
+
+    let auth_route = if args.allow_get_auth {
+        post(post_authenticate).get(get_authenticate)
+    } else {
+        post(post_authenticate)
+    };
+
     let app = Router::new()
         .route("/health", get(get_health))
-        .route("/auth", get(get_authenticate))
+        .route("/auth", auth_route)
+        .route("/logout", post(post_logout))
+        .route(
+            "/user",
+            get(user_handler)
+                .post(user_handler::create)
+                .put(user_handler::update)
+                .delete(user_handler::delete),
+        )
+        .route(
+            "/api_token",
+            get(api_token_handler)
+                .post(api_token_handler::create)
+                .delete(api_token_handler::delete),
+        )
+        .route("/audit", get(audit_handler))
+        .route("/batch", post(batch_handler))
+        .route("/bulk/{entity}", post(bulk_handler))
         .route("/lv", get(get_lv_data))
+        .route("/lv/diff", get(get_lv_diff))
+        .route("/lv/verify", get(get_lv_verify))
+        .route("/lv/balance", get(get_lv_balance))
         .route("/parcela", get(get_parceala_data))
         .route("/spravni_rizeni", get(get_spravni_rizeni))
         .route(
@@ -117,6 +240,10 @@ async fn run() -> Result<()> {
                 .put(majitel_handler::update)
                 .delete(majitel_handler::delete),
         )
+        .route(
+            "/majitel/{id}",
+            get(majitel_handler::get_one).patch(majitel_handler::patch),
+        )
         .route(
             "/kraj",
             get(kraj_handler)
@@ -124,6 +251,10 @@ async fn run() -> Result<()> {
                 .put(kraj_handler::update)
                 .delete(kraj_handler::delete),
         )
+        .route(
+            "/kraj/{id}",
+            get(kraj_handler::get_one).patch(kraj_handler::patch),
+        )
         .route(
             "/okres",
             get(okres_handler)
@@ -131,6 +262,10 @@ async fn run() -> Result<()> {
                 .put(okres_handler::update)
                 .delete(okres_handler::delete),
         )
+        .route(
+            "/okres/{id}",
+            get(okres_handler::get_one).patch(okres_handler::patch),
+        )
         .route(
             "/obec",
             get(obec_handler)
@@ -138,6 +273,10 @@ async fn run() -> Result<()> {
                 .put(obec_handler::update)
                 .delete(obec_handler::delete),
         )
+        .route(
+            "/obec/{id}",
+            get(obec_handler::get_one).patch(obec_handler::patch),
+        )
         .route(
             "/katastralni_uzemi",
             get(katastralni_uzemi_handler)
@@ -145,6 +284,10 @@ async fn run() -> Result<()> {
                 .put(katastralni_uzemi_handler::update)
                 .delete(katastralni_uzemi_handler::delete),
         )
+        .route(
+            "/katastralni_uzemi/{id}",
+            get(katastralni_uzemi_handler::get_one).patch(katastralni_uzemi_handler::patch),
+        )
         .route(
             "/bpej",
             get(bpej_handler)
@@ -152,6 +295,10 @@ async fn run() -> Result<()> {
                 .put(bpej_handler::update)
                 .delete(bpej_handler::delete),
         )
+        .route(
+            "/bpej/{id}",
+            get(bpej_handler::get_one).patch(bpej_handler::patch),
+        )
         .route(
             "/typ_rizeni",
             get(typ_rizeni_handler)
@@ -159,6 +306,10 @@ async fn run() -> Result<()> {
                 .put(typ_rizeni_handler::update)
                 .delete(typ_rizeni_handler::delete),
         )
+        .route(
+            "/typ_rizeni/{id}",
+            get(typ_rizeni_handler::get_one).patch(typ_rizeni_handler::patch),
+        )
         .route(
             "/typ_operace",
             get(typ_operace_handler)
@@ -166,6 +317,10 @@ async fn run() -> Result<()> {
                 .put(typ_operace_handler::update)
                 .delete(typ_operace_handler::delete),
         )
+        .route(
+            "/typ_operace/{id}",
+            get(typ_operace_handler::get_one).patch(typ_operace_handler::patch),
+        )
         .route(
             "/typ_ucastnika",
             get(typ_ucastnika_handler)
@@ -173,6 +328,10 @@ async fn run() -> Result<()> {
                 .put(typ_ucastnika_handler::update)
                 .delete(typ_ucastnika_handler::delete),
         )
+        .route(
+            "/typ_ucastnika/{id}",
+            get(typ_ucastnika_handler::get_one).patch(typ_ucastnika_handler::patch),
+        )
         .route(
             "/ucastnik_rizeni",
             get(ucastnik_rizeni_handler)
@@ -180,6 +339,10 @@ async fn run() -> Result<()> {
                 .put(ucastnik_rizeni_handler::update)
                 .delete(ucastnik_rizeni_handler::delete),
         )
+        .route(
+            "/ucastnik_rizeni/{id}",
+            get(ucastnik_rizeni_handler::get_one).patch(ucastnik_rizeni_handler::patch),
+        )
         .route(
             "/list_vlastnictvi",
             get(list_vlastnictvi_handler)
@@ -187,6 +350,10 @@ async fn run() -> Result<()> {
                 .put(list_vlastnictvi_handler::update)
                 .delete(list_vlastnictvi_handler::delete),
         )
+        .route(
+            "/list_vlastnictvi/{id}",
+            get(list_vlastnictvi_handler::get_one).patch(list_vlastnictvi_handler::patch),
+        )
         .route(
             "/parcela_row",
             get(parcela_row_handler)
@@ -194,6 +361,10 @@ async fn run() -> Result<()> {
                 .put(parcela_row_handler::update)
                 .delete(parcela_row_handler::delete),
         )
+        .route(
+            "/parcela_row/{id}",
+            get(parcela_row_handler::get_one).patch(parcela_row_handler::patch),
+        )
         .route(
             "/rizeni",
             get(rizeni_handler)
@@ -201,6 +372,21 @@ async fn run() -> Result<()> {
                 .put(rizeni_handler::update)
                 .delete(rizeni_handler::delete),
         )
+        .route(
+            "/rizeni/{id}",
+            get(rizeni_handler::get_one).patch(rizeni_handler::patch),
+        )
+        .route(
+            "/nabyvaci_titul",
+            get(nabyvaci_titul_handler)
+                .post(nabyvaci_titul_handler::create)
+                .put(nabyvaci_titul_handler::update)
+                .delete(nabyvaci_titul_handler::delete),
+        )
+        .route(
+            "/nabyvaci_titul/{id}",
+            get(nabyvaci_titul_handler::get_one).patch(nabyvaci_titul_handler::patch),
+        )
         .route(
             "/vlastnictvi",
             get(vlastnictvi_handler)
@@ -208,6 +394,10 @@ async fn run() -> Result<()> {
                 .put(vlastnictvi_handler::update)
                 .delete(vlastnictvi_handler::delete),
         )
+        .route(
+            "/vlastnictvi/{parcela_id}/{majitel_id}",
+            get(vlastnictvi_handler::get_one).patch(vlastnictvi_handler::patch),
+        )
         .route(
             "/bremeno_parcela_parcela",
             get(bremeno_parcela_parcela_handler)
@@ -215,6 +405,11 @@ async fn run() -> Result<()> {
                 .put(bremeno_parcela_parcela_handler::update)
                 .delete(bremeno_parcela_parcela_handler::delete),
         )
+        .route(
+            "/bremeno_parcela_parcela/{parcela_id}/{parcela_povinna_id}",
+            get(bremeno_parcela_parcela_handler::get_one)
+                .patch(bremeno_parcela_parcela_handler::patch),
+        )
         .route(
             "/bremeno_parcela_majitel",
             get(bremeno_parcela_majitel_handler)
@@ -222,6 +417,11 @@ async fn run() -> Result<()> {
                 .put(bremeno_parcela_majitel_handler::update)
                 .delete(bremeno_parcela_majitel_handler::delete),
         )
+        .route(
+            "/bremeno_parcela_majitel/{parcela_id}/{majitel_povinny_id}",
+            get(bremeno_parcela_majitel_handler::get_one)
+                .patch(bremeno_parcela_majitel_handler::patch),
+        )
         .route(
             "/rizeni_operace",
             get(rizeni_operace_row_handler)
@@ -229,25 +429,39 @@ async fn run() -> Result<()> {
                 .put(rizeni_operace_row_handler::update)
                 .delete(rizeni_operace_row_handler::delete),
         )
+        .route(
+            "/rizeni_operace/{rizeni_id}/{typ_operace_id}",
+            get(rizeni_operace_row_handler::get_one).patch(rizeni_operace_row_handler::patch),
+        )
         .route(
             "/plomba",
             get(plomba_handler)
                 .post(plomba_handler::create)
                 .delete(plomba_handler::delete),
         )
+        .route(
+            "/plomba/{rizeni_id}/{parcela_id}",
+            get(plomba_handler::get_one),
+        )
         .route(
             "/ucast",
             get(ucast_handler)
                 .post(ucast_handler::create)
                 .delete(ucast_handler::delete),
         )
-        .with_state(pool)
+        .route(
+            "/ucast/{rizeni_id}/{ucastnik_rizeni_id}/{typ_ucastnika_id}",
+            get(ucast_handler::get_one),
+        )
+        .with_state(pool.clone())
         .layer(Extension(state.clone()))
         .layer(middleware::from_fn({
             let s = state.clone();
+            let p = pool.clone();
             move |req, next| {
                 let s = s.clone();
-                async move { require_auth_cookie(s, req, next).await }
+                let p = p.clone();
+                async move { require_auth_cookie(s, p, req, next).await }
             }
         }))
         .layer(CompressionLayer::new())
@@ -271,9 +485,13 @@ async fn run() -> Result<()> {
     info!("Server running on http://{}", ip);
     info!("Press 'q' then Enter to stop");
 
-    axum::serve(listener, app)
-        .with_graceful_shutdown(wait_for_q())
-        .await?;
+    // Client addresses feed the login throttle
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(wait_for_q())
+    .await?;
 
     Ok(())
 }
@@ -1,12 +1,47 @@
-use crate::models::AppState;
-use axum::{extract::Request, http::StatusCode, middleware::Next, response::IntoResponse};
+use crate::db::authenticate_api_token;
+use crate::error::AppError;
+use crate::models::{AppState, Role, User};
+use crate::session::SESSION_COOKIE;
+use crate::token::hash_api_token;
+use axum::http::Uri;
+use axum::{
+    extract::Request,
+    http::{HeaderMap, Method},
+    middleware::Next,
+    response::IntoResponse,
+};
+use deadpool_postgres::Pool;
 use std::time::Instant;
 use tracing::info;
 
+/// Query parameters whose values must never reach the logs.
+const SENSITIVE_QUERY_PARAMS: &[&str] = &["password", "token", "access_token", "secret"];
+
+/// Renders the URI for logging with sensitive query parameter values masked.
+pub fn redact_uri(uri: &Uri) -> String {
+    let Some(query) = uri.query() else {
+        return uri.to_string();
+    };
+
+    let redacted: Vec<String> = query
+        .split('&')
+        .map(|pair| match pair.split_once('=') {
+            Some((key, _))
+                if SENSITIVE_QUERY_PARAMS.contains(&key.to_ascii_lowercase().as_str()) =>
+            {
+                format!("{}=***", key)
+            }
+            _ => pair.to_string(),
+        })
+        .collect();
+
+    format!("{}?{}", uri.path(), redacted.join("&"))
+}
+
 pub async fn track_latency(req: Request, next: Next) -> impl IntoResponse {
     let start = Instant::now();
     let method = req.method().clone();
-    let uri = req.uri().clone();
+    let uri = redact_uri(req.uri());
     let response = next.run(req).await;
     let duration = start.elapsed();
 
@@ -25,90 +60,251 @@ pub async fn track_latency(req: Request, next: Next) -> impl IntoResponse {
     response
 }
 
-pub async fn require_auth_cookie(state: AppState, req: Request, next: Next) -> impl IntoResponse {
-    // Allow safe methods without auth
+/// First path segment, e.g. `/majitel` for `/majitel/42`; access rules are per resource.
+pub fn resource(path: &str) -> &str {
+    match path[1..].find('/') {
+        Some(end) => &path[..end + 1],
+        None => path,
+    }
+}
 
-    let method = req.method().clone();
+/// Minimum role a request needs; `None` means it is open to anonymous callers.
+pub fn required_role(state: &AppState, method: &Method, path: &str) -> Option<Role> {
+    if method == Method::OPTIONS {
+        return None;
+    }
 
-    let path = req.uri().path().to_string();
+    let resource = resource(path);
 
-    info!("Auth middleware entry: {} {}", method, path);
+    // User and token management and the audit log are admin-only, including listing
+    if resource == "/user" || resource == "/api_token" || resource == "/audit" {
+        return Some(Role::Admin);
+    }
 
-    if method == axum::http::Method::GET || method == axum::http::Method::OPTIONS {
-        info!("Auth middleware: allowing safe method {}", method);
+    // Integrity reports scan the whole register, so they are for clerks
+    if path == "/lv/verify" || path == "/lv/balance" {
+        return Some(Role::Clerk);
+    }
 
-        return next.run(req).await;
+    if method == Method::GET || method == Method::HEAD {
+        // Reads are anonymous unless the server is locked down, in which case
+        // only the configured allowlist stays public
+        if state.require_auth_for_reads
+            && !state
+                .public_read_paths
+                .iter()
+                .any(|p| is_public_read_path(p, path))
+        {
+            return Some(Role::Reader);
+        }
+        return None;
     }
 
-    // Allow health and auth endpoints without the cookie
+    Some(write_role(resource))
+}
+
+/// Role needed to change a resource; also checked per operation by `/batch`.
+pub fn write_role(resource: &str) -> Role {
+    match resource {
+        // Code lists are reference data maintained by admins
+        "/kraj" | "/okres" | "/obec" | "/katastralni_uzemi" | "/bpej" | "/typ_rizeni"
+        | "/typ_operace" | "/typ_ucastnika" => Role::Admin,
+        _ => Role::Clerk,
+    }
+}
+
+/// Whether an allowlisted path covers a request path: exactly, or for a
+/// pattern ending in `/*`, any path below it. `/majitel` opens `/majitel`
+/// but not `/majitel/5`.
+pub fn is_public_read_path(pattern: &str, path: &str) -> bool {
+    match pattern.strip_suffix("/*") {
+        Some(prefix) => path
+            .strip_prefix(prefix)
+            .is_some_and(|rest| rest.len() > 1 && rest.starts_with('/')),
+        None => pattern == path,
+    }
+}
+
+pub async fn require_auth_cookie(
+    state: AppState,
+    pool: Pool,
+    mut req: Request,
+    next: Next,
+) -> impl IntoResponse {
+    let method = req.method().clone();
 
-    if path == "/health" || path == "/auth" {
+    let path = req.uri().path().to_string();
+
+    info!("Auth middleware entry: {} {}", method, path);
+
+    // Allow health, auth and logout endpoints without the cookie
+
+    if path == "/health" || path == "/auth" || path == "/logout" {
         info!("Auth middleware: allowing public path {}", path);
 
         return next.run(req).await;
     }
 
-    // Parse cookie header for `katastr_session`
+    let required = required_role(&state, &method, &path);
 
-    let cookie_header = req
-        .headers()
-        .get(axum::http::header::COOKIE)
-        .and_then(|v| v.to_str().ok());
+    // An `Authorization: Bearer` API token takes precedence over the session cookie
 
-    info!("Auth middleware: cookie header = {:?}", cookie_header);
+    let bearer = bearer_token(req.headers());
 
-    let cookie_value = cookie_header.and_then(|s| {
-        s.split(';').map(|pair| pair.trim()).find_map(|pair| {
-            let mut parts = pair.splitn(2, '=');
+    let session_id = if bearer.is_none() {
+        session_cookie(req.headers())
+    } else {
+        None
+    };
 
-            let key = parts.next()?;
+    let user = if let Some(token) = &bearer {
+        match authenticate_api_token(pool, &hash_api_token(token)).await {
+            Ok(Some(user)) => Some(user),
 
-            let val = parts.next()?;
+            // A presented but unknown or revoked token is always an error, even
+            // on paths that would be open anonymously
+            Ok(None) => {
+                info!("Auth middleware: invalid or revoked API token - returning 401");
 
-            if key == "katastr_session" {
-                Some(val.to_string())
-            } else {
-                None
+                return AppError::Unauthorized("Invalid API token".to_string()).into_response();
             }
-        })
-    });
 
-    let session_id = match cookie_value {
-        Some(v) => {
-            info!("Auth middleware: found cookie katastr_session={v}");
+            Err(e) => {
+                return AppError::Internal(format!("Auth middleware: token lookup error: {}", e))
+                    .into_response();
+            }
+        }
+    } else {
+        if session_id.is_some() {
+            info!("Auth middleware: found cookie {SESSION_COOKIE}");
+        }
 
-            v
+        // Check that the session exists and has not expired; touch it if it is alive
+
+        match &session_id {
+            Some(session_id) => match session_user(&state, session_id).await {
+                Ok(user) => user,
+
+                Err(response) => return response,
+            },
+            None => None,
         }
+    };
 
-        None => {
-            info!("Auth middleware: missing auth cookie - returning 401");
+    let Some(required) = required else {
+        info!("Auth middleware: allowing safe method {}", method);
 
-            return (StatusCode::UNAUTHORIZED, "Missing auth cookie".to_string()).into_response();
+        if let Some(user) = user {
+            req.extensions_mut().insert(user);
         }
+        return next.run(req).await;
     };
 
-    // Check if session exists in memory
+    let Some(user) = user else {
+        if session_id.is_none() {
+            info!("Auth middleware: missing credentials - returning 401");
 
-    let is_valid = {
-        let sessions = match state.sessions.read() {
-            Ok(s) => s,
+            return AppError::Unauthorized("Missing auth cookie".to_string()).into_response();
+        }
 
-            Err(_) => {
-                return (StatusCode::INTERNAL_SERVER_ERROR, "Lock error".to_string())
-                    .into_response();
-            }
-        };
+        info!("Auth middleware: invalid or expired session - returning 401");
 
-        sessions.contains_key(&session_id)
+        return AppError::Unauthorized("Invalid session".to_string()).into_response();
     };
 
-    if is_valid {
-        info!("Auth middleware: valid session");
+    if user.role < required {
+        info!(
+            "Auth middleware: user {} ({}) lacks role {} - returning 403",
+            user.username,
+            user.role.as_str(),
+            required.as_str()
+        );
 
-        next.run(req).await
-    } else {
-        info!("Auth middleware: invalid session - returning 401");
+        return AppError::Forbidden("Insufficient role".to_string()).into_response();
+    }
+
+    info!("Auth middleware: authenticated {}", user.username);
+
+    req.extensions_mut().insert(user);
+    next.run(req).await
+}
+
+async fn session_user(
+    state: &AppState,
+    session_id: &str,
+) -> Result<Option<User>, axum::response::Response> {
+    match state.sessions.touch(session_id).await {
+        Ok(session) => Ok(session.map(|s| s.user)),
+        Err(e) => Err(
+            AppError::Internal(format!("Auth middleware: session store error: {}", e))
+                .into_response(),
+        ),
+    }
+}
+
+/// Extracts the token from an `Authorization: Bearer <token>` header.
+pub fn bearer_token(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(axum::http::header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|s| s.strip_prefix("Bearer "))
+        .map(|t| t.trim().to_string())
+        .filter(|t| !t.is_empty())
+}
+
+/// Extracts the `katastr_session` value from the request's `Cookie` header.
+pub fn session_cookie(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(axum::http::header::COOKIE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|s| {
+            s.split(';').map(|pair| pair.trim()).find_map(|pair| {
+                let (key, val) = pair.split_once('=')?;
+                (key == SESSION_COOKIE).then(|| val.to_string())
+            })
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn redacted(uri: &str) -> String {
+        redact_uri(&uri.parse().unwrap())
+    }
+
+    #[test]
+    fn redacts_sensitive_query_params() {
+        assert_eq!(
+            redacted("/auth?username=admin&password=heslo"),
+            "/auth?username=admin&password=***"
+        );
+        assert_eq!(
+            redacted("/lv?Token=abc&cislo_lv=1&access_token=x"),
+            "/lv?Token=***&cislo_lv=1&access_token=***"
+        );
+    }
+
+    #[test]
+    fn keeps_other_uris_as_they_are() {
+        assert_eq!(redacted("/lv"), "/lv");
+        assert_eq!(redacted("/lv?cislo_lv=1&secret"), "/lv?cislo_lv=1&secret");
+    }
+
+    #[test]
+    fn matches_public_read_paths_exactly() {
+        assert!(is_public_read_path("/lv", "/lv"));
+        assert!(!is_public_read_path("/lv", "/lv/diff"));
+        assert!(!is_public_read_path("/lv", "/lvx"));
+        assert!(!is_public_read_path("/lv", "/"));
+    }
 
-        (StatusCode::UNAUTHORIZED, "Invalid session".to_string()).into_response()
+    #[test]
+    fn matches_public_read_paths_below_a_wildcard() {
+        assert!(is_public_read_path("/majitel/*", "/majitel/5"));
+        assert!(is_public_read_path("/majitel/*", "/majitel/5/parcely"));
+        assert!(!is_public_read_path("/majitel/*", "/majitel"));
+        assert!(!is_public_read_path("/majitel/*", "/majitel/"));
+        assert!(!is_public_read_path("/majitel/*", "/majitelx/5"));
     }
 }
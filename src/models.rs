@@ -1,14 +1,124 @@
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::sync::{Arc, RwLock};
-use std::time::Instant;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::session::SessionStore;
+use crate::throttle::LoginThrottle;
 
 #[derive(Clone)]
 pub struct AppState {
-    pub password: String,
     pub no_print: bool,
-    pub sessions: Arc<RwLock<HashMap<String, Instant>>>,
+    pub sessions: Arc<dyn SessionStore>,
+    pub session_max_age: Duration,
+    pub require_auth_for_reads: bool,
+    pub public_read_paths: Arc<Vec<String>>,
+    pub login_throttle: Arc<LoginThrottle>,
+}
+
+// --- Users ---
+/// Roles are ordered: every role may do everything the roles below it may.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    Reader,
+    Clerk,
+    Admin,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Reader => "reader",
+            Role::Clerk => "clerk",
+            Role::Admin => "admin",
+        }
+    }
+}
+
+impl std::str::FromStr for Role {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "reader" => Ok(Role::Reader),
+            "clerk" => Ok(Role::Clerk),
+            "admin" => Ok(Role::Admin),
+            other => Err(format!("Unknown role: {}", other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct User {
+    pub id: i32,
+    pub username: String,
+    pub role: Role,
+    /// May see birth numbers and addresses unmasked; granted per user, independent of role
+    pub can_view_personal_data: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewUser {
+    pub username: String,
+    pub password: String,
+    pub role: Role,
+    #[serde(default)]
+    pub can_view_personal_data: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct UpdateUser {
+    pub id: i32,
+    pub password: Option<String>,
+    pub role: Option<Role>,
+    pub can_view_personal_data: Option<bool>,
+}
+
+// --- API tokens ---
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiToken {
+    pub id: i32,
+    pub name: String,
+    pub user_id: i32,
+    pub username: String,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub last_used_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub revoked_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewApiToken {
+    pub name: String,
+    pub user_id: i32,
+}
+
+// --- Audit log ---
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditEntry {
+    pub id: i64,
+    pub at: chrono::DateTime<chrono::Utc>,
+    pub user_id: Option<i32>,
+    pub username: Option<String>,
+    pub action: String,
+    pub entity: String,
+    pub entity_key: serde_json::Value,
+    pub before: Option<serde_json::Value>,
+    pub after: Option<serde_json::Value>,
+}
+
+/// Validated filters of an audit log query, combined with AND. Entries come
+/// newest first; `after` continues below the given entry id.
+#[derive(Debug, Clone)]
+pub struct AuditFilter {
+    pub entity: Option<String>,
+    pub entity_key: Option<serde_json::Value>,
+    pub user_id: Option<i32>,
+    pub username: Option<String>,
+    pub from: Option<chrono::DateTime<chrono::Utc>>,
+    pub to: Option<chrono::DateTime<chrono::Utc>>,
+    pub limit: i64,
+    pub after: Option<i64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -28,6 +138,19 @@ pub struct Majitel {
     pub bydliste: Option<String>,
     pub rodne_cislo: Option<String>,
     pub ico: Option<String>,
+    /// Derived from `rodne_cislo` on read; ignored on write
+    #[serde(default, skip_deserializing)]
+    pub datum_narozeni: Option<chrono::NaiveDate>,
+    /// Derived from `rodne_cislo` on read; ignored on write
+    #[serde(default, skip_deserializing)]
+    pub pohlavi: Option<Pohlavi>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Pohlavi {
+    Muz,
+    Zena,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -77,7 +200,7 @@ pub struct MajitelB {
     pub ico_povinny: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct PartC {
     pub popis: String,
     pub datum_zrizeni: chrono::NaiveDate,
@@ -90,7 +213,7 @@ pub struct PartC {
     pub cast_parcely_povinna: i64,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct PartD {
     pub je_stavebni: bool,
     pub parcelni_cislo: i64,
@@ -101,6 +224,20 @@ pub struct PartD {
     pub rok_rizeni: i64,
 }
 
+/// Part E: the titles by which the owners acquired their shares.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PartE {
+    pub druh: String,
+    pub popis: String,
+    pub datum_vyhotoveni: chrono::NaiveDate,
+    pub datum_pravnich_ucinku: chrono::NaiveDate,
+    pub typ_rizeni_zkratka: String,
+    pub cislo_rizeni: i64,
+    pub rok_rizeni: i64,
+    pub jmeno: String,
+    pub prijmeni: String,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PartF {
     pub je_stavebni: bool,
@@ -109,6 +246,49 @@ pub struct PartF {
     pub hodnota: Option<i64>,
 }
 
+/// A parcel on an LV on some date, identified for comparing states.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ParcelaNaLv {
+    pub parcela_id: i32,
+    pub je_stavebni: bool,
+    pub parcelni_cislo: i32,
+    pub cast_parcely: i32,
+}
+
+/// The stored and the freshly computed `vlastnicky_hash` of an LV.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VlastnickyHash {
+    pub list_vlastnictvi_id: i32,
+    pub katastralni_uzemi: String,
+    pub cislo_lv: i32,
+    pub stored: Option<String>,
+    pub computed: String,
+}
+
+/// A parcel whose owners' shares do not add up to 100.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NevyrovnanaParcela {
+    pub parcela_id: i32,
+    pub list_vlastnictvi_id: i32,
+    pub katastralni_uzemi: String,
+    pub cislo_lv: i32,
+    pub je_stavebni: bool,
+    pub parcelni_cislo: i32,
+    pub cast_parcely: i32,
+    pub pocet_vlastniku: i64,
+    pub soucet_setin: i64,
+}
+
+/// One owner's share in one parcel of an LV on some date.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PodilNaLv {
+    pub parcela_id: i32,
+    pub majitel_id: i32,
+    pub jmeno: String,
+    pub prijmeni: String,
+    pub podil_setin: i32,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FindParcela {
     pub je_stavebni: bool,
@@ -261,6 +441,9 @@ pub struct ListVlastnictvi {
     pub id: i32,
     pub katastralni_uzemi_id: i32,
     pub cislo_lv: i32,
+    /// Computed by the database from the LV's parcels and shares; on writes,
+    /// send null or the computed value, anything else is rejected (see
+    /// `GET /lv/verify`)
     pub vlastnicky_hash: Option<String>,
 }
 
@@ -268,6 +451,7 @@ pub struct ListVlastnictvi {
 pub struct NewListVlastnictvi {
     pub katastralni_uzemi_id: i32,
     pub cislo_lv: i32,
+    /// Optional; if given, it must equal the hash the database computes
     pub vlastnicky_hash: Option<String>,
 }
 
@@ -414,3 +598,33 @@ pub struct NewUcast {
     pub ucastnik_rizeni_id: i32,
     pub typ_ucastnika_id: i32,
 }
+
+// --- NabyvaciTitul ---
+/// Kinds of acquisition title (`nabyvaci_titul.druh`).
+pub const DRUHY_TITULU: &[&str] = &["smlouva", "rozhodnuti", "listina"];
+
+/// A document (contract, decision, other deed) by which an owner acquired
+/// their share in the properties of a list vlastnictví, registered in a řízení.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NabyvaciTitul {
+    pub id: i32,
+    pub list_vlastnictvi_id: i32,
+    pub majitel_id: i32,
+    pub rizeni_id: i32,
+    /// One of `DRUHY_TITULU`
+    pub druh: String,
+    pub popis: String,
+    pub datum_vyhotoveni: chrono::NaiveDate,
+    pub datum_pravnich_ucinku: chrono::NaiveDate,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NewNabyvaciTitul {
+    pub list_vlastnictvi_id: i32,
+    pub majitel_id: i32,
+    pub rizeni_id: i32,
+    pub druh: String,
+    pub popis: String,
+    pub datum_vyhotoveni: chrono::NaiveDate,
+    pub datum_pravnich_ucinku: chrono::NaiveDate,
+}
@@ -0,0 +1,470 @@
+//! A minimal PDF writer: A4 pages with text in the standard Helvetica fonts,
+//! lines and filled rectangles. Enough for generated reports, without
+//! embedding fonts or pulling in a layout engine.
+
+use flate2::{Compression, write::ZlibEncoder};
+use std::fmt::Write;
+use std::io::Write as _;
+
+pub const A4_WIDTH: f32 = 595.28;
+pub const A4_HEIGHT: f32 = 841.89;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Font {
+    Regular,
+    Bold,
+}
+
+impl Font {
+    fn resource(&self) -> &'static str {
+        match self {
+            Font::Regular => "F1",
+            Font::Bold => "F2",
+        }
+    }
+
+    fn base_font(&self) -> &'static str {
+        match self {
+            Font::Regular => "Helvetica",
+            Font::Bold => "Helvetica-Bold",
+        }
+    }
+
+    /// Advance widths of ASCII 32..=126 in thousandths of the font size.
+    fn widths(&self) -> &'static [u16; 95] {
+        match self {
+            Font::Regular => &HELVETICA_WIDTHS,
+            Font::Bold => &HELVETICA_BOLD_WIDTHS,
+        }
+    }
+}
+
+#[rustfmt::skip]
+const HELVETICA_WIDTHS: [u16; 95] = [
+    278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333, 278, 278,
+    556, 556, 556, 556, 556, 556, 556, 556, 556, 556, 278, 278, 584, 584, 584, 556,
+    1015, 667, 667, 722, 722, 667, 611, 778, 722, 278, 500, 667, 556, 833, 722, 778,
+    667, 778, 722, 667, 611, 722, 667, 944, 667, 667, 611, 278, 278, 278, 469, 556,
+    333, 556, 556, 500, 556, 556, 278, 556, 556, 222, 222, 500, 222, 833, 556, 556,
+    556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500, 334, 260, 334, 584,
+];
+
+#[rustfmt::skip]
+const HELVETICA_BOLD_WIDTHS: [u16; 95] = [
+    278, 333, 474, 556, 556, 889, 722, 238, 333, 333, 389, 584, 278, 333, 278, 278,
+    556, 556, 556, 556, 556, 556, 556, 556, 556, 556, 333, 333, 584, 584, 584, 611,
+    975, 722, 722, 722, 722, 667, 611, 778, 722, 278, 556, 722, 611, 833, 722, 778,
+    667, 778, 722, 667, 611, 722, 667, 944, 667, 667, 611, 333, 278, 333, 584, 556,
+    333, 556, 611, 556, 611, 556, 333, 611, 611, 278, 278, 556, 278, 889, 611, 611,
+    611, 611, 389, 556, 333, 611, 556, 778, 556, 556, 500, 389, 280, 389, 584,
+];
+
+/// Letters outside WinAnsiEncoding that Czech and Slovak need, mapped onto the
+/// unused codes from 1 up through the fonts' `/Differences`.
+const EXTRA_GLYPHS: [(char, &str); 20] = [
+    ('Č', "Ccaron"),
+    ('č', "ccaron"),
+    ('Ď', "Dcaron"),
+    ('ď', "dcaron"),
+    ('Ě', "Ecaron"),
+    ('ě', "ecaron"),
+    ('Ň', "Ncaron"),
+    ('ň', "ncaron"),
+    ('Ř', "Rcaron"),
+    ('ř', "rcaron"),
+    ('Ť', "Tcaron"),
+    ('ť', "tcaron"),
+    ('Ů', "Uring"),
+    ('ů', "uring"),
+    ('Ľ', "Lcaron"),
+    ('ľ', "lcaron"),
+    ('Ĺ', "Lacute"),
+    ('ĺ', "lacute"),
+    ('Ŕ', "Racute"),
+    ('ŕ', "racute"),
+];
+
+/// WinAnsiEncoding above 0x7F, for the characters it has.
+const WIN_ANSI_HIGH: [(char, u8); 27] = [
+    ('€', 0x80),
+    ('‚', 0x82),
+    ('„', 0x84),
+    ('…', 0x85),
+    ('‰', 0x89),
+    ('Š', 0x8A),
+    ('‹', 0x8B),
+    ('Ž', 0x8E),
+    ('‘', 0x91),
+    ('’', 0x92),
+    ('“', 0x93),
+    ('”', 0x94),
+    ('•', 0x95),
+    ('–', 0x96),
+    ('—', 0x97),
+    ('™', 0x99),
+    ('š', 0x9A),
+    ('›', 0x9B),
+    ('ž', 0x9E),
+    ('Ÿ', 0x9F),
+    ('ƒ', 0x83),
+    ('†', 0x86),
+    ('‡', 0x87),
+    ('ˆ', 0x88),
+    ('Œ', 0x8C),
+    ('œ', 0x9C),
+    ('˜', 0x98),
+];
+
+/// The byte the fonts use for `c`, or `None` if they cannot show it.
+fn encode_char(c: char) -> Option<u8> {
+    match c {
+        ' '..='~' => Some(c as u8),
+        // Latin-1 matches WinAnsi from 0xA0
+        '\u{A0}'..='\u{FF}' => Some(c as u32 as u8),
+        _ => EXTRA_GLYPHS
+            .iter()
+            .position(|(g, _)| *g == c)
+            .map(|i| i as u8 + 1)
+            .or_else(|| WIN_ANSI_HIGH.iter().find(|(g, _)| *g == c).map(|(_, b)| *b)),
+    }
+}
+
+/// Width of `c` in thousandths of the font size. Accented letters are about as
+/// wide as their base letter; anything else outside ASCII counts as an `n`.
+fn char_width(font: Font, c: char) -> u16 {
+    let base = match c {
+        ' '..='~' => c,
+        'Á' | 'Ä' | 'À' | 'Â' => 'A',
+        'á' | 'ä' | 'à' | 'â' => 'a',
+        'Č' | 'Ç' => 'C',
+        'č' | 'ç' => 'c',
+        'Ď' => 'D',
+        'É' | 'Ě' | 'Ë' => 'E',
+        'é' | 'ě' | 'ë' => 'e',
+        'Í' => 'I',
+        'í' => 'i',
+        'Ĺ' | 'Ľ' => 'L',
+        'ĺ' | 'ľ' => 'l',
+        'Ň' => 'N',
+        'ň' => 'n',
+        'Ó' | 'Ö' | 'Ô' => 'O',
+        'ó' | 'ö' | 'ô' => 'o',
+        'Ř' | 'Ŕ' => 'R',
+        'ř' | 'ŕ' => 'r',
+        'Š' => 'S',
+        'š' => 's',
+        'Ť' => 'T',
+        'ť' | 'ď' => 't',
+        'Ú' | 'Ů' | 'Ü' => 'U',
+        'ú' | 'ů' | 'ü' => 'u',
+        'Ý' => 'Y',
+        'ý' => 'y',
+        'Ž' => 'Z',
+        'ž' => 'z',
+        _ => 'n',
+    };
+    font.widths()[base as usize - 32]
+}
+
+/// Width of `text` in points.
+pub fn text_width(text: &str, font: Font, size: f32) -> f32 {
+    text.chars()
+        .map(|c| char_width(font, c) as f32)
+        .sum::<f32>()
+        * size
+        / 1000.0
+}
+
+/// Breaks `text` into lines no wider than `width`, at spaces where possible.
+pub fn wrap(text: &str, font: Font, size: f32, width: f32) -> Vec<String> {
+    let mut lines = Vec::new();
+    for paragraph in text.lines() {
+        let mut line = String::new();
+        for word in paragraph.split(' ') {
+            let candidate = if line.is_empty() {
+                word.to_string()
+            } else {
+                format!("{} {}", line, word)
+            };
+            if text_width(&candidate, font, size) <= width || line.is_empty() {
+                line = candidate;
+            } else {
+                lines.push(std::mem::take(&mut line));
+                line = word.to_string();
+            }
+            // A single word wider than the column is split wherever it overflows
+            while text_width(&line, font, size) > width && line.chars().count() > 1 {
+                let mut head = String::new();
+                for c in line.chars() {
+                    if !head.is_empty() && text_width(&format!("{}{}", head, c), font, size) > width
+                    {
+                        break;
+                    }
+                    head.push(c);
+                }
+                line = line[head.len()..].to_string();
+                lines.push(head);
+            }
+        }
+        lines.push(line);
+    }
+    if lines.is_empty() {
+        lines.push(String::new());
+    }
+    lines
+}
+
+fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    // Writing to a Vec cannot fail
+    let _ = encoder.write_all(data);
+    encoder.finish().unwrap_or_default()
+}
+
+/// `text` as a PDF string literal in the fonts' encoding.
+fn literal(text: &str) -> String {
+    let mut out = String::from("(");
+    for c in text.chars() {
+        match encode_char(c) {
+            Some(b'(') => out.push_str("\\("),
+            Some(b')') => out.push_str("\\)"),
+            Some(b'\\') => out.push_str("\\\\"),
+            Some(b @ 0x20..=0x7E) => out.push(b as char),
+            Some(b) => {
+                let _ = write!(out, "\\{:03o}", b);
+            }
+            None => out.push('?'),
+        }
+    }
+    out.push(')');
+    out
+}
+
+/// A document under construction; coordinates are in points from the bottom
+/// left corner of the page.
+#[derive(Debug, Default)]
+pub struct PdfDocument {
+    title: String,
+    pages: Vec<String>,
+}
+
+impl PdfDocument {
+    pub fn new(title: impl Into<String>) -> Self {
+        PdfDocument {
+            title: title.into(),
+            pages: Vec::new(),
+        }
+    }
+
+    /// Starts a new page and returns its index.
+    pub fn add_page(&mut self) -> usize {
+        self.pages.push(String::new());
+        self.pages.len() - 1
+    }
+
+    pub fn page_count(&self) -> usize {
+        self.pages.len()
+    }
+
+    pub fn text(&mut self, page: usize, x: f32, y: f32, font: Font, size: f32, text: &str) {
+        let _ = writeln!(
+            self.pages[page],
+            "BT /{} {:.2} Tf {:.2} {:.2} Td {} Tj ET",
+            font.resource(),
+            size,
+            x,
+            y,
+            literal(text)
+        );
+    }
+
+    pub fn line(&mut self, page: usize, from: (f32, f32), to: (f32, f32), width: f32) {
+        let _ = writeln!(
+            self.pages[page],
+            "{:.2} w {:.2} {:.2} m {:.2} {:.2} l S",
+            width, from.0, from.1, to.0, to.1
+        );
+    }
+
+    /// Fills a rectangle with a shade of grey, 0 being black and 1 white.
+    pub fn fill_rect(&mut self, page: usize, x: f32, y: f32, width: f32, height: f32, grey: f32) {
+        let _ = writeln!(
+            self.pages[page],
+            "q {:.2} g {:.2} {:.2} {:.2} {:.2} re f Q",
+            grey, x, y, width, height
+        );
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out: Vec<u8> = b"%PDF-1.4\n%\xE2\xE3\xCF\xD3\n".to_vec();
+        let mut offsets = Vec::new();
+        let mut object = |out: &mut Vec<u8>, body: &[u8]| {
+            offsets.push(out.len());
+            out.extend_from_slice(format!("{} 0 obj\n", offsets.len()).as_bytes());
+            out.extend_from_slice(body);
+            out.extend_from_slice(b"\nendobj\n");
+        };
+
+        // 1 catalog, 2 page tree, 3-4 fonts, 5 info, then a page and its content per page
+        let first_page = 6;
+        let kids: Vec<String> = (0..self.pages.len())
+            .map(|i| format!("{} 0 R", first_page + 2 * i))
+            .collect();
+        object(&mut out, b"<< /Type /Catalog /Pages 2 0 R >>");
+        object(
+            &mut out,
+            format!(
+                "<< /Type /Pages /Kids [{}] /Count {} >>",
+                kids.join(" "),
+                self.pages.len()
+            )
+            .as_bytes(),
+        );
+        let differences: Vec<String> = EXTRA_GLYPHS
+            .iter()
+            .map(|(_, n)| format!("/{}", n))
+            .collect();
+        for font in [Font::Regular, Font::Bold] {
+            object(
+                &mut out,
+                format!(
+                    "<< /Type /Font /Subtype /Type1 /BaseFont /{} /Encoding << /Type /Encoding /BaseEncoding /WinAnsiEncoding /Differences [1 {}] >> >>",
+                    font.base_font(),
+                    differences.join(" ")
+                )
+                .as_bytes(),
+            );
+        }
+        object(
+            &mut out,
+            format!(
+                "<< /Title {} /Producer (katastr) /CreationDate (D:{}) >>",
+                literal(&self.title),
+                chrono::Utc::now().format("%Y%m%d%H%M%SZ")
+            )
+            .as_bytes(),
+        );
+        for (i, content) in self.pages.iter().enumerate() {
+            object(
+                &mut out,
+                format!(
+                    "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] /Resources << /Font << /F1 3 0 R /F2 4 0 R >> >> /Contents {} 0 R >>",
+                    A4_WIDTH,
+                    A4_HEIGHT,
+                    first_page + 2 * i + 1
+                )
+                .as_bytes(),
+            );
+            let stream = deflate(content.as_bytes());
+            let mut body = format!(
+                "<< /Length {} /Filter /FlateDecode >>\nstream\n",
+                stream.len()
+            )
+            .into_bytes();
+            body.extend_from_slice(&stream);
+            body.extend_from_slice(b"\nendstream");
+            object(&mut out, &body);
+        }
+
+        let xref = out.len();
+        let mut trailer = format!("xref\n0 {}\n0000000000 65535 f \n", offsets.len() + 1);
+        for offset in &offsets {
+            let _ = writeln!(trailer, "{:010} 00000 n ", offset);
+        }
+        let _ = write!(
+            trailer,
+            "trailer\n<< /Size {} /Root 1 0 R /Info 5 0 R >>\nstartxref\n{}\n%%EOF\n",
+            offsets.len() + 1,
+            xref
+        );
+        out.extend_from_slice(trailer.as_bytes());
+        out
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    #[test]
+    fn wraps_at_spaces() {
+        let width = text_width("Bez zápisu", Font::Regular, 10.0);
+        assert_eq!(
+            wrap("Bez zápisu Bez zápisu\nBez", Font::Regular, 10.0, width),
+            ["Bez zápisu", "Bez zápisu", "Bez"]
+        );
+        assert_eq!(wrap("", Font::Regular, 10.0, width), [""]);
+    }
+
+    #[test]
+    fn splits_a_word_wider_than_the_column() {
+        let width = text_width("Přík", Font::Bold, 8.0);
+        let lines = wrap("Příkazní smlouva", Font::Bold, 8.0, width);
+        assert_eq!(lines[0], "Přík");
+        assert!(lines.len() >= 4);
+        assert_eq!(lines.concat(), "Příkaznísmlouva");
+        assert!(
+            lines
+                .iter()
+                .all(|l| text_width(l, Font::Bold, 8.0) <= width)
+        );
+        // A single character always makes progress
+        assert_eq!(wrap("WW", Font::Regular, 10.0, 1.0), ["W", "W"]);
+    }
+
+    #[test]
+    fn escapes_string_literals() {
+        assert_eq!(literal("LV (č. 5) \\ 1"), "(LV \\(\\002. 5\\) \\\\ 1)");
+        assert_eq!(
+            literal("Žluťoučký kůň"),
+            "(\\216lu\\014ou\\002k\\375 k\\016\\010)"
+        );
+        assert_eq!(literal("šíř €"), "(\\232\\355\\012 \\200)");
+        assert_eq!(literal("日"), "(?)");
+    }
+
+    /// Checks that every xref entry points at the start of its object.
+    pub(crate) fn assert_xref_consistent(pdf: &[u8]) {
+        let text = String::from_utf8_lossy(pdf);
+        let startxref = text.rfind("startxref\n").unwrap() + "startxref\n".len();
+        let xref: usize = text[startxref..].lines().next().unwrap().parse().unwrap();
+        assert!(pdf[xref..].starts_with(b"xref\n0 "));
+        let entries = pdf[xref..]
+            .split(|b| *b == b'\n')
+            .skip(3)
+            .take_while(|line| line.ends_with(b" n "));
+        let mut count = 0;
+        for (i, entry) in entries.enumerate() {
+            let offset: usize = std::str::from_utf8(&entry[..10]).unwrap().parse().unwrap();
+            let header = format!("{} 0 obj\n", i + 1);
+            assert!(
+                pdf[offset..].starts_with(header.as_bytes()),
+                "object {}",
+                i + 1
+            );
+            count += 1;
+        }
+        assert_eq!(text.matches(" 0 obj\n").count(), count);
+    }
+
+    #[test]
+    fn writes_a_consistent_cross_reference_table() {
+        let mut pdf = PdfDocument::new("List vlastnictví č. 5");
+        for i in 0..3 {
+            let page = pdf.add_page();
+            pdf.text(
+                page,
+                40.0,
+                800.0,
+                Font::Bold,
+                11.0,
+                &format!("Strana {} (čtvrtá)", i),
+            );
+            pdf.line(page, (40.0, 790.0), (555.0, 790.0), 0.5);
+        }
+        let bytes = pdf.to_bytes();
+        assert!(bytes.starts_with(b"%PDF-"));
+        assert!(bytes.ends_with(b"%%EOF\n"));
+        assert_xref_consistent(&bytes);
+    }
+}
@@ -0,0 +1,144 @@
+use axum::{extract::FromRequestParts, http::request::Parts};
+use serde_json::Value;
+use std::convert::Infallible;
+
+use crate::error::{AppError, AppResult};
+use crate::listing::{ListQuery, Listable};
+use crate::models::*;
+
+/// Replaces the masked part of a value.
+pub const MASK: &str = "****";
+
+/// Keeps the date part of a birth number and hides the serial, e.g. `805412/****`.
+pub fn mask_rodne_cislo(rodne_cislo: &str) -> String {
+    let date: String = rodne_cislo
+        .chars()
+        .filter(char::is_ascii_digit)
+        .take(6)
+        .collect();
+    format!("{}/{}", date, MASK)
+}
+
+/// Response types carrying personal data (birth numbers, addresses), which is
+/// only shown to users holding the personal data permission.
+pub trait PersonalData {
+    /// Columns that may not be filtered or sorted on without the permission,
+    /// since list queries would otherwise reveal the masked values.
+    const COLUMNS: &'static [&'static str] = &[];
+
+    fn mask(&mut self) {}
+}
+
+impl PersonalData for Majitel {
+    const COLUMNS: &'static [&'static str] = &["rodne_cislo", "bydliste"];
+
+    fn mask(&mut self) {
+        if let Some(rodne_cislo) = &self.rodne_cislo {
+            self.rodne_cislo = Some(mask_rodne_cislo(rodne_cislo));
+        }
+        if self.bydliste.is_some() {
+            self.bydliste = Some(MASK.to_string());
+        }
+    }
+}
+
+impl PersonalData for MajitelPartA {
+    fn mask(&mut self) {
+        self.bydliste = MASK.to_string();
+    }
+}
+
+impl PersonalData for MajitelB {
+    fn mask(&mut self) {
+        if let Some(rodne_cislo) = &self.rodne_cislo_povinny {
+            self.rodne_cislo_povinny = Some(mask_rodne_cislo(rodne_cislo));
+        }
+    }
+}
+
+/// Row images of `majitel` carry the same fields as `Majitel`.
+impl PersonalData for AuditEntry {
+    fn mask(&mut self) {
+        if self.entity != Majitel::TABLE.name {
+            return;
+        }
+        for image in [&mut self.before, &mut self.after].into_iter().flatten() {
+            if let Some(Value::String(rodne_cislo)) = image.get_mut("rodne_cislo") {
+                *rodne_cislo = mask_rodne_cislo(rodne_cislo);
+            }
+            if let Some(bydliste @ Value::String(_)) = image.get_mut("bydliste") {
+                *bydliste = Value::from(MASK);
+            }
+        }
+    }
+}
+
+macro_rules! no_personal_data {
+    ($($ty:ty),+) => {
+        $(impl PersonalData for $ty {})+
+    };
+}
+
+no_personal_data!(
+    Kraj,
+    Okres,
+    Obec,
+    KatastralniUzemi,
+    Bpej,
+    TypRizeni,
+    TypOperace,
+    TypUcastnika,
+    UcastnikRizeni,
+    ListVlastnictvi,
+    ParcelaRow,
+    Rizeni,
+    Vlastnictvi,
+    BremenoParcelaParcela,
+    BremenoParcelaMajitel,
+    Plomba,
+    RizeniOperaceRow,
+    Ucast,
+    NabyvaciTitul,
+    PartE
+);
+
+/// Whether the caller may see personal data unmasked. Every response that can
+/// contain `PersonalData` goes through this; anonymous callers never may.
+#[derive(Debug, Clone, Copy)]
+pub struct PersonalDataAccess(pub bool);
+
+impl PersonalDataAccess {
+    pub fn shape<T: PersonalData>(&self, mut item: T) -> T {
+        if !self.0 {
+            item.mask();
+        }
+        item
+    }
+
+    pub fn shape_all<T: PersonalData>(&self, items: &mut [T]) {
+        if !self.0 {
+            items.iter_mut().for_each(PersonalData::mask);
+        }
+    }
+
+    pub fn check_query<T: PersonalData>(&self, query: &ListQuery) -> AppResult<()> {
+        match T::COLUMNS.iter().find(|c| query.references(c)) {
+            Some(column) if !self.0 => Err(AppError::Forbidden(format!(
+                "Filtering or sorting by {} requires the personal data permission",
+                column
+            ))),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for PersonalDataAccess {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let user = parts.extensions.get::<User>();
+        Ok(PersonalDataAccess(
+            user.is_some_and(|u| u.can_view_personal_data),
+        ))
+    }
+}
@@ -0,0 +1,790 @@
+use anyhow::Result;
+use deadpool_postgres::Pool;
+
+use crate::listing::Listable;
+use crate::models::*;
+
+// Tables owned by the server itself. The cadastre tables and `fn_get_*`
+// functions are provisioned separately, except for the later additions at the
+// end; everything here must be idempotent because it runs on every startup.
+const SCHEMA: &[&str] = &[
+    "CREATE TABLE IF NOT EXISTS app_user (
+        id SERIAL PRIMARY KEY,
+        username TEXT NOT NULL UNIQUE,
+        password_hash TEXT NOT NULL,
+        role TEXT NOT NULL CHECK (role IN ('reader', 'clerk', 'admin')),
+        can_view_personal_data BOOLEAN NOT NULL DEFAULT false
+    )",
+    "CREATE TABLE IF NOT EXISTS app_session (
+        id TEXT PRIMARY KEY,
+        user_id INT NOT NULL REFERENCES app_user(id) ON DELETE CASCADE,
+        created_at TIMESTAMPTZ NOT NULL,
+        last_seen TIMESTAMPTZ NOT NULL
+    )",
+    "CREATE TABLE IF NOT EXISTS api_token (
+        id SERIAL PRIMARY KEY,
+        name TEXT NOT NULL,
+        token_hash TEXT NOT NULL UNIQUE,
+        user_id INT NOT NULL REFERENCES app_user(id) ON DELETE CASCADE,
+        created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+        last_used_at TIMESTAMPTZ,
+        revoked_at TIMESTAMPTZ
+    )",
+    "CREATE TABLE IF NOT EXISTS audit_log (
+        id BIGSERIAL PRIMARY KEY,
+        at TIMESTAMPTZ NOT NULL DEFAULT now(),
+        user_id INT,
+        username TEXT,
+        action TEXT NOT NULL CHECK (action IN ('insert', 'update', 'delete')),
+        entity TEXT NOT NULL,
+        entity_key JSONB NOT NULL,
+        before JSONB,
+        after JSONB
+    )",
+    "CREATE INDEX IF NOT EXISTS audit_log_entity_idx ON audit_log (entity, entity_key)",
+    "CREATE INDEX IF NOT EXISTS audit_log_user_idx ON audit_log (user_id)",
+    "CREATE INDEX IF NOT EXISTS audit_log_at_idx ON audit_log (at)",
+    "CREATE OR REPLACE FUNCTION audit_log_append_only() RETURNS trigger LANGUAGE plpgsql AS $$
+    BEGIN
+        RAISE EXCEPTION 'audit_log is append-only';
+    END
+    $$",
+    "CREATE OR REPLACE TRIGGER audit_log_append_only
+        BEFORE UPDATE OR DELETE OR TRUNCATE ON audit_log
+        FOR EACH STATEMENT EXECUTE FUNCTION audit_log_append_only()",
+    // Trigger arguments name the key columns. The actor comes from the
+    // transaction-local settings made by `begin_audited`; writes made outside
+    // the server are logged without one. Password and token hashes never
+    // enter the log.
+    "CREATE OR REPLACE FUNCTION audit_row() RETURNS trigger LANGUAGE plpgsql AS $$
+    DECLARE
+        old_row JSONB := CASE WHEN TG_OP <> 'INSERT' THEN to_jsonb(OLD) - 'password_hash' - 'token_hash' END;
+        new_row JSONB := CASE WHEN TG_OP <> 'DELETE' THEN to_jsonb(NEW) - 'password_hash' - 'token_hash' END;
+        entity_key JSONB := '{}';
+        key_column TEXT;
+    BEGIN
+        -- Token use only bumps last_used_at, which is bookkeeping rather than a change
+        IF TG_OP = 'UPDATE' AND to_jsonb(OLD) - 'last_used_at' = to_jsonb(NEW) - 'last_used_at' THEN
+            RETURN NULL;
+        END IF;
+        FOREACH key_column IN ARRAY TG_ARGV LOOP
+            entity_key := entity_key || jsonb_build_object(key_column, COALESCE(new_row, old_row) -> key_column);
+        END LOOP;
+        INSERT INTO audit_log (user_id, username, action, entity, entity_key, before, after)
+        VALUES (
+            NULLIF(current_setting('katastr.user_id', true), '')::INT,
+            NULLIF(current_setting('katastr.username', true), ''),
+            lower(TG_OP),
+            TG_TABLE_NAME,
+            entity_key,
+            old_row,
+            new_row
+        );
+        RETURN NULL;
+    END
+    $$",
+    // LV part E: acquisition titles
+    "CREATE TABLE IF NOT EXISTS nabyvaci_titul (
+        id SERIAL PRIMARY KEY,
+        list_vlastnictvi_id INT NOT NULL REFERENCES list_vlastnictvi(id),
+        majitel_id INT NOT NULL REFERENCES majitel(id),
+        rizeni_id INT NOT NULL REFERENCES rizeni(id),
+        druh TEXT NOT NULL CHECK (druh IN ('smlouva', 'rozhodnuti', 'listina')),
+        popis TEXT NOT NULL,
+        datum_vyhotoveni DATE NOT NULL,
+        datum_pravnich_ucinku DATE NOT NULL
+    )",
+    "CREATE INDEX IF NOT EXISTS nabyvaci_titul_lv_idx ON nabyvaci_titul (list_vlastnictvi_id)",
+    "CREATE OR REPLACE FUNCTION fn_get_lv_part_e(ku text, lv integer)
+        RETURNS TABLE(druh text, popis text, datum_vyhotoveni date, datum_pravnich_ucinku date, typ_rizeni_zkratka text, cislo_rizeni integer, rok_rizeni integer, jmeno text, prijmeni text)
+        LANGUAGE sql
+    AS $$
+        SELECT n.druh, n.popis, n.datum_vyhotoveni, n.datum_pravnich_ucinku, t.zkratka, r.cislo_rizeni, r.rok, m.jmeno, m.prijmeni
+        FROM nabyvaci_titul n
+        JOIN list_vlastnictvi l ON l.id = n.list_vlastnictvi_id
+        JOIN katastralni_uzemi k ON k.id = l.katastralni_uzemi_id
+        JOIN rizeni r ON r.id = n.rizeni_id
+        JOIN typ_rizeni t ON t.id = r.typ_rizeni_id
+        JOIN majitel m ON m.id = n.majitel_id
+        WHERE k.nazev = ku AND l.cislo_lv = lv
+        ORDER BY n.datum_pravnich_ucinku, n.id
+    $$",
+    // History of ownership, of parcels and the LV they are on, of LVs and of
+    // owners. Each row held from `platnost_od` until before `platnost_do`, or
+    // still holds when that is NULL; a change counts from the day it is
+    // written, so a date shows the state at its end. Rows that predate the
+    // history are taken to have always held. The history keeps its own copy
+    // of every column the LV shows, so deleting a row does not change the past.
+    "CREATE TABLE IF NOT EXISTS vlastnictvi_historie (
+        id BIGSERIAL PRIMARY KEY,
+        parcela_id INT NOT NULL,
+        majitel_id INT NOT NULL,
+        podil_setin INT NOT NULL,
+        platnost_od DATE NOT NULL,
+        platnost_do DATE CHECK (platnost_do > platnost_od)
+    )",
+    "CREATE INDEX IF NOT EXISTS vlastnictvi_historie_parcela_idx ON vlastnictvi_historie (parcela_id, platnost_od)",
+    "CREATE TABLE IF NOT EXISTS parcela_lv_historie (
+        id BIGSERIAL PRIMARY KEY,
+        parcela_id INT NOT NULL,
+        list_vlastnictvi_id INT NOT NULL,
+        katastralni_uzemi_id INT NOT NULL,
+        je_stavebni BOOLEAN NOT NULL,
+        parcelni_cislo INT NOT NULL,
+        cast_parcely INT NOT NULL,
+        vymera_metru_ctverecnich NUMERIC NOT NULL,
+        ulice TEXT,
+        cislo_popisne TEXT,
+        bpej_id INT,
+        platnost_od DATE NOT NULL,
+        platnost_do DATE CHECK (platnost_do > platnost_od)
+    )",
+    "CREATE INDEX IF NOT EXISTS parcela_lv_historie_lv_idx ON parcela_lv_historie (list_vlastnictvi_id, platnost_od)",
+    "CREATE INDEX IF NOT EXISTS parcela_lv_historie_parcela_idx ON parcela_lv_historie (parcela_id, platnost_od)",
+    "CREATE TABLE IF NOT EXISTS list_vlastnictvi_historie (
+        id BIGSERIAL PRIMARY KEY,
+        list_vlastnictvi_id INT NOT NULL,
+        katastralni_uzemi_id INT NOT NULL,
+        cislo_lv INT NOT NULL,
+        platnost_od DATE NOT NULL,
+        platnost_do DATE CHECK (platnost_do > platnost_od)
+    )",
+    "CREATE INDEX IF NOT EXISTS list_vlastnictvi_historie_cislo_idx ON list_vlastnictvi_historie (katastralni_uzemi_id, cislo_lv, platnost_od)",
+    "CREATE INDEX IF NOT EXISTS list_vlastnictvi_historie_lv_idx ON list_vlastnictvi_historie (list_vlastnictvi_id, platnost_od)",
+    "CREATE TABLE IF NOT EXISTS majitel_historie (
+        id BIGSERIAL PRIMARY KEY,
+        majitel_id INT NOT NULL,
+        jmeno TEXT NOT NULL,
+        prijmeni TEXT NOT NULL,
+        titul TEXT,
+        bydliste TEXT,
+        rodne_cislo TEXT,
+        ico TEXT,
+        platnost_od DATE NOT NULL,
+        platnost_do DATE CHECK (platnost_do > platnost_od)
+    )",
+    "CREATE INDEX IF NOT EXISTS majitel_historie_majitel_idx ON majitel_historie (majitel_id, platnost_od)",
+    // Closing an interval opened the same day drops it, since it never held
+    // at the end of any day
+    "CREATE OR REPLACE FUNCTION vlastnictvi_historie_zapis() RETURNS trigger LANGUAGE plpgsql AS $$
+    BEGIN
+        IF TG_OP = 'UPDATE' AND OLD IS NOT DISTINCT FROM NEW THEN
+            RETURN NULL;
+        END IF;
+        IF TG_OP <> 'INSERT' THEN
+            DELETE FROM vlastnictvi_historie
+            WHERE parcela_id = OLD.parcela_id AND majitel_id = OLD.majitel_id
+                AND platnost_do IS NULL AND platnost_od >= current_date;
+            UPDATE vlastnictvi_historie SET platnost_do = current_date
+            WHERE parcela_id = OLD.parcela_id AND majitel_id = OLD.majitel_id AND platnost_do IS NULL;
+        END IF;
+        IF TG_OP <> 'DELETE' THEN
+            INSERT INTO vlastnictvi_historie (parcela_id, majitel_id, podil_setin, platnost_od)
+            VALUES (NEW.parcela_id, NEW.majitel_id, NEW.podil_setin, current_date);
+        END IF;
+        RETURN NULL;
+    END
+    $$",
+    "CREATE OR REPLACE TRIGGER historie AFTER INSERT OR UPDATE OR DELETE ON vlastnictvi
+        FOR EACH ROW EXECUTE FUNCTION vlastnictvi_historie_zapis()",
+    "CREATE OR REPLACE FUNCTION parcela_lv_historie_zapis() RETURNS trigger LANGUAGE plpgsql AS $$
+    BEGIN
+        IF TG_OP = 'UPDATE' AND OLD IS NOT DISTINCT FROM NEW THEN
+            RETURN NULL;
+        END IF;
+        IF TG_OP <> 'INSERT' THEN
+            DELETE FROM parcela_lv_historie
+            WHERE parcela_id = OLD.id AND platnost_do IS NULL AND platnost_od >= current_date;
+            UPDATE parcela_lv_historie SET platnost_do = current_date
+            WHERE parcela_id = OLD.id AND platnost_do IS NULL;
+        END IF;
+        IF TG_OP <> 'DELETE' THEN
+            INSERT INTO parcela_lv_historie (parcela_id, list_vlastnictvi_id, katastralni_uzemi_id, je_stavebni,
+                parcelni_cislo, cast_parcely, vymera_metru_ctverecnich, ulice, cislo_popisne, bpej_id, platnost_od)
+            VALUES (NEW.id, NEW.list_vlastnictvi_id, NEW.katastralni_uzemi_id, NEW.je_stavebni,
+                NEW.parcelni_cislo, NEW.cast_parcely, NEW.vymera_metru_ctverecnich, NEW.ulice, NEW.cislo_popisne,
+                NEW.bpej_id, current_date);
+        END IF;
+        RETURN NULL;
+    END
+    $$",
+    "CREATE OR REPLACE TRIGGER historie AFTER INSERT OR UPDATE OR DELETE ON parcela
+        FOR EACH ROW EXECUTE FUNCTION parcela_lv_historie_zapis()",
+    // A new ownership hash is no change to the LV itself
+    "CREATE OR REPLACE FUNCTION list_vlastnictvi_historie_zapis() RETURNS trigger LANGUAGE plpgsql AS $$
+    BEGIN
+        IF TG_OP = 'UPDATE' AND OLD.katastralni_uzemi_id = NEW.katastralni_uzemi_id
+            AND OLD.cislo_lv = NEW.cislo_lv
+        THEN
+            RETURN NULL;
+        END IF;
+        IF TG_OP <> 'INSERT' THEN
+            DELETE FROM list_vlastnictvi_historie
+            WHERE list_vlastnictvi_id = OLD.id AND platnost_do IS NULL AND platnost_od >= current_date;
+            UPDATE list_vlastnictvi_historie SET platnost_do = current_date
+            WHERE list_vlastnictvi_id = OLD.id AND platnost_do IS NULL;
+        END IF;
+        IF TG_OP <> 'DELETE' THEN
+            INSERT INTO list_vlastnictvi_historie (list_vlastnictvi_id, katastralni_uzemi_id, cislo_lv, platnost_od)
+            VALUES (NEW.id, NEW.katastralni_uzemi_id, NEW.cislo_lv, current_date);
+        END IF;
+        RETURN NULL;
+    END
+    $$",
+    "CREATE OR REPLACE TRIGGER historie AFTER INSERT OR UPDATE OR DELETE ON list_vlastnictvi
+        FOR EACH ROW EXECUTE FUNCTION list_vlastnictvi_historie_zapis()",
+    "CREATE OR REPLACE FUNCTION majitel_historie_zapis() RETURNS trigger LANGUAGE plpgsql AS $$
+    BEGIN
+        IF TG_OP = 'UPDATE' AND OLD IS NOT DISTINCT FROM NEW THEN
+            RETURN NULL;
+        END IF;
+        IF TG_OP <> 'INSERT' THEN
+            DELETE FROM majitel_historie
+            WHERE majitel_id = OLD.id AND platnost_do IS NULL AND platnost_od >= current_date;
+            UPDATE majitel_historie SET platnost_do = current_date
+            WHERE majitel_id = OLD.id AND platnost_do IS NULL;
+        END IF;
+        IF TG_OP <> 'DELETE' THEN
+            INSERT INTO majitel_historie (majitel_id, jmeno, prijmeni, titul, bydliste, rodne_cislo, ico, platnost_od)
+            VALUES (NEW.id, NEW.jmeno, NEW.prijmeni, NEW.titul, NEW.bydliste, NEW.rodne_cislo, NEW.ico, current_date);
+        END IF;
+        RETURN NULL;
+    END
+    $$",
+    "CREATE OR REPLACE TRIGGER historie AFTER INSERT OR UPDATE OR DELETE ON majitel
+        FOR EACH ROW EXECUTE FUNCTION majitel_historie_zapis()",
+    // Rows written while the triggers were missing start where their last
+    // interval ended
+    "INSERT INTO vlastnictvi_historie (parcela_id, majitel_id, podil_setin, platnost_od)
+    SELECT v.parcela_id, v.majitel_id, v.podil_setin, COALESCE(
+        (SELECT max(h.platnost_do) FROM vlastnictvi_historie h
+         WHERE h.parcela_id = v.parcela_id AND h.majitel_id = v.majitel_id),
+        '-infinity')
+    FROM vlastnictvi v
+    WHERE NOT EXISTS (
+        SELECT 1 FROM vlastnictvi_historie h
+        WHERE h.parcela_id = v.parcela_id AND h.majitel_id = v.majitel_id AND h.platnost_do IS NULL
+    )",
+    "INSERT INTO parcela_lv_historie (parcela_id, list_vlastnictvi_id, katastralni_uzemi_id, je_stavebni,
+        parcelni_cislo, cast_parcely, vymera_metru_ctverecnich, ulice, cislo_popisne, bpej_id, platnost_od)
+    SELECT p.id, p.list_vlastnictvi_id, p.katastralni_uzemi_id, p.je_stavebni,
+        p.parcelni_cislo, p.cast_parcely, p.vymera_metru_ctverecnich, p.ulice, p.cislo_popisne, p.bpej_id, COALESCE(
+        (SELECT max(h.platnost_do) FROM parcela_lv_historie h WHERE h.parcela_id = p.id),
+        '-infinity')
+    FROM parcela p
+    WHERE NOT EXISTS (
+        SELECT 1 FROM parcela_lv_historie h WHERE h.parcela_id = p.id AND h.platnost_do IS NULL
+    )",
+    "INSERT INTO list_vlastnictvi_historie (list_vlastnictvi_id, katastralni_uzemi_id, cislo_lv, platnost_od)
+    SELECT l.id, l.katastralni_uzemi_id, l.cislo_lv, COALESCE(
+        (SELECT max(h.platnost_do) FROM list_vlastnictvi_historie h WHERE h.list_vlastnictvi_id = l.id),
+        '-infinity')
+    FROM list_vlastnictvi l
+    WHERE NOT EXISTS (
+        SELECT 1 FROM list_vlastnictvi_historie h WHERE h.list_vlastnictvi_id = l.id AND h.platnost_do IS NULL
+    )",
+    "INSERT INTO majitel_historie (majitel_id, jmeno, prijmeni, titul, bydliste, rodne_cislo, ico, platnost_od)
+    SELECT m.id, m.jmeno, m.prijmeni, m.titul, m.bydliste, m.rodne_cislo, m.ico, COALESCE(
+        (SELECT max(h.platnost_do) FROM majitel_historie h WHERE h.majitel_id = m.id),
+        '-infinity')
+    FROM majitel m
+    WHERE NOT EXISTS (
+        SELECT 1 FROM majitel_historie h WHERE h.majitel_id = m.id AND h.platnost_do IS NULL
+    )",
+    // Notes and easements come and go without dates of their own, so their
+    // history is kept the same way
+    "CREATE TABLE IF NOT EXISTS plomba_historie (
+        id BIGSERIAL PRIMARY KEY,
+        rizeni_id INT NOT NULL,
+        parcela_id INT NOT NULL,
+        platnost_od DATE NOT NULL,
+        platnost_do DATE CHECK (platnost_do > platnost_od)
+    )",
+    "CREATE INDEX IF NOT EXISTS plomba_historie_parcela_idx ON plomba_historie (parcela_id, platnost_od)",
+    "CREATE TABLE IF NOT EXISTS bremeno_parcela_parcela_historie (
+        id BIGSERIAL PRIMARY KEY,
+        parcela_id INT NOT NULL,
+        parcela_povinna_id INT NOT NULL,
+        popis TEXT NOT NULL,
+        datum_zrizeni DATE NOT NULL,
+        datum_pravnich_ucinku DATE NOT NULL,
+        platnost_od DATE NOT NULL,
+        platnost_do DATE CHECK (platnost_do > platnost_od)
+    )",
+    "CREATE INDEX IF NOT EXISTS bremeno_parcela_parcela_historie_parcela_idx ON bremeno_parcela_parcela_historie (parcela_id, platnost_od)",
+    "CREATE INDEX IF NOT EXISTS bremeno_parcela_parcela_historie_povinna_idx ON bremeno_parcela_parcela_historie (parcela_povinna_id, platnost_od)",
+    "CREATE TABLE IF NOT EXISTS bremeno_parcela_majitel_historie (
+        id BIGSERIAL PRIMARY KEY,
+        parcela_id INT NOT NULL,
+        majitel_povinny_id INT NOT NULL,
+        popis TEXT NOT NULL,
+        datum_zrizeni DATE NOT NULL,
+        datum_pravnich_ucinku DATE NOT NULL,
+        platnost_od DATE NOT NULL,
+        platnost_do DATE CHECK (platnost_do > platnost_od)
+    )",
+    "CREATE INDEX IF NOT EXISTS bremeno_parcela_majitel_historie_parcela_idx ON bremeno_parcela_majitel_historie (parcela_id, platnost_od)",
+    "CREATE OR REPLACE FUNCTION plomba_historie_zapis() RETURNS trigger LANGUAGE plpgsql AS $$
+    BEGIN
+        IF TG_OP = 'UPDATE' AND OLD IS NOT DISTINCT FROM NEW THEN
+            RETURN NULL;
+        END IF;
+        IF TG_OP <> 'INSERT' THEN
+            DELETE FROM plomba_historie
+            WHERE rizeni_id = OLD.rizeni_id AND parcela_id = OLD.parcela_id
+                AND platnost_do IS NULL AND platnost_od >= current_date;
+            UPDATE plomba_historie SET platnost_do = current_date
+            WHERE rizeni_id = OLD.rizeni_id AND parcela_id = OLD.parcela_id AND platnost_do IS NULL;
+        END IF;
+        IF TG_OP <> 'DELETE' THEN
+            INSERT INTO plomba_historie (rizeni_id, parcela_id, platnost_od)
+            VALUES (NEW.rizeni_id, NEW.parcela_id, current_date);
+        END IF;
+        RETURN NULL;
+    END
+    $$",
+    "CREATE OR REPLACE TRIGGER historie AFTER INSERT OR UPDATE OR DELETE ON plomba
+        FOR EACH ROW EXECUTE FUNCTION plomba_historie_zapis()",
+    "CREATE OR REPLACE FUNCTION bremeno_parcela_parcela_historie_zapis() RETURNS trigger LANGUAGE plpgsql AS $$
+    BEGIN
+        IF TG_OP = 'UPDATE' AND OLD IS NOT DISTINCT FROM NEW THEN
+            RETURN NULL;
+        END IF;
+        IF TG_OP <> 'INSERT' THEN
+            DELETE FROM bremeno_parcela_parcela_historie
+            WHERE parcela_id = OLD.parcela_id AND parcela_povinna_id = OLD.parcela_povinna_id
+                AND platnost_do IS NULL AND platnost_od >= current_date;
+            UPDATE bremeno_parcela_parcela_historie SET platnost_do = current_date
+            WHERE parcela_id = OLD.parcela_id AND parcela_povinna_id = OLD.parcela_povinna_id
+                AND platnost_do IS NULL;
+        END IF;
+        IF TG_OP <> 'DELETE' THEN
+            INSERT INTO bremeno_parcela_parcela_historie
+                (parcela_id, parcela_povinna_id, popis, datum_zrizeni, datum_pravnich_ucinku, platnost_od)
+            VALUES (NEW.parcela_id, NEW.parcela_povinna_id, NEW.popis, NEW.datum_zrizeni, NEW.datum_pravnich_ucinku, current_date);
+        END IF;
+        RETURN NULL;
+    END
+    $$",
+    "CREATE OR REPLACE TRIGGER historie AFTER INSERT OR UPDATE OR DELETE ON bremeno_parcela_parcela
+        FOR EACH ROW EXECUTE FUNCTION bremeno_parcela_parcela_historie_zapis()",
+    "CREATE OR REPLACE FUNCTION bremeno_parcela_majitel_historie_zapis() RETURNS trigger LANGUAGE plpgsql AS $$
+    BEGIN
+        IF TG_OP = 'UPDATE' AND OLD IS NOT DISTINCT FROM NEW THEN
+            RETURN NULL;
+        END IF;
+        IF TG_OP <> 'INSERT' THEN
+            DELETE FROM bremeno_parcela_majitel_historie
+            WHERE parcela_id = OLD.parcela_id AND majitel_povinny_id = OLD.majitel_povinny_id
+                AND platnost_do IS NULL AND platnost_od >= current_date;
+            UPDATE bremeno_parcela_majitel_historie SET platnost_do = current_date
+            WHERE parcela_id = OLD.parcela_id AND majitel_povinny_id = OLD.majitel_povinny_id
+                AND platnost_do IS NULL;
+        END IF;
+        IF TG_OP <> 'DELETE' THEN
+            INSERT INTO bremeno_parcela_majitel_historie
+                (parcela_id, majitel_povinny_id, popis, datum_zrizeni, datum_pravnich_ucinku, platnost_od)
+            VALUES (NEW.parcela_id, NEW.majitel_povinny_id, NEW.popis, NEW.datum_zrizeni, NEW.datum_pravnich_ucinku, current_date);
+        END IF;
+        RETURN NULL;
+    END
+    $$",
+    "CREATE OR REPLACE TRIGGER historie AFTER INSERT OR UPDATE OR DELETE ON bremeno_parcela_majitel
+        FOR EACH ROW EXECUTE FUNCTION bremeno_parcela_majitel_historie_zapis()",
+    "INSERT INTO plomba_historie (rizeni_id, parcela_id, platnost_od)
+    SELECT pl.rizeni_id, pl.parcela_id, COALESCE(
+        (SELECT max(h.platnost_do) FROM plomba_historie h
+         WHERE h.rizeni_id = pl.rizeni_id AND h.parcela_id = pl.parcela_id),
+        '-infinity')
+    FROM plomba pl
+    WHERE NOT EXISTS (
+        SELECT 1 FROM plomba_historie h
+        WHERE h.rizeni_id = pl.rizeni_id AND h.parcela_id = pl.parcela_id AND h.platnost_do IS NULL
+    )",
+    "INSERT INTO bremeno_parcela_parcela_historie
+        (parcela_id, parcela_povinna_id, popis, datum_zrizeni, datum_pravnich_ucinku, platnost_od)
+    SELECT b.parcela_id, b.parcela_povinna_id, b.popis, b.datum_zrizeni, b.datum_pravnich_ucinku, COALESCE(
+        (SELECT max(h.platnost_do) FROM bremeno_parcela_parcela_historie h
+         WHERE h.parcela_id = b.parcela_id AND h.parcela_povinna_id = b.parcela_povinna_id),
+        '-infinity')
+    FROM bremeno_parcela_parcela b
+    WHERE NOT EXISTS (
+        SELECT 1 FROM bremeno_parcela_parcela_historie h
+        WHERE h.parcela_id = b.parcela_id AND h.parcela_povinna_id = b.parcela_povinna_id
+            AND h.platnost_do IS NULL
+    )",
+    "INSERT INTO bremeno_parcela_majitel_historie
+        (parcela_id, majitel_povinny_id, popis, datum_zrizeni, datum_pravnich_ucinku, platnost_od)
+    SELECT b.parcela_id, b.majitel_povinny_id, b.popis, b.datum_zrizeni, b.datum_pravnich_ucinku, COALESCE(
+        (SELECT max(h.platnost_do) FROM bremeno_parcela_majitel_historie h
+         WHERE h.parcela_id = b.parcela_id AND h.majitel_povinny_id = b.majitel_povinny_id),
+        '-infinity')
+    FROM bremeno_parcela_majitel b
+    WHERE NOT EXISTS (
+        SELECT 1 FROM bremeno_parcela_majitel_historie h
+        WHERE h.parcela_id = b.parcela_id AND h.majitel_povinny_id = b.majitel_povinny_id
+            AND h.platnost_do IS NULL
+    )",
+    "CREATE OR REPLACE FUNCTION platna(platnost_od date, platnost_do date, as_of date) RETURNS boolean
+        LANGUAGE sql IMMUTABLE
+    AS $$
+        SELECT platnost_od <= as_of AND (platnost_do IS NULL OR as_of < platnost_do)
+    $$",
+    // The parcels on an LV on a date, as they were then
+    "CREATE OR REPLACE FUNCTION parcely_na_lv(ku text, lv integer, as_of date)
+        RETURNS SETOF parcela_lv_historie
+        LANGUAGE sql STABLE
+    AS $$
+        SELECT p.*
+        FROM list_vlastnictvi_historie l
+        JOIN katastralni_uzemi k ON k.id = l.katastralni_uzemi_id
+        JOIN parcela_lv_historie p ON p.list_vlastnictvi_id = l.list_vlastnictvi_id
+            AND platna(p.platnost_od, p.platnost_do, as_of)
+        WHERE k.nazev = ku AND l.cislo_lv = lv AND platna(l.platnost_od, l.platnost_do, as_of)
+    $$",
+    // The LV parts as they stood at the end of a date, read from the history
+    // alone. Easements and titles also count only from their legal effect.
+    "CREATE OR REPLACE FUNCTION fn_get_lv_part_a_as_of(ku text, lv integer, as_of date)
+        RETURNS TABLE(jmeno text, prijmeni text, bydliste text, podil_setin integer)
+        LANGUAGE sql
+    AS $$
+        SELECT DISTINCT m.jmeno, m.prijmeni, m.bydliste, v.podil_setin
+        FROM parcely_na_lv(ku, lv, as_of) p
+        JOIN vlastnictvi_historie v ON v.parcela_id = p.parcela_id AND platna(v.platnost_od, v.platnost_do, as_of)
+        JOIN majitel_historie m ON m.majitel_id = v.majitel_id AND platna(m.platnost_od, m.platnost_do, as_of)
+    $$",
+    "CREATE OR REPLACE FUNCTION fn_get_lv_part_b_as_of(ku text, lv integer, as_of date)
+        RETURNS TABLE(parcelni_cislo integer, je_stavebni boolean, ulice text, cislo_popisne text, nazev_ku text)
+        LANGUAGE sql
+    AS $$
+        SELECT p.parcelni_cislo, p.je_stavebni, p.ulice, p.cislo_popisne, k.nazev
+        FROM parcely_na_lv(ku, lv, as_of) p
+        JOIN katastralni_uzemi k ON k.id = p.katastralni_uzemi_id
+    $$",
+    "CREATE OR REPLACE FUNCTION fn_get_lv_part_b_parcela_as_of(ku text, lv integer, as_of date)
+        RETURNS TABLE(popis text, datum_zrizeni date, datum_pravnich_ucinku date, je_stavebni_opravnena boolean, parcelni_cislo_opravnena integer, cast_parcely_opravnena integer, je_stavebni_povinna boolean, parcelni_cislo_povinna integer, cast_parcely_povinna integer)
+        LANGUAGE sql
+    AS $$
+        SELECT b.popis, b.datum_zrizeni, b.datum_pravnich_ucinku, p.je_stavebni, p.parcelni_cislo, p.cast_parcely, q.je_stavebni, q.parcelni_cislo, q.cast_parcely
+        FROM parcely_na_lv(ku, lv, as_of) p
+        JOIN bremeno_parcela_parcela_historie b ON b.parcela_id = p.parcela_id AND platna(b.platnost_od, b.platnost_do, as_of)
+        JOIN parcela_lv_historie q ON q.parcela_id = b.parcela_povinna_id AND platna(q.platnost_od, q.platnost_do, as_of)
+        WHERE b.datum_pravnich_ucinku <= as_of
+    $$",
+    "CREATE OR REPLACE FUNCTION fn_get_lv_part_b_majitel_as_of(ku text, lv integer, as_of date)
+        RETURNS TABLE(popis text, datum_zrizeni date, datum_pravnich_ucinku date, je_stavebni_opravnena boolean, parcelni_cislo_opravnena integer, cast_parcely_opravnena integer, jmeno_povinny text, prijmeni_povinny text, titul_povinny text, rodne_cislo_povinny text, ico_povinny text)
+        LANGUAGE sql
+    AS $$
+        SELECT b.popis, b.datum_zrizeni, b.datum_pravnich_ucinku, p.je_stavebni, p.parcelni_cislo, p.cast_parcely, m.jmeno, m.prijmeni, m.titul, m.rodne_cislo, m.ico
+        FROM parcely_na_lv(ku, lv, as_of) p
+        JOIN bremeno_parcela_majitel_historie b ON b.parcela_id = p.parcela_id AND platna(b.platnost_od, b.platnost_do, as_of)
+        JOIN majitel_historie m ON m.majitel_id = b.majitel_povinny_id AND platna(m.platnost_od, m.platnost_do, as_of)
+        WHERE b.datum_pravnich_ucinku <= as_of
+    $$",
+    "CREATE OR REPLACE FUNCTION fn_get_lv_part_c_as_of(ku text, lv integer, as_of date)
+        RETURNS TABLE(popis text, datum_zrizeni date, datum_pravnich_ucinku date, je_stavebni_opravnena boolean, parcelni_cislo_opravnena integer, cast_parcely_opravnena integer, je_stavebni_povinna boolean, parcelni_cislo_povinna integer, cast_parcely_povinna integer)
+        LANGUAGE sql
+    AS $$
+        SELECT b.popis, b.datum_zrizeni, b.datum_pravnich_ucinku, p.je_stavebni, p.parcelni_cislo, p.cast_parcely, q.je_stavebni, q.parcelni_cislo, q.cast_parcely
+        FROM parcely_na_lv(ku, lv, as_of) q
+        JOIN bremeno_parcela_parcela_historie b ON b.parcela_povinna_id = q.parcela_id AND platna(b.platnost_od, b.platnost_do, as_of)
+        JOIN parcela_lv_historie p ON p.parcela_id = b.parcela_id AND platna(p.platnost_od, p.platnost_do, as_of)
+        WHERE b.datum_pravnich_ucinku <= as_of
+    $$",
+    "CREATE OR REPLACE FUNCTION fn_get_lv_part_d_as_of(ku text, lv integer, as_of date)
+        RETURNS TABLE(je_stavebni boolean, parcelni_cislo integer, cast_parcely integer, nazev_katastralniho_uzemi text, typ_rizeni_zkratka text, cislo_rizeni integer, rok_rizeni integer)
+        LANGUAGE sql
+    AS $$
+        SELECT p.je_stavebni, p.parcelni_cislo, p.cast_parcely, k.nazev, t.zkratka, r.cislo_rizeni, r.rok
+        FROM parcely_na_lv(ku, lv, as_of) p
+        JOIN plomba_historie pl ON pl.parcela_id = p.parcela_id AND platna(pl.platnost_od, pl.platnost_do, as_of)
+        JOIN katastralni_uzemi k ON k.id = p.katastralni_uzemi_id
+        JOIN rizeni r ON r.id = pl.rizeni_id
+        JOIN typ_rizeni t ON t.id = r.typ_rizeni_id
+    $$",
+    "CREATE OR REPLACE FUNCTION fn_get_lv_part_e_as_of(ku text, lv integer, as_of date)
+        RETURNS TABLE(druh text, popis text, datum_vyhotoveni date, datum_pravnich_ucinku date, typ_rizeni_zkratka text, cislo_rizeni integer, rok_rizeni integer, jmeno text, prijmeni text)
+        LANGUAGE sql
+    AS $$
+        SELECT * FROM fn_get_lv_part_e(ku, lv) e WHERE e.datum_pravnich_ucinku <= as_of
+    $$",
+    "CREATE OR REPLACE FUNCTION fn_get_lv_part_f_as_of(ku text, lv integer, as_of date)
+        RETURNS TABLE(je_stavebni boolean, parcelni_cislo integer, cast_parcely integer, hodnota integer)
+        LANGUAGE sql
+    AS $$
+        SELECT p.je_stavebni, p.parcelni_cislo, p.cast_parcely, b.hodnota
+        FROM parcely_na_lv(ku, lv, as_of) p
+        LEFT JOIN bpej b ON b.id = p.bpej_id
+    $$",
+    // Parcels and per-parcel shares of an LV on a date, with their ids, for
+    // comparing two states
+    "CREATE OR REPLACE FUNCTION fn_get_lv_parcely_as_of(ku text, lv integer, as_of date)
+        RETURNS TABLE(parcela_id integer, je_stavebni boolean, parcelni_cislo integer, cast_parcely integer)
+        LANGUAGE sql
+    AS $$
+        SELECT p.parcela_id, p.je_stavebni, p.parcelni_cislo, p.cast_parcely
+        FROM parcely_na_lv(ku, lv, as_of) p
+        ORDER BY p.je_stavebni DESC, p.parcelni_cislo, p.cast_parcely
+    $$",
+    "CREATE OR REPLACE FUNCTION fn_get_lv_podily_as_of(ku text, lv integer, as_of date)
+        RETURNS TABLE(parcela_id integer, majitel_id integer, jmeno text, prijmeni text, podil_setin integer)
+        LANGUAGE sql
+    AS $$
+        SELECT v.parcela_id, v.majitel_id, m.jmeno, m.prijmeni, v.podil_setin
+        FROM parcely_na_lv(ku, lv, as_of) p
+        JOIN vlastnictvi_historie v ON v.parcela_id = p.parcela_id AND platna(v.platnost_od, v.platnost_do, as_of)
+        JOIN majitel_historie m ON m.majitel_id = v.majitel_id AND platna(m.platnost_od, m.platnost_do, as_of)
+        ORDER BY v.parcela_id, v.majitel_id
+    $$",
+    "CREATE OR REPLACE FUNCTION fn_get_parcela_as_of(ku text, st boolean, pc integer, cp integer, as_of date)
+        RETURNS TABLE(je_stavebni boolean, parcelni_cislo integer, cast_parcely integer, vymera_metru_ctverecnich numeric, ulice text, cislo_popisne text, hodnota integer, cislo_lv integer)
+        LANGUAGE sql
+    AS $$
+        SELECT p.je_stavebni, p.parcelni_cislo, p.cast_parcely, p.vymera_metru_ctverecnich, p.ulice, p.cislo_popisne, b.hodnota, l.cislo_lv
+        FROM parcela_lv_historie p
+        JOIN katastralni_uzemi k ON k.id = p.katastralni_uzemi_id
+        JOIN list_vlastnictvi_historie l ON l.list_vlastnictvi_id = p.list_vlastnictvi_id
+            AND platna(l.platnost_od, l.platnost_do, as_of)
+        LEFT JOIN bpej b ON b.id = p.bpej_id
+        WHERE k.nazev = ku AND p.je_stavebni = st AND p.parcelni_cislo = pc AND p.cast_parcely = cp
+            AND platna(p.platnost_od, p.platnost_do, as_of)
+    $$",
+    // `vlastnicky_hash` is the hex SHA-256 of the LV's ownership state: one
+    // line `P|<katastralni_uzemi_id>|<t or f>|<parcelni_cislo>|<cast_parcely>`
+    // per parcel, ordered by those values, each followed by its shares as
+    // `V|<majitel_id>|<podil_setin>` ordered by owner, joined by newlines.
+    "CREATE OR REPLACE FUNCTION fn_vlastnicky_hash(lv_id integer) RETURNS text
+        LANGUAGE sql STABLE
+    AS $$
+        SELECT encode(sha256(convert_to(COALESCE(string_agg(r.radek, E'\n' ORDER BY r.ku, r.st, r.pc, r.cp, r.majitel), ''), 'UTF8')), 'hex')
+        FROM (
+            SELECT p.katastralni_uzemi_id AS ku, p.je_stavebni AS st, p.parcelni_cislo AS pc, p.cast_parcely AS cp, -1 AS majitel,
+                format('P|%s|%s|%s|%s', p.katastralni_uzemi_id, CASE WHEN p.je_stavebni THEN 't' ELSE 'f' END, p.parcelni_cislo, p.cast_parcely) AS radek
+            FROM parcela p
+            WHERE p.list_vlastnictvi_id = lv_id
+            UNION ALL
+            SELECT p.katastralni_uzemi_id, p.je_stavebni, p.parcelni_cislo, p.cast_parcely, v.majitel_id,
+                format('V|%s|%s', v.majitel_id, v.podil_setin)
+            FROM parcela p
+            JOIN vlastnictvi v ON v.parcela_id = p.id
+            WHERE p.list_vlastnictvi_id = lv_id
+        ) r
+    $$",
+    // Every write of an LV row stores the computed hash. A client may send null
+    // or the matching hash; any other value is rejected, including a stale one
+    // sent back unchanged. With the triggers below keeping it current, a stored
+    // hash can only go stale through data changed with these triggers disabled
+    // or from before they existed; `/lv/verify` finds such LVs, and saving the
+    // LV again stores the current value.
+    "CREATE OR REPLACE FUNCTION vlastnicky_hash_zapis() RETURNS trigger LANGUAGE plpgsql AS $$
+    DECLARE
+        vypocteny TEXT := fn_vlastnicky_hash(NEW.id);
+    BEGIN
+        IF NEW.vlastnicky_hash IS NOT NULL AND NEW.vlastnicky_hash <> vypocteny THEN
+            RAISE EXCEPTION 'vlastnicky_hash does not match the ownership state of the LV'
+                USING COLUMN = 'vlastnicky_hash';
+        END IF;
+        NEW.vlastnicky_hash := vypocteny;
+        RETURN NEW;
+    END
+    $$",
+    "CREATE OR REPLACE TRIGGER vlastnicky_hash BEFORE INSERT OR UPDATE ON list_vlastnictvi
+        FOR EACH ROW EXECUTE FUNCTION vlastnicky_hash_zapis()",
+    "CREATE OR REPLACE FUNCTION vlastnicky_hash_prepocet(lv_ids integer[]) RETURNS void
+        LANGUAGE sql
+    AS $$
+        UPDATE list_vlastnictvi SET vlastnicky_hash = fn_vlastnicky_hash(id)
+        WHERE id = ANY(lv_ids) AND vlastnicky_hash IS DISTINCT FROM fn_vlastnicky_hash(id)
+    $$",
+    // Once per statement, so a bulk import rehashes each LV once. Transition
+    // tables allow a single event per trigger; `stare` and `nove` are only
+    // referenced by the events that have them.
+    "CREATE OR REPLACE FUNCTION vlastnicky_hash_parcela() RETURNS trigger LANGUAGE plpgsql AS $$
+    BEGIN
+        IF TG_OP = 'INSERT' THEN
+            PERFORM vlastnicky_hash_prepocet(ARRAY(SELECT DISTINCT list_vlastnictvi_id FROM nove));
+        ELSIF TG_OP = 'DELETE' THEN
+            PERFORM vlastnicky_hash_prepocet(ARRAY(SELECT DISTINCT list_vlastnictvi_id FROM stare));
+        ELSE
+            PERFORM vlastnicky_hash_prepocet(ARRAY(
+                SELECT list_vlastnictvi_id FROM stare UNION SELECT list_vlastnictvi_id FROM nove
+            ));
+        END IF;
+        RETURN NULL;
+    END
+    $$",
+    "CREATE OR REPLACE TRIGGER vlastnicky_hash_insert AFTER INSERT ON parcela
+        REFERENCING NEW TABLE AS nove
+        FOR EACH STATEMENT EXECUTE FUNCTION vlastnicky_hash_parcela()",
+    "CREATE OR REPLACE TRIGGER vlastnicky_hash_update AFTER UPDATE ON parcela
+        REFERENCING OLD TABLE AS stare NEW TABLE AS nove
+        FOR EACH STATEMENT EXECUTE FUNCTION vlastnicky_hash_parcela()",
+    "CREATE OR REPLACE TRIGGER vlastnicky_hash_delete AFTER DELETE ON parcela
+        REFERENCING OLD TABLE AS stare
+        FOR EACH STATEMENT EXECUTE FUNCTION vlastnicky_hash_parcela()",
+    "CREATE OR REPLACE FUNCTION vlastnicky_hash_vlastnictvi() RETURNS trigger LANGUAGE plpgsql AS $$
+    BEGIN
+        IF TG_OP = 'INSERT' THEN
+            PERFORM vlastnicky_hash_prepocet(ARRAY(
+                SELECT DISTINCT p.list_vlastnictvi_id FROM nove v JOIN parcela p ON p.id = v.parcela_id
+            ));
+        ELSIF TG_OP = 'DELETE' THEN
+            PERFORM vlastnicky_hash_prepocet(ARRAY(
+                SELECT DISTINCT p.list_vlastnictvi_id FROM stare v JOIN parcela p ON p.id = v.parcela_id
+            ));
+        ELSE
+            PERFORM vlastnicky_hash_prepocet(ARRAY(
+                SELECT p.list_vlastnictvi_id FROM stare v JOIN parcela p ON p.id = v.parcela_id
+                UNION
+                SELECT p.list_vlastnictvi_id FROM nove v JOIN parcela p ON p.id = v.parcela_id
+            ));
+        END IF;
+        RETURN NULL;
+    END
+    $$",
+    "CREATE OR REPLACE TRIGGER vlastnicky_hash_insert AFTER INSERT ON vlastnictvi
+        REFERENCING NEW TABLE AS nove
+        FOR EACH STATEMENT EXECUTE FUNCTION vlastnicky_hash_vlastnictvi()",
+    "CREATE OR REPLACE TRIGGER vlastnicky_hash_update AFTER UPDATE ON vlastnictvi
+        REFERENCING OLD TABLE AS stare NEW TABLE AS nove
+        FOR EACH STATEMENT EXECUTE FUNCTION vlastnicky_hash_vlastnictvi()",
+    "CREATE OR REPLACE TRIGGER vlastnicky_hash_delete AFTER DELETE ON vlastnictvi
+        REFERENCING OLD TABLE AS stare
+        FOR EACH STATEMENT EXECUTE FUNCTION vlastnicky_hash_vlastnictvi()",
+    // Parcels among `parcely` whose shares, if any, do not sum to 100, with
+    // the message to reject them by
+    "CREATE OR REPLACE FUNCTION fn_podily_necele(parcely integer[])
+        RETURNS TABLE(parcela_id integer, zprava text)
+        LANGUAGE sql STABLE
+    AS $$
+        SELECT pa.id, format('Ownership shares of parcel %s in %s (LV %s) sum to %s/100 instead of a whole',
+            CASE WHEN pa.je_stavebni THEN 'st. ' ELSE '' END || pa.parcelni_cislo
+                || CASE WHEN pa.cast_parcely = 0 THEN '' ELSE '/' || pa.cast_parcely END,
+            k.nazev, l.cislo_lv, sum(v.podil_setin))
+        FROM parcela pa
+        JOIN katastralni_uzemi k ON k.id = pa.katastralni_uzemi_id
+        JOIN list_vlastnictvi l ON l.id = pa.list_vlastnictvi_id
+        JOIN vlastnictvi v ON v.parcela_id = pa.id
+        WHERE pa.id = ANY(parcely)
+        GROUP BY pa.id, k.nazev, l.cislo_lv
+        HAVING sum(v.podil_setin) <> 100
+        ORDER BY pa.id
+    $$",
+    // The shares of a parcel's owners must add up to 100, unless it has none.
+    // Checked at commit, so shares can be re-allocated over several
+    // statements of one transaction, e.g. a `/batch`. Each statement queues
+    // the parcels it touched, once each, and the check runs once per queued
+    // parcel, however many of its shares were written. Parcels out of balance
+    // can only come from data changed with these triggers disabled or from
+    // before they existed; `/lv/balance` lists them.
+    "CREATE TABLE IF NOT EXISTS podily_ke_kontrole (
+        parcela_id INT PRIMARY KEY
+    )",
+    "CREATE OR REPLACE FUNCTION podily_zmena() RETURNS trigger LANGUAGE plpgsql AS $$
+    BEGIN
+        IF TG_OP = 'INSERT' THEN
+            INSERT INTO podily_ke_kontrole SELECT DISTINCT parcela_id FROM nove
+                ON CONFLICT DO NOTHING;
+        ELSIF TG_OP = 'DELETE' THEN
+            INSERT INTO podily_ke_kontrole SELECT DISTINCT parcela_id FROM stare
+                ON CONFLICT DO NOTHING;
+        ELSE
+            INSERT INTO podily_ke_kontrole SELECT parcela_id FROM stare UNION SELECT parcela_id FROM nove
+                ON CONFLICT DO NOTHING;
+        END IF;
+        RETURN NULL;
+    END
+    $$",
+    "CREATE OR REPLACE TRIGGER podily_zmena_insert AFTER INSERT ON vlastnictvi
+        REFERENCING NEW TABLE AS nove
+        FOR EACH STATEMENT EXECUTE FUNCTION podily_zmena()",
+    "CREATE OR REPLACE TRIGGER podily_zmena_update AFTER UPDATE ON vlastnictvi
+        REFERENCING OLD TABLE AS stare NEW TABLE AS nove
+        FOR EACH STATEMENT EXECUTE FUNCTION podily_zmena()",
+    "CREATE OR REPLACE TRIGGER podily_zmena_delete AFTER DELETE ON vlastnictvi
+        REFERENCING OLD TABLE AS stare
+        FOR EACH STATEMENT EXECUTE FUNCTION podily_zmena()",
+    "CREATE OR REPLACE FUNCTION podily_celek_kontrola() RETURNS trigger LANGUAGE plpgsql AS $$
+    DECLARE
+        p RECORD;
+    BEGIN
+        FOR p IN SELECT * FROM fn_podily_necele(ARRAY[NEW.parcela_id]) LOOP
+            RAISE EXCEPTION '%', p.zprava
+                USING COLUMN = 'podil_setin', CONSTRAINT = 'podily_celek';
+        END LOOP;
+        DELETE FROM podily_ke_kontrole WHERE parcela_id = NEW.parcela_id;
+        RETURN NULL;
+    END
+    $$",
+    // Constraint triggers cannot be replaced, only created once
+    "DO $$
+    BEGIN
+        IF NOT EXISTS (
+            SELECT 1 FROM pg_trigger WHERE tgname = 'podily_celek' AND tgrelid = 'podily_ke_kontrole'::regclass
+        ) THEN
+            CREATE CONSTRAINT TRIGGER podily_celek AFTER INSERT ON podily_ke_kontrole
+                DEFERRABLE INITIALLY DEFERRED
+                FOR EACH ROW EXECUTE FUNCTION podily_celek_kontrola();
+        END IF;
+    END
+    $$",
+];
+
+/// Tables whose every write is recorded in `audit_log`, with their key columns.
+pub const AUDITED: &[(&str, &[&str])] = &[
+    ("app_user", &["id"]),
+    ("api_token", &["id"]),
+    (Majitel::TABLE.name, Majitel::TABLE.key),
+    (Kraj::TABLE.name, Kraj::TABLE.key),
+    (Okres::TABLE.name, Okres::TABLE.key),
+    (Obec::TABLE.name, Obec::TABLE.key),
+    (KatastralniUzemi::TABLE.name, KatastralniUzemi::TABLE.key),
+    (Bpej::TABLE.name, Bpej::TABLE.key),
+    (TypRizeni::TABLE.name, TypRizeni::TABLE.key),
+    (TypOperace::TABLE.name, TypOperace::TABLE.key),
+    (TypUcastnika::TABLE.name, TypUcastnika::TABLE.key),
+    (UcastnikRizeni::TABLE.name, UcastnikRizeni::TABLE.key),
+    (ListVlastnictvi::TABLE.name, ListVlastnictvi::TABLE.key),
+    (ParcelaRow::TABLE.name, ParcelaRow::TABLE.key),
+    (Rizeni::TABLE.name, Rizeni::TABLE.key),
+    (Vlastnictvi::TABLE.name, Vlastnictvi::TABLE.key),
+    (
+        BremenoParcelaParcela::TABLE.name,
+        BremenoParcelaParcela::TABLE.key,
+    ),
+    (
+        BremenoParcelaMajitel::TABLE.name,
+        BremenoParcelaMajitel::TABLE.key,
+    ),
+    (Plomba::TABLE.name, Plomba::TABLE.key),
+    (RizeniOperaceRow::TABLE.name, RizeniOperaceRow::TABLE.key),
+    (Ucast::TABLE.name, Ucast::TABLE.key),
+    (NabyvaciTitul::TABLE.name, NabyvaciTitul::TABLE.key),
+];
+
+/// Key columns of an audited table.
+pub fn audited_key(entity: &str) -> Option<&'static [&'static str]> {
+    AUDITED
+        .iter()
+        .find(|(name, _)| *name == entity)
+        .map(|(_, key)| *key)
+}
+
+pub async fn ensure_schema(pool: Pool) -> Result<()> {
+    let client = pool.get().await?;
+    for stmt in SCHEMA {
+        client.batch_execute(stmt).await?;
+    }
+    for (table, key) in AUDITED {
+        let args: Vec<String> = key.iter().map(|k| format!("'{}'", k)).collect();
+        client
+            .batch_execute(&format!(
+                "CREATE OR REPLACE TRIGGER audit AFTER INSERT OR UPDATE OR DELETE ON {} \
+                 FOR EACH ROW EXECUTE FUNCTION audit_row({})",
+                table,
+                args.join(", ")
+            ))
+            .await?;
+    }
+    Ok(())
+}
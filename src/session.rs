@@ -0,0 +1,254 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use deadpool_postgres::Pool;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tracing::{error, info};
+
+use crate::db::user_from_row;
+use crate::models::User;
+
+pub const SESSION_COOKIE: &str = "katastr_session";
+
+#[derive(Debug, Clone)]
+pub struct Session {
+    pub user: User,
+    pub created_at: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+}
+
+impl Session {
+    pub fn new(user: User) -> Self {
+        let now = Utc::now();
+        Session {
+            user,
+            created_at: now,
+            last_seen: now,
+        }
+    }
+
+    /// A session dies either after `idle_timeout` without a request or after
+    /// `max_age` since login, whichever comes first.
+    pub fn is_expired(
+        &self,
+        now: DateTime<Utc>,
+        idle_timeout: Duration,
+        max_age: Duration,
+    ) -> bool {
+        let since = |t: DateTime<Utc>| (now - t).to_std().unwrap_or_default();
+        since(self.last_seen) >= idle_timeout || since(self.created_at) >= max_age
+    }
+}
+
+/// Backend holding the server-side half of `katastr_session` cookies.
+///
+/// Expiry policy lives in the store so that every backend enforces the same
+/// idle and absolute timeouts.
+#[async_trait]
+pub trait SessionStore: Send + Sync {
+    async fn insert(&self, id: &str, session: Session) -> Result<()>;
+
+    /// Returns the session if it exists and is still alive, refreshing its
+    /// last-seen time. Expired sessions are removed and reported as missing.
+    async fn touch(&self, id: &str) -> Result<Option<Session>>;
+
+    async fn remove(&self, id: &str) -> Result<()>;
+
+    /// Logs out every session of the given user, e.g. after a role change or deletion.
+    async fn remove_user(&self, user_id: i32) -> Result<()>;
+
+    /// Removes every expired session, returns how many were dropped.
+    async fn purge_expired(&self) -> Result<u64>;
+}
+
+/// Process-local store; sessions are lost on restart and not shared between instances.
+pub struct MemorySessionStore {
+    sessions: RwLock<HashMap<String, Session>>,
+    idle_timeout: Duration,
+    max_age: Duration,
+}
+
+impl MemorySessionStore {
+    pub fn new(idle_timeout: Duration, max_age: Duration) -> Self {
+        MemorySessionStore {
+            sessions: RwLock::new(HashMap::new()),
+            idle_timeout,
+            max_age,
+        }
+    }
+}
+
+#[async_trait]
+impl SessionStore for MemorySessionStore {
+    async fn insert(&self, id: &str, session: Session) -> Result<()> {
+        let mut sessions = self
+            .sessions
+            .write()
+            .map_err(|_| anyhow::anyhow!("Lock error"))?;
+        sessions.insert(id.to_string(), session);
+        Ok(())
+    }
+
+    async fn touch(&self, id: &str) -> Result<Option<Session>> {
+        let mut sessions = self
+            .sessions
+            .write()
+            .map_err(|_| anyhow::anyhow!("Lock error"))?;
+        let now = Utc::now();
+        match sessions.get_mut(id) {
+            Some(session) if !session.is_expired(now, self.idle_timeout, self.max_age) => {
+                session.last_seen = now;
+                Ok(Some(session.clone()))
+            }
+            Some(_) => {
+                sessions.remove(id);
+                Ok(None)
+            }
+            None => Ok(None),
+        }
+    }
+
+    async fn remove(&self, id: &str) -> Result<()> {
+        let mut sessions = self
+            .sessions
+            .write()
+            .map_err(|_| anyhow::anyhow!("Lock error"))?;
+        sessions.remove(id);
+        Ok(())
+    }
+
+    async fn remove_user(&self, user_id: i32) -> Result<()> {
+        let mut sessions = self
+            .sessions
+            .write()
+            .map_err(|_| anyhow::anyhow!("Lock error"))?;
+        sessions.retain(|_, s| s.user.id != user_id);
+        Ok(())
+    }
+
+    async fn purge_expired(&self) -> Result<u64> {
+        let mut sessions = self
+            .sessions
+            .write()
+            .map_err(|_| anyhow::anyhow!("Lock error"))?;
+        let now = Utc::now();
+        let before = sessions.len();
+        sessions.retain(|_, s| !s.is_expired(now, self.idle_timeout, self.max_age));
+        Ok((before - sessions.len()) as u64)
+    }
+}
+
+/// Store backed by the `app_session` table, so sessions survive restarts and
+/// are shared by every instance using the same database. Timestamps come from
+/// the database clock to keep instances consistent.
+pub struct PostgresSessionStore {
+    pool: Pool,
+    idle_timeout: Duration,
+    max_age: Duration,
+}
+
+impl PostgresSessionStore {
+    pub fn new(pool: Pool, idle_timeout: Duration, max_age: Duration) -> Self {
+        PostgresSessionStore {
+            pool,
+            idle_timeout,
+            max_age,
+        }
+    }
+}
+
+#[async_trait]
+impl SessionStore for PostgresSessionStore {
+    async fn insert(&self, id: &str, session: Session) -> Result<()> {
+        let client = self.pool.get().await?;
+        client
+            .execute(
+                "INSERT INTO app_session (id, user_id, created_at, last_seen) VALUES ($1, $2, now(), now())",
+                &[&id, &session.user.id],
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn touch(&self, id: &str) -> Result<Option<Session>> {
+        let client = self.pool.get().await?;
+        let row = client
+            .query_opt(
+                "UPDATE app_session s SET last_seen = now() FROM app_user u \
+                 WHERE s.id = $1 AND u.id = s.user_id \
+                 AND s.last_seen > now() - make_interval(secs => $2) \
+                 AND s.created_at > now() - make_interval(secs => $3) \
+                 RETURNING u.id, u.username, u.role, u.can_view_personal_data, \
+                 s.created_at, s.last_seen",
+                &[
+                    &id,
+                    &self.idle_timeout.as_secs_f64(),
+                    &self.max_age.as_secs_f64(),
+                ],
+            )
+            .await?;
+        match row {
+            Some(row) => Ok(Some(Session {
+                user: user_from_row(&row)?,
+                created_at: row.try_get("created_at")?,
+                last_seen: row.try_get("last_seen")?,
+            })),
+            None => {
+                // Either unknown or expired; make sure an expired row does not linger
+                client
+                    .execute("DELETE FROM app_session WHERE id = $1", &[&id])
+                    .await?;
+                Ok(None)
+            }
+        }
+    }
+
+    async fn remove(&self, id: &str) -> Result<()> {
+        let client = self.pool.get().await?;
+        client
+            .execute("DELETE FROM app_session WHERE id = $1", &[&id])
+            .await?;
+        Ok(())
+    }
+
+    async fn remove_user(&self, user_id: i32) -> Result<()> {
+        let client = self.pool.get().await?;
+        client
+            .execute("DELETE FROM app_session WHERE user_id = $1", &[&user_id])
+            .await?;
+        Ok(())
+    }
+
+    async fn purge_expired(&self) -> Result<u64> {
+        let client = self.pool.get().await?;
+        let rows = client
+            .execute(
+                "DELETE FROM app_session \
+                 WHERE last_seen <= now() - make_interval(secs => $1) \
+                 OR created_at <= now() - make_interval(secs => $2)",
+                &[
+                    &self.idle_timeout.as_secs_f64(),
+                    &self.max_age.as_secs_f64(),
+                ],
+            )
+            .await?;
+        Ok(rows)
+    }
+}
+
+/// Periodically purges expired sessions so the store does not grow forever.
+pub fn spawn_session_sweeper(store: Arc<dyn SessionStore>, every: Duration) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(every);
+        loop {
+            interval.tick().await;
+            match store.purge_expired().await {
+                Ok(0) => {}
+                Ok(removed) => info!("Session sweeper: removed {} expired sessions", removed),
+                Err(e) => error!("Session sweeper failed: {}", e),
+            }
+        }
+    });
+}
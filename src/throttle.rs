@@ -0,0 +1,333 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Length of the window the per-IP and global attempt limits are counted in.
+const WINDOW: Duration = Duration::from_secs(60);
+
+/// Failures tolerated before backoff kicks in, so a single typo is not punished.
+const FREE_FAILURES: u32 = 2;
+
+/// Upper bound of the exponential backoff before the lockout takes over.
+const MAX_BACKOFF: Duration = Duration::from_secs(300);
+
+/// Keeps per-IP entries bounded; stale ones are dropped once the map grows past this.
+const PRUNE_THRESHOLD: usize = 4096;
+
+#[derive(Debug, Clone, Copy)]
+pub struct LoginThrottleConfig {
+    /// Login attempts a single IP may make per minute
+    pub per_ip_per_minute: u32,
+    /// Login attempts all clients together may make per minute
+    pub global_per_minute: u32,
+    /// Consecutive failures after which an IP is locked out
+    pub lockout_threshold: u32,
+    pub lockout_duration: Duration,
+}
+
+#[derive(Debug)]
+struct Window {
+    started: Instant,
+    attempts: u32,
+}
+
+impl Window {
+    fn new(now: Instant) -> Self {
+        Window {
+            started: now,
+            attempts: 0,
+        }
+    }
+
+    /// Counts an attempt, or returns how long until the window resets if it is full.
+    fn hit(&mut self, now: Instant, limit: u32) -> Result<(), Duration> {
+        if now.duration_since(self.started) >= WINDOW {
+            *self = Window::new(now);
+        }
+        if self.attempts >= limit {
+            return Err(WINDOW.saturating_sub(now.duration_since(self.started)));
+        }
+        self.attempts += 1;
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct IpState {
+    window: Window,
+    failures: u32,
+    blocked_until: Option<Instant>,
+    last_seen: Instant,
+}
+
+impl IpState {
+    fn new(now: Instant) -> Self {
+        IpState {
+            window: Window::new(now),
+            failures: 0,
+            blocked_until: None,
+            last_seen: now,
+        }
+    }
+}
+
+/// Why an attempt was refused, with how long the client should wait.
+#[derive(Debug, Clone, Copy)]
+pub enum Throttled {
+    Backoff(Duration),
+    LockedOut(Duration),
+    IpRateLimited(Duration),
+    GlobalRateLimited(Duration),
+}
+
+impl Throttled {
+    pub fn retry_after(&self) -> Duration {
+        match *self {
+            Throttled::Backoff(d)
+            | Throttled::LockedOut(d)
+            | Throttled::IpRateLimited(d)
+            | Throttled::GlobalRateLimited(d) => d,
+        }
+    }
+
+    pub fn reason(&self) -> &'static str {
+        match self {
+            Throttled::Backoff(_) => "backoff",
+            Throttled::LockedOut(_) => "lockout",
+            Throttled::IpRateLimited(_) => "ip_rate_limit",
+            Throttled::GlobalRateLimited(_) => "global_rate_limit",
+        }
+    }
+}
+
+/// In-memory brute-force protection for `/auth`.
+///
+/// Every attempt counts against a per-IP and a global per-minute budget.
+/// Consecutive failures from one IP add an exponential backoff and, past
+/// `lockout_threshold`, a lockout; a successful login clears the IP's record.
+pub struct LoginThrottle {
+    config: LoginThrottleConfig,
+    ips: Mutex<HashMap<IpAddr, IpState>>,
+    global: Mutex<Window>,
+}
+
+impl LoginThrottle {
+    pub fn new(config: LoginThrottleConfig) -> Self {
+        LoginThrottle {
+            config,
+            ips: Mutex::new(HashMap::new()),
+            global: Mutex::new(Window::new(Instant::now())),
+        }
+    }
+
+    /// Admits an attempt from `ip`, counting it against the limits, or says
+    /// how long the client has to wait.
+    pub fn check(&self, ip: IpAddr) -> Result<(), Throttled> {
+        self.check_at(ip, Instant::now())
+    }
+
+    fn check_at(&self, ip: IpAddr, now: Instant) -> Result<(), Throttled> {
+        let mut ips = self.ips.lock().unwrap_or_else(|e| e.into_inner());
+        if ips.len() > PRUNE_THRESHOLD {
+            let horizon = self.config.lockout_duration.max(MAX_BACKOFF).max(WINDOW);
+            ips.retain(|_, s| {
+                now.duration_since(s.last_seen) < horizon
+                    || s.blocked_until.is_some_and(|until| until > now)
+            });
+        }
+        let entry = ips.entry(ip).or_insert_with(|| IpState::new(now));
+        entry.last_seen = now;
+
+        if let Some(until) = entry.blocked_until.filter(|until| *until > now) {
+            let wait = until - now;
+            return Err(if entry.failures >= self.config.lockout_threshold {
+                Throttled::LockedOut(wait)
+            } else {
+                Throttled::Backoff(wait)
+            });
+        }
+        if entry.failures >= self.config.lockout_threshold {
+            // Lockout served, start over with a clean slate
+            entry.failures = 0;
+            entry.blocked_until = None;
+        }
+
+        entry
+            .window
+            .hit(now, self.config.per_ip_per_minute)
+            .map_err(Throttled::IpRateLimited)?;
+        self.global
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .hit(now, self.config.global_per_minute)
+            .map_err(Throttled::GlobalRateLimited)
+    }
+
+    /// Records a failed attempt; returns the lockout duration if this failure
+    /// triggered one.
+    pub fn record_failure(&self, ip: IpAddr) -> Option<Duration> {
+        self.record_failure_at(ip, Instant::now())
+    }
+
+    fn record_failure_at(&self, ip: IpAddr, now: Instant) -> Option<Duration> {
+        let mut ips = self.ips.lock().unwrap_or_else(|e| e.into_inner());
+        let entry = ips.entry(ip).or_insert_with(|| IpState::new(now));
+        entry.failures += 1;
+        entry.last_seen = now;
+
+        if entry.failures >= self.config.lockout_threshold {
+            entry.blocked_until = Some(now + self.config.lockout_duration);
+            return Some(self.config.lockout_duration);
+        }
+        if entry.failures > FREE_FAILURES {
+            let exponent = (entry.failures - FREE_FAILURES - 1).min(16);
+            let backoff = Duration::from_secs(1u64 << exponent).min(MAX_BACKOFF);
+            entry.blocked_until = Some(now + backoff);
+        }
+        None
+    }
+
+    pub fn record_success(&self, ip: IpAddr) {
+        let mut ips = self.ips.lock().unwrap_or_else(|e| e.into_inner());
+        ips.remove(&ip);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: LoginThrottleConfig = LoginThrottleConfig {
+        per_ip_per_minute: 100,
+        global_per_minute: 1000,
+        lockout_threshold: 5,
+        lockout_duration: Duration::from_secs(900),
+    };
+
+    fn ip(last: u8) -> IpAddr {
+        IpAddr::from([192, 0, 2, last])
+    }
+
+    fn seconds(d: Duration) -> u64 {
+        d.as_secs()
+    }
+
+    #[test]
+    fn backs_off_exponentially_after_the_free_failures() {
+        let throttle = LoginThrottle::new(CONFIG);
+        let now = Instant::now();
+        for _ in 0..FREE_FAILURES {
+            assert_eq!(throttle.record_failure_at(ip(1), now), None);
+            assert!(throttle.check_at(ip(1), now).is_ok());
+        }
+
+        throttle.record_failure_at(ip(1), now);
+        match throttle.check_at(ip(1), now) {
+            Err(Throttled::Backoff(wait)) => assert_eq!(seconds(wait), 1),
+            other => panic!("expected a backoff, got {:?}", other),
+        }
+        throttle.record_failure_at(ip(1), now);
+        match throttle.check_at(ip(1), now) {
+            Err(Throttled::Backoff(wait)) => assert_eq!(seconds(wait), 2),
+            other => panic!("expected a backoff, got {:?}", other),
+        }
+        assert!(
+            throttle
+                .check_at(ip(1), now + Duration::from_secs(2))
+                .is_ok()
+        );
+        // Other clients are not affected
+        assert!(throttle.check_at(ip(2), now).is_ok());
+    }
+
+    #[test]
+    fn locks_out_and_starts_over_once_served() {
+        let throttle = LoginThrottle::new(CONFIG);
+        let now = Instant::now();
+        for _ in 1..CONFIG.lockout_threshold {
+            assert_eq!(throttle.record_failure_at(ip(1), now), None);
+        }
+        assert_eq!(
+            throttle.record_failure_at(ip(1), now),
+            Some(CONFIG.lockout_duration)
+        );
+        match throttle.check_at(ip(1), now + Duration::from_secs(60)) {
+            Err(Throttled::LockedOut(wait)) => assert_eq!(seconds(wait), 840),
+            other => panic!("expected a lockout, got {:?}", other),
+        }
+
+        let served = now + CONFIG.lockout_duration;
+        assert!(throttle.check_at(ip(1), served).is_ok());
+        // The failure count was reset with the lockout
+        assert_eq!(throttle.record_failure_at(ip(1), served), None);
+        assert!(throttle.check_at(ip(1), served).is_ok());
+    }
+
+    #[test]
+    fn success_clears_the_record() {
+        let throttle = LoginThrottle::new(CONFIG);
+        let now = Instant::now();
+        for _ in 0..=FREE_FAILURES {
+            throttle.record_failure_at(ip(1), now);
+        }
+        assert!(throttle.check_at(ip(1), now).is_err());
+        throttle.record_success(ip(1));
+        assert!(throttle.check_at(ip(1), now).is_ok());
+    }
+
+    #[test]
+    fn limits_attempts_per_ip_and_globally() {
+        let throttle = LoginThrottle::new(LoginThrottleConfig {
+            per_ip_per_minute: 2,
+            global_per_minute: 3,
+            ..CONFIG
+        });
+        let now = Instant::now();
+        assert!(throttle.check_at(ip(1), now).is_ok());
+        assert!(throttle.check_at(ip(1), now).is_ok());
+        assert!(matches!(
+            throttle.check_at(ip(1), now),
+            Err(Throttled::IpRateLimited(_))
+        ));
+        assert!(throttle.check_at(ip(2), now).is_ok());
+        match throttle.check_at(ip(3), now + Duration::from_secs(20)) {
+            // The global window opened when the throttle was created, just before `now`
+            Err(Throttled::GlobalRateLimited(wait)) => {
+                assert!(wait <= Duration::from_secs(40) && seconds(wait) >= 39)
+            }
+            other => panic!("expected the global limit, got {:?}", other),
+        }
+        // Both windows start over after a minute
+        assert!(throttle.check_at(ip(1), now + WINDOW).is_ok());
+    }
+
+    #[test]
+    fn prunes_entries_not_seen_for_a_while() {
+        let throttle = LoginThrottle::new(LoginThrottleConfig {
+            per_ip_per_minute: u32::MAX,
+            global_per_minute: u32::MAX,
+            ..CONFIG
+        });
+        let now = Instant::now();
+        for i in 0..=PRUNE_THRESHOLD as u32 {
+            let filler = IpAddr::from(std::net::Ipv4Addr::from((10 << 24) | i));
+            assert!(throttle.check_at(filler, now).is_ok());
+        }
+        assert!(
+            throttle
+                .check_at(ip(1), now + Duration::from_secs(600))
+                .is_ok()
+        );
+
+        // The longest anything is remembered is the lockout
+        assert!(
+            throttle
+                .check_at(ip(2), now + CONFIG.lockout_duration)
+                .is_ok()
+        );
+        let ips = throttle.ips.lock().unwrap();
+        assert_eq!(ips.len(), 2);
+        assert!(ips.contains_key(&ip(1)) && ips.contains_key(&ip(2)));
+    }
+}
@@ -0,0 +1,23 @@
+use rand::{Rng, distr::Alphanumeric};
+use sha2::{Digest, Sha256};
+
+/// Prefix making API tokens recognisable in configs and secret scanners.
+pub const API_TOKEN_PREFIX: &str = "kt_";
+
+pub fn generate_api_token() -> String {
+    let secret: String = rand::rng()
+        .sample_iter(&Alphanumeric)
+        .take(40)
+        .map(char::from)
+        .collect();
+    format!("{}{}", API_TOKEN_PREFIX, secret)
+}
+
+/// Tokens are long random strings, so a plain SHA-256 is enough to store
+/// them safely and, unlike bcrypt, cheap enough to check on every request.
+pub fn hash_api_token(token: &str) -> String {
+    Sha256::digest(token.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
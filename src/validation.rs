@@ -0,0 +1,310 @@
+use chrono::{Datelike, NaiveDate};
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::fmt::Display;
+
+use crate::error::AppError;
+use crate::identifiers::{RodneCislo, parse_ico};
+use crate::listing::Patch;
+use crate::models::*;
+use crate::privacy::MASK;
+
+/// Oldest proceedings year accepted; older records predate the digital cadastre.
+const MIN_RIZENI_ROK: i32 = 1900;
+
+/// BPEJ codes have five digits.
+const MAX_BPEJ: i32 = 99_999;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldError {
+    pub field: &'static str,
+    pub message: String,
+}
+
+/// Collects the field errors of one value; each rule adds at most one error.
+#[derive(Debug, Default)]
+pub struct Rules {
+    errors: Vec<FieldError>,
+}
+
+impl Rules {
+    pub fn fail(&mut self, field: &'static str, message: impl Into<String>) {
+        self.errors.push(FieldError {
+            field,
+            message: message.into(),
+        });
+    }
+
+    pub fn not_blank(&mut self, field: &'static str, value: &str) {
+        if value.trim().is_empty() {
+            self.fail(field, "must not be blank");
+        }
+    }
+
+    /// Optional text may be absent, but not present and empty; use `null` instead.
+    pub fn not_blank_opt(&mut self, field: &'static str, value: Option<&str>) {
+        if let Some(value) = value {
+            self.not_blank(field, value);
+        }
+    }
+
+    pub fn at_least<T: PartialOrd + Display>(&mut self, field: &'static str, value: T, min: T) {
+        if value < min {
+            self.fail(field, format!("must be at least {}", min));
+        }
+    }
+
+    pub fn range<T: PartialOrd + Display>(
+        &mut self,
+        field: &'static str,
+        value: T,
+        min: T,
+        max: T,
+    ) {
+        if value < min || value > max {
+            self.fail(field, format!("must be between {} and {}", min, max));
+        }
+    }
+
+    pub fn not_before(
+        &mut self,
+        field: &'static str,
+        value: NaiveDate,
+        other_field: &'static str,
+        other: NaiveDate,
+    ) {
+        if value < other {
+            self.fail(field, format!("must not be before {}", other_field));
+        }
+    }
+}
+
+/// Checks run before a value reaches the database, so clients get every
+/// problem at once as a 422 instead of the first constraint the insert trips.
+pub trait Validate {
+    fn rules(&self, rules: &mut Rules);
+
+    /// Rewrites fields into their canonical stored form. Values it cannot make
+    /// sense of are left alone for `rules` to report.
+    fn normalize(&mut self) {}
+
+    /// Does what `normalize` does for the fields a PATCH sets.
+    fn normalize_patch(_patch: &mut Patch)
+    where
+        Self: Sized,
+    {
+    }
+
+    fn validate(&self) -> Result<(), AppError> {
+        let mut rules = Rules::default();
+        self.rules(&mut rules);
+        if rules.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(AppError::Validation(rules.errors))
+        }
+    }
+
+    /// `normalize` followed by `validate`, for values about to be written.
+    fn validated(mut self) -> Result<Self, AppError>
+    where
+        Self: Sized,
+    {
+        self.normalize();
+        self.validate()?;
+        Ok(self)
+    }
+}
+
+/// Implements `Validate` with the same rules for each listed type, typically a
+/// model and its `New*` counterpart, which share field names.
+macro_rules! validate {
+    ($($ty:ty),+ => |$item:ident, $rules:ident| $body:block) => {
+        $(
+            impl Validate for $ty {
+                fn rules(&self, $rules: &mut Rules) {
+                    let $item = self;
+                    $body
+                }
+            }
+        )+
+    };
+    (
+        $($ty:ty),+ => |$item:ident, $rules:ident| $body:block
+        normalize |$nitem:ident| $nbody:block
+        patch |$patch:ident| $pbody:block
+    ) => {
+        $(
+            impl Validate for $ty {
+                fn rules(&self, $rules: &mut Rules) {
+                    let $item = self;
+                    $body
+                }
+
+                fn normalize(&mut self) {
+                    let $nitem = self;
+                    $nbody
+                }
+
+                fn normalize_patch($patch: &mut Patch) {
+                    $pbody
+                }
+            }
+        )+
+    };
+}
+
+validate!(NewUser => |u, r| {
+    r.not_blank("username", &u.username);
+    if u.password.is_empty() {
+        r.fail("password", "must not be empty");
+    }
+});
+
+validate!(UpdateUser => |u, r| {
+    if u.password.as_deref() == Some("") {
+        r.fail("password", "must not be empty");
+    }
+});
+
+validate!(NewApiToken => |t, r| {
+    r.not_blank("name", &t.name);
+});
+
+fn canonical_rodne_cislo(raw: &str) -> Option<String> {
+    RodneCislo::parse(raw).ok().map(|rc| rc.canonical())
+}
+
+fn canonical_ico(raw: &str) -> Option<String> {
+    parse_ico(raw).ok()
+}
+
+validate!(Majitel, NewMajitel => |m, r| {
+    r.not_blank("jmeno", &m.jmeno);
+    r.not_blank("prijmeni", &m.prijmeni);
+    r.not_blank_opt("titul", m.titul.as_deref());
+    r.not_blank_opt("bydliste", m.bydliste.as_deref());
+    // A masked read must not be written back over the real address
+    if m.bydliste.as_deref() == Some(MASK) {
+        r.fail("bydliste", "must not be the masked placeholder");
+    }
+    if let Some(Err(e)) = m.rodne_cislo.as_deref().map(RodneCislo::parse) {
+        r.fail("rodne_cislo", e);
+    }
+    if let Some(Err(e)) = m.ico.as_deref().map(parse_ico) {
+        r.fail("ico", e);
+    }
+} normalize |m| {
+    if let Some(rc) = m.rodne_cislo.as_deref().and_then(canonical_rodne_cislo) {
+        m.rodne_cislo = Some(rc);
+    }
+    if let Some(ico) = m.ico.as_deref().and_then(canonical_ico) {
+        m.ico = Some(ico);
+    }
+} patch |p| {
+    p.map_text("rodne_cislo", canonical_rodne_cislo);
+    p.map_text("ico", canonical_ico);
+});
+
+validate!(Kraj, NewKraj => |k, r| {
+    r.not_blank("nazev", &k.nazev);
+});
+
+validate!(Okres, NewOkres => |o, r| {
+    r.not_blank("nazev", &o.nazev);
+});
+
+validate!(Obec, NewObec => |o, r| {
+    r.not_blank("nazev", &o.nazev);
+});
+
+validate!(KatastralniUzemi, NewKatastralniUzemi => |k, r| {
+    r.not_blank("nazev", &k.nazev);
+});
+
+validate!(Bpej, NewBpej => |b, r| {
+    r.range("hodnota", b.hodnota, 0, MAX_BPEJ);
+});
+
+validate!(TypRizeni, NewTypRizeni => |t, r| {
+    r.not_blank("nazev", &t.nazev);
+    r.not_blank("zkratka", &t.zkratka);
+});
+
+validate!(TypOperace, NewTypOperace => |t, r| {
+    r.not_blank("popis", &t.popis);
+});
+
+validate!(TypUcastnika, NewTypUcastnika => |t, r| {
+    r.not_blank("nazev", &t.nazev);
+});
+
+validate!(UcastnikRizeni, NewUcastnikRizeni => |u, r| {
+    r.not_blank("jmeno", &u.jmeno);
+});
+
+validate!(ListVlastnictvi, NewListVlastnictvi => |lv, r| {
+    r.at_least("cislo_lv", lv.cislo_lv, 1);
+});
+
+validate!(ParcelaRow, NewParcelaRow => |p, r| {
+    r.at_least("parcelni_cislo", p.parcelni_cislo, 1);
+    r.at_least("cast_parcely", p.cast_parcely, 0);
+    if p.vymera_metru_ctverecnich <= Decimal::ZERO {
+        r.fail("vymera_metru_ctverecnich", "must be positive");
+    }
+    r.not_blank_opt("ulice", p.ulice.as_deref());
+    r.not_blank_opt("cislo_popisne", p.cislo_popisne.as_deref());
+});
+
+validate!(Rizeni, NewRizeni => |z, r| {
+    // Proceedings may be registered for the coming year around New Year
+    let max_rok = chrono::Local::now().year() + 1;
+    r.range("rok", z.rok, MIN_RIZENI_ROK, max_rok);
+    r.at_least("cislo_rizeni", z.cislo_rizeni, 1);
+    r.not_blank("predmet", &z.predmet);
+});
+
+validate!(Vlastnictvi, NewVlastnictvi => |v, r| {
+    r.range("podil_setin", v.podil_setin, 1, 100);
+});
+
+validate!(BremenoParcelaParcela, NewBremenoParcelaParcela => |b, r| {
+    if b.parcela_povinna_id == b.parcela_id {
+        r.fail("parcela_povinna_id", "must differ from parcela_id");
+    }
+    r.not_blank("popis", &b.popis);
+    r.not_before(
+        "datum_pravnich_ucinku",
+        b.datum_pravnich_ucinku,
+        "datum_zrizeni",
+        b.datum_zrizeni,
+    );
+});
+
+validate!(BremenoParcelaMajitel, NewBremenoParcelaMajitel => |b, r| {
+    r.not_blank("popis", &b.popis);
+    r.not_before(
+        "datum_pravnich_ucinku",
+        b.datum_pravnich_ucinku,
+        "datum_zrizeni",
+        b.datum_zrizeni,
+    );
+});
+
+validate!(NabyvaciTitul, NewNabyvaciTitul => |n, r| {
+    if !DRUHY_TITULU.contains(&n.druh.as_str()) {
+        r.fail("druh", format!("must be one of {}", DRUHY_TITULU.join(", ")));
+    }
+    r.not_blank("popis", &n.popis);
+    // A deed takes effect in the register only after it was drawn up
+    r.not_before(
+        "datum_pravnich_ucinku",
+        n.datum_pravnich_ucinku,
+        "datum_vyhotoveni",
+        n.datum_vyhotoveni,
+    );
+});
+
+// Pure link tables: everything they hold is checked by their foreign keys
+validate!(Plomba, NewPlomba, RizeniOperaceRow, NewRizeniOperaceRow, Ucast, NewUcast => |_item, _r| {});
@@ -0,0 +1,142 @@
+use axum::{
+    extract::FromRequestParts,
+    http::{
+        HeaderMap, HeaderValue,
+        header::{ETAG, IF_MATCH},
+        request::Parts,
+    },
+};
+
+use crate::error::AppError;
+
+/// The version of a row is its `xmin`, the id of the transaction that last
+/// wrote it, so every committed update changes it without a dedicated column.
+pub const VERSION_COLUMN: &str = "xmin::text";
+
+/// `ETag` header carrying a row version as a strong entity tag.
+pub fn etag_header(version: &str) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    if let Ok(value) = HeaderValue::from_str(&format!("\"{}\"", version)) {
+        headers.insert(ETAG, value);
+    }
+    headers
+}
+
+/// The `If-Match` precondition of a write. Writes without it behave as before;
+/// with it, the row must still be at one of the listed versions.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum IfMatch {
+    #[default]
+    Absent,
+    /// `*`: any current version
+    Any,
+    /// Strong tags without their quotes; weak tags never match and are dropped
+    Tags(Vec<String>),
+}
+
+impl IfMatch {
+    pub fn is_absent(&self) -> bool {
+        *self == IfMatch::Absent
+    }
+
+    pub fn matches(&self, version: &str) -> bool {
+        match self {
+            IfMatch::Absent | IfMatch::Any => true,
+            IfMatch::Tags(tags) => tags.iter().any(|t| t == version),
+        }
+    }
+
+    /// Parses the comma separated values of one or more `If-Match` headers.
+    pub fn parse<'a>(values: impl IntoIterator<Item = &'a str>) -> Result<Self, String> {
+        let mut tags = Vec::new();
+        let mut any = false;
+        let mut present = false;
+        for tag in values.into_iter().flat_map(|v| v.split(',')) {
+            let tag = tag.trim();
+            present = true;
+            if tag == "*" {
+                any = true;
+            } else if let Some(weak) = tag.strip_prefix("W/") {
+                if !is_quoted(weak) {
+                    return Err(format!("Invalid entity tag '{}'", tag));
+                }
+            } else if is_quoted(tag) {
+                tags.push(tag[1..tag.len() - 1].to_string());
+            } else {
+                return Err(format!("Invalid entity tag '{}'", tag));
+            }
+        }
+        Ok(match (present, any) {
+            (false, _) => IfMatch::Absent,
+            (true, true) => IfMatch::Any,
+            (true, false) => IfMatch::Tags(tags),
+        })
+    }
+}
+
+fn is_quoted(tag: &str) -> bool {
+    tag.len() >= 2 && tag.starts_with('"') && tag.ends_with('"')
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for IfMatch {
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let values = parts
+            .headers
+            .get_all(IF_MATCH)
+            .iter()
+            .map(|v| v.to_str())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| AppError::BadRequest("Invalid If-Match header".to_string()))?;
+        IfMatch::parse(values).map_err(AppError::BadRequest)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(tags: &[&str]) -> IfMatch {
+        IfMatch::Tags(tags.iter().map(|t| t.to_string()).collect())
+    }
+
+    #[test]
+    fn parses_if_match_headers() {
+        assert_eq!(IfMatch::parse([]), Ok(IfMatch::Absent));
+        assert_eq!(IfMatch::parse(["*"]), Ok(IfMatch::Any));
+        assert_eq!(IfMatch::parse(["\"741\""]), Ok(tags(&["741"])));
+        assert_eq!(
+            IfMatch::parse(["\"741\", \"742\"", "\"900\""]),
+            Ok(tags(&["741", "742", "900"]))
+        );
+        assert_eq!(IfMatch::parse(["\"741\", *"]), Ok(IfMatch::Any));
+    }
+
+    #[test]
+    fn drops_weak_tags() {
+        let if_match = IfMatch::parse(["W/\"741\""]).unwrap();
+        assert_eq!(if_match, tags(&[]));
+        assert!(!if_match.matches("741"));
+    }
+
+    #[test]
+    fn rejects_unquoted_tags() {
+        for header in ["741", "\"741", "W/741", ""] {
+            assert!(IfMatch::parse([header]).is_err(), "{:?}", header);
+        }
+    }
+
+    #[test]
+    fn matches_versions() {
+        assert!(IfMatch::Absent.matches("1"));
+        assert!(IfMatch::Any.matches("1"));
+        assert!(tags(&["1", "2"]).matches("2"));
+        assert!(!tags(&["1", "2"]).matches("3"));
+    }
+
+    #[test]
+    fn quotes_the_etag() {
+        assert_eq!(etag_header("741")[ETAG], "\"741\"");
+    }
+}
@@ -0,0 +1,639 @@
+//! Printable extracts of a list vlastnictví ("výpis z katastru nemovitostí"),
+//! as HTML or PDF, laid out as one table per part of the LV.
+
+use chrono::{DateTime, Local, NaiveDate};
+use std::fmt::Write;
+
+use crate::models::*;
+use crate::pdf::{A4_HEIGHT, A4_WIDTH, Font, PdfDocument, text_width, wrap};
+
+/// Shown in place of a part that has no entries, as on official extracts.
+const NO_ENTRIES: &str = "Bez zápisu";
+
+/// The data of one LV, already masked for the caller.
+#[derive(Debug, Clone)]
+pub struct LvDocument {
+    pub katastralni_uzemi: String,
+    pub cislo_lv: i32,
+    pub vyhotoveno: DateTime<Local>,
+    /// The date the LV was reconstructed for, when not the current state
+    pub as_of: Option<NaiveDate>,
+    pub part_a: Vec<MajitelPartA>,
+    pub part_b: Vec<Parcela>,
+    pub part_b_parcela: Vec<ParcelaB>,
+    pub part_b_majitel: Vec<MajitelB>,
+    pub part_c: Vec<PartC>,
+    pub part_d: Vec<PartD>,
+    pub part_e: Vec<PartE>,
+    pub part_f: Vec<PartF>,
+}
+
+/// One part of the LV as a table; column widths are relative.
+struct Section {
+    title: &'static str,
+    columns: &'static [(&'static str, f32)],
+    rows: Vec<Vec<String>>,
+}
+
+/// A parcel number as printed, e.g. `st. 120/3`.
+fn parcela(je_stavebni: bool, parcelni_cislo: i64, cast_parcely: i64) -> String {
+    let prefix = if je_stavebni { "st. " } else { "" };
+    if cast_parcely == 0 {
+        format!("{}{}", prefix, parcelni_cislo)
+    } else {
+        format!("{}{}/{}", prefix, parcelni_cislo, cast_parcely)
+    }
+}
+
+fn datum(date: NaiveDate) -> String {
+    date.format("%-d. %-m. %Y").to_string()
+}
+
+fn osoba(titul: Option<&str>, jmeno: &str, prijmeni: &str) -> String {
+    match titul {
+        Some(titul) if !titul.is_empty() => format!("{} {} {}", titul, jmeno, prijmeni),
+        _ => format!("{} {}", jmeno, prijmeni),
+    }
+}
+
+fn opt(value: &Option<String>) -> String {
+    value.clone().unwrap_or_default()
+}
+
+impl LvDocument {
+    pub fn title(&self) -> String {
+        format!(
+            "Výpis z katastru nemovitostí – LV {}, k. ú. {}",
+            self.cislo_lv, self.katastralni_uzemi
+        )
+    }
+
+    fn timestamp(&self) -> String {
+        self.vyhotoveno.format("%-d. %-m. %Y %H:%M:%S").to_string()
+    }
+
+    /// Which state of the LV the extract shows.
+    fn stav(&self) -> String {
+        match self.as_of {
+            Some(as_of) => format!("Stav ke dni {}", datum(as_of)),
+            None => "Aktuální stav".to_string(),
+        }
+    }
+
+    fn sections(&self) -> Vec<Section> {
+        vec![
+            Section {
+                title: "A – Vlastník, jiný oprávněný",
+                columns: &[("Jméno", 2.0), ("Bydliště", 3.0), ("Podíl", 1.0)],
+                rows: self
+                    .part_a
+                    .iter()
+                    .map(|m| {
+                        vec![
+                            osoba(None, &m.jmeno, &m.prijmeni),
+                            m.bydliste.clone(),
+                            format!("{}/100", m.podil_setin),
+                        ]
+                    })
+                    .collect(),
+            },
+            Section {
+                title: "B – Nemovitosti",
+                columns: &[
+                    ("Parcela", 1.0),
+                    ("Ulice", 2.0),
+                    ("Číslo popisné", 1.0),
+                    ("Katastrální území", 2.0),
+                ],
+                rows: self
+                    .part_b
+                    .iter()
+                    .map(|p| {
+                        vec![
+                            parcela(p.je_stavebni, p.parcelni_cislo, 0),
+                            opt(&p.ulice),
+                            opt(&p.cislo_popisne),
+                            p.nazev_ku.clone(),
+                        ]
+                    })
+                    .collect(),
+            },
+            Section {
+                title: "B1 – Věcná práva sloužící ve prospěch nemovitostí v části B",
+                columns: &[
+                    ("Popis", 3.0),
+                    ("Oprávněná parcela", 1.2),
+                    ("Povinný", 2.0),
+                    ("Zřízeno", 1.0),
+                    ("Právní účinky", 1.0),
+                ],
+                rows: self
+                    .part_b_parcela
+                    .iter()
+                    .map(|b| {
+                        vec![
+                            b.popis.clone(),
+                            parcela(
+                                b.je_stavebni_opravnena,
+                                b.parcelni_cislo_opravnena,
+                                b.cast_parcely_opravnena,
+                            ),
+                            format!(
+                                "parcela {}",
+                                parcela(
+                                    b.je_stavebni_povinna,
+                                    b.parcelni_cislo_povinna,
+                                    b.cast_parcely_povinna
+                                )
+                            ),
+                            datum(b.datum_zrizeni),
+                            datum(b.datum_pravnich_ucinku),
+                        ]
+                    })
+                    .chain(self.part_b_majitel.iter().map(|b| {
+                        let mut povinny = osoba(
+                            b.titul_povinny.as_deref(),
+                            &b.jmeno_povinny,
+                            &b.prijmeni_povinny,
+                        );
+                        if let Some(rodne_cislo) = &b.rodne_cislo_povinny {
+                            let _ = write!(povinny, ", RČ {}", rodne_cislo);
+                        }
+                        if let Some(ico) = &b.ico_povinny {
+                            let _ = write!(povinny, ", IČO {}", ico);
+                        }
+                        vec![
+                            b.popis.clone(),
+                            parcela(
+                                b.je_stavebni_opravnena,
+                                b.parcelni_cislo_opravnena,
+                                b.cast_parcely_opravnena,
+                            ),
+                            povinny,
+                            datum(b.datum_zrizeni),
+                            datum(b.datum_pravnich_ucinku),
+                        ]
+                    }))
+                    .collect(),
+            },
+            Section {
+                title: "C – Věcná práva zatěžující nemovitosti v části B",
+                columns: &[
+                    ("Popis", 3.0),
+                    ("Oprávněná parcela", 1.2),
+                    ("Povinná parcela", 1.2),
+                    ("Zřízeno", 1.0),
+                    ("Právní účinky", 1.0),
+                ],
+                rows: self
+                    .part_c
+                    .iter()
+                    .map(|c| {
+                        vec![
+                            c.popis.clone(),
+                            parcela(
+                                c.je_stavebni_opravnena,
+                                c.parcelni_cislo_opravnena,
+                                c.cast_parcely_opravnena,
+                            ),
+                            parcela(
+                                c.je_stavebni_povinna,
+                                c.parcelni_cislo_povinna,
+                                c.cast_parcely_povinna,
+                            ),
+                            datum(c.datum_zrizeni),
+                            datum(c.datum_pravnich_ucinku),
+                        ]
+                    })
+                    .collect(),
+            },
+            Section {
+                title: "D – Poznámky a další obdobné údaje",
+                columns: &[
+                    ("Parcela", 1.0),
+                    ("Katastrální území", 2.0),
+                    ("Řízení", 1.5),
+                ],
+                rows: self
+                    .part_d
+                    .iter()
+                    .map(|d| {
+                        vec![
+                            parcela(d.je_stavebni, d.parcelni_cislo, d.cast_parcely),
+                            d.nazev_katastralniho_uzemi.clone(),
+                            format!(
+                                "{}-{}/{}",
+                                d.typ_rizeni_zkratka, d.cislo_rizeni, d.rok_rizeni
+                            ),
+                        ]
+                    })
+                    .collect(),
+            },
+            Section {
+                title: "E – Nabývací tituly a jiné podklady zápisu",
+                columns: &[
+                    ("Listina", 3.0),
+                    ("Vyhotoveno", 1.0),
+                    ("Právní účinky", 1.0),
+                    ("Řízení", 1.2),
+                    ("Pro", 1.8),
+                ],
+                rows: self
+                    .part_e
+                    .iter()
+                    .map(|e| {
+                        vec![
+                            format!("{}: {}", e.druh, e.popis),
+                            datum(e.datum_vyhotoveni),
+                            datum(e.datum_pravnich_ucinku),
+                            format!(
+                                "{}-{}/{}",
+                                e.typ_rizeni_zkratka, e.cislo_rizeni, e.rok_rizeni
+                            ),
+                            osoba(None, &e.jmeno, &e.prijmeni),
+                        ]
+                    })
+                    .collect(),
+            },
+            Section {
+                title: "F – Vztah bonitovaných půdně ekologických jednotek (BPEJ) k parcelám",
+                columns: &[("Parcela", 1.0), ("Hodnota BPEJ", 1.0)],
+                rows: self
+                    .part_f
+                    .iter()
+                    .map(|f| {
+                        vec![
+                            parcela(f.je_stavebni, f.parcelni_cislo, f.cast_parcely),
+                            f.hodnota.map(|h| h.to_string()).unwrap_or_default(),
+                        ]
+                    })
+                    .collect(),
+            },
+        ]
+    }
+
+    /// A standalone HTML page; browsers paginate it when printing, repeating
+    /// table headers on every page.
+    pub fn to_html(&self) -> String {
+        let mut html = String::new();
+        let _ = write!(
+            html,
+            r#"<!DOCTYPE html>
+<html lang="cs">
+<head>
+<meta charset="utf-8">
+<title>{title}</title>
+<style>
+@page {{ size: A4; margin: 15mm; }}
+body {{ font-family: Helvetica, Arial, sans-serif; font-size: 10pt; margin: 0 auto; max-width: 180mm; }}
+header {{ border-bottom: 1.5pt solid #000; margin-bottom: 1em; }}
+header h1 {{ font-size: 14pt; margin: 0 0 0.3em; }}
+header p {{ margin: 0.2em 0; }}
+section {{ margin-bottom: 1.2em; }}
+h2 {{ font-size: 11pt; margin: 0 0 0.4em; break-after: avoid; page-break-after: avoid; }}
+table {{ width: 100%; border-collapse: collapse; }}
+thead {{ display: table-header-group; }}
+th, td {{ border: 0.5pt solid #888; padding: 2pt 4pt; text-align: left; vertical-align: top; }}
+th {{ background: #e6e6e6; font-size: 8.5pt; }}
+tr {{ break-inside: avoid; page-break-inside: avoid; }}
+p.empty {{ font-style: italic; margin: 0; }}
+footer {{ border-top: 0.5pt solid #000; font-size: 8pt; margin-top: 2em; padding-top: 0.3em; }}
+</style>
+</head>
+<body>
+<header>
+<h1>Výpis z katastru nemovitostí</h1>
+<p>Katastrální území: <strong>{ku}</strong></p>
+<p>List vlastnictví: <strong>{lv}</strong></p>
+<p>Vyhotoveno: {timestamp}</p>
+<p>{stav}</p>
+</header>
+"#,
+            title = escape_html(&self.title()),
+            ku = escape_html(&self.katastralni_uzemi),
+            lv = self.cislo_lv,
+            timestamp = escape_html(&self.timestamp()),
+            stav = escape_html(&self.stav()),
+        );
+
+        for section in self.sections() {
+            let _ = writeln!(html, "<section>\n<h2>{}</h2>", escape_html(section.title));
+            if section.rows.is_empty() {
+                let _ = writeln!(html, "<p class=\"empty\">{}</p>", NO_ENTRIES);
+            } else {
+                html.push_str("<table>\n<thead><tr>");
+                for (column, _) in section.columns {
+                    let _ = write!(html, "<th>{}</th>", escape_html(column));
+                }
+                html.push_str("</tr></thead>\n<tbody>\n");
+                for row in &section.rows {
+                    html.push_str("<tr>");
+                    for cell in row {
+                        let _ = write!(html, "<td>{}</td>", escape_html(cell));
+                    }
+                    html.push_str("</tr>\n");
+                }
+                html.push_str("</tbody>\n</table>\n");
+            }
+            html.push_str("</section>\n");
+        }
+
+        let _ = write!(
+            html,
+            "<footer>LV {} – k. ú. {} – vyhotoveno {}</footer>\n</body>\n</html>\n",
+            self.cislo_lv,
+            escape_html(&self.katastralni_uzemi),
+            escape_html(&self.timestamp())
+        );
+        html
+    }
+
+    /// An A4 PDF with the header on every page and `Strana x z y` in the footer.
+    pub fn to_pdf(&self) -> Vec<u8> {
+        let mut layout = PdfLayout::new(self);
+        for section in self.sections() {
+            layout.section(&section);
+        }
+        layout.finish()
+    }
+}
+
+pub fn escape_html(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+const MARGIN: f32 = 40.0;
+const HEADER_HEIGHT: f32 = 62.0;
+const FOOTER_HEIGHT: f32 = 30.0;
+const TITLE_SIZE: f32 = 11.0;
+const CELL_SIZE: f32 = 8.0;
+const LEADING: f32 = 10.0;
+const CELL_PADDING: f32 = 3.0;
+
+/// Flows sections down the pages, starting a new page whenever the next row
+/// does not fit and repeating the section title and table header there.
+struct PdfLayout<'a> {
+    lv: &'a LvDocument,
+    pdf: PdfDocument,
+    page: usize,
+    /// Top of the free space on the current page
+    y: f32,
+}
+
+impl<'a> PdfLayout<'a> {
+    fn new(lv: &'a LvDocument) -> Self {
+        let mut layout = PdfLayout {
+            lv,
+            pdf: PdfDocument::new(lv.title()),
+            page: 0,
+            y: 0.0,
+        };
+        layout.new_page();
+        layout
+    }
+
+    fn width() -> f32 {
+        A4_WIDTH - 2.0 * MARGIN
+    }
+
+    fn bottom() -> f32 {
+        MARGIN + FOOTER_HEIGHT
+    }
+
+    fn new_page(&mut self) {
+        self.page = self.pdf.add_page();
+        let top = A4_HEIGHT - MARGIN;
+        let (page, lv) = (self.page, self.lv);
+        self.pdf.text(
+            page,
+            MARGIN,
+            top - 14.0,
+            Font::Bold,
+            14.0,
+            "Výpis z katastru nemovitostí",
+        );
+        self.pdf.text(
+            page,
+            MARGIN,
+            top - 32.0,
+            Font::Regular,
+            9.0,
+            &format!(
+                "Katastrální území: {}     List vlastnictví: {}",
+                lv.katastralni_uzemi, lv.cislo_lv
+            ),
+        );
+        self.pdf.text(
+            page,
+            MARGIN,
+            top - 44.0,
+            Font::Regular,
+            9.0,
+            &format!("Vyhotoveno: {}     {}", lv.timestamp(), lv.stav()),
+        );
+        let rule = top - HEADER_HEIGHT + 10.0;
+        self.pdf
+            .line(page, (MARGIN, rule), (A4_WIDTH - MARGIN, rule), 1.2);
+        self.y = top - HEADER_HEIGHT;
+    }
+
+    /// Moves to a new page unless `height` still fits on this one.
+    fn ensure(&mut self, height: f32) -> bool {
+        if self.y - height < Self::bottom() {
+            self.new_page();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn title(&mut self, title: &str) {
+        self.pdf.text(
+            self.page,
+            MARGIN,
+            self.y - TITLE_SIZE,
+            Font::Bold,
+            TITLE_SIZE,
+            title,
+        );
+        self.y -= TITLE_SIZE + 6.0;
+    }
+
+    fn section(&mut self, section: &Section) {
+        let widths = column_widths(section.columns);
+        let header: Vec<String> = section.columns.iter().map(|(c, _)| c.to_string()).collect();
+        let header_height = row_height(&header, &widths, Font::Bold);
+
+        // Keep the title together with the table header and its first row
+        let first_row = section
+            .rows
+            .first()
+            .map_or(LEADING, |r| row_height(r, &widths, Font::Regular));
+        self.ensure(TITLE_SIZE + 6.0 + header_height + first_row);
+        self.title(section.title);
+
+        if section.rows.is_empty() {
+            self.pdf.text(
+                self.page,
+                MARGIN,
+                self.y - CELL_SIZE,
+                Font::Regular,
+                CELL_SIZE,
+                NO_ENTRIES,
+            );
+            self.y -= LEADING + 12.0;
+            return;
+        }
+
+        self.row(&header, &widths, Font::Bold);
+        for row in &section.rows {
+            if self.ensure(row_height(row, &widths, Font::Regular)) {
+                self.title(&format!("{} (pokračování)", section.title));
+                self.row(&header, &widths, Font::Bold);
+            }
+            self.row(row, &widths, Font::Regular);
+        }
+        self.y -= 12.0;
+    }
+
+    fn row(&mut self, cells: &[String], widths: &[f32], font: Font) {
+        let height = row_height(cells, widths, font);
+        let top = self.y;
+        if font == Font::Bold {
+            self.pdf
+                .fill_rect(self.page, MARGIN, top - height, Self::width(), height, 0.9);
+        }
+        let mut x = MARGIN;
+        for (cell, width) in cells.iter().zip(widths) {
+            let lines = wrap(cell, font, CELL_SIZE, width - 2.0 * CELL_PADDING);
+            for (i, line) in lines.iter().enumerate() {
+                self.pdf.text(
+                    self.page,
+                    x + CELL_PADDING,
+                    top - CELL_PADDING - CELL_SIZE - i as f32 * LEADING + 1.5,
+                    font,
+                    CELL_SIZE,
+                    line,
+                );
+            }
+            x += width;
+        }
+        self.pdf.line(
+            self.page,
+            (MARGIN, top - height),
+            (A4_WIDTH - MARGIN, top - height),
+            0.3,
+        );
+        self.y -= height;
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        let count = self.pdf.page_count();
+        let footer = format!(
+            "LV {} – k. ú. {} – vyhotoveno {}",
+            self.lv.cislo_lv,
+            self.lv.katastralni_uzemi,
+            self.lv.timestamp()
+        );
+        for page in 0..count {
+            let y = MARGIN + FOOTER_HEIGHT - 8.0;
+            self.pdf
+                .line(page, (MARGIN, y), (A4_WIDTH - MARGIN, y), 0.5);
+            self.pdf
+                .text(page, MARGIN, y - 12.0, Font::Regular, 7.5, &footer);
+            let number = format!("Strana {} z {}", page + 1, count);
+            let x = A4_WIDTH - MARGIN - text_width(&number, Font::Regular, 7.5);
+            self.pdf
+                .text(page, x, y - 12.0, Font::Regular, 7.5, &number);
+        }
+        self.pdf.to_bytes()
+    }
+}
+
+fn column_widths(columns: &[(&str, f32)]) -> Vec<f32> {
+    let total: f32 = columns.iter().map(|(_, w)| w).sum();
+    columns
+        .iter()
+        .map(|(_, w)| w / total * PdfLayout::width())
+        .collect()
+}
+
+fn row_height(cells: &[String], widths: &[f32], font: Font) -> f32 {
+    let lines = cells
+        .iter()
+        .zip(widths)
+        .map(|(cell, width)| wrap(cell, font, CELL_SIZE, width - 2.0 * CELL_PADDING).len())
+        .max()
+        .unwrap_or(1);
+    lines as f32 * LEADING + 2.0 * CELL_PADDING - 2.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pdf::tests::assert_xref_consistent;
+
+    fn lv(owners: usize) -> LvDocument {
+        LvDocument {
+            katastralni_uzemi: "Malá Strana".to_string(),
+            cislo_lv: 5,
+            vyhotoveno: Local::now(),
+            as_of: None,
+            part_a: (0..owners)
+                .map(|i| MajitelPartA {
+                    jmeno: "Jiří <b>".to_string(),
+                    prijmeni: format!("Dvořák {}", i),
+                    bydliste: "Nerudova 12, Praha & okolí".to_string(),
+                    podil_setin: 1,
+                })
+                .collect(),
+            part_b: Vec::new(),
+            part_b_parcela: Vec::new(),
+            part_b_majitel: Vec::new(),
+            part_c: Vec::new(),
+            part_d: Vec::new(),
+            part_e: Vec::new(),
+            part_f: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn escapes_html() {
+        assert_eq!(
+            escape_html(r#"<a href="x">Tom & Jerry's</a>"#),
+            "&lt;a href=&quot;x&quot;&gt;Tom &amp; Jerry&#39;s&lt;/a&gt;"
+        );
+        assert_eq!(escape_html("Žluťoučký kůň"), "Žluťoučký kůň");
+    }
+
+    #[test]
+    fn escapes_data_in_the_html_extract() {
+        let html = lv(1).to_html();
+        assert!(html.contains("Jiří &lt;b&gt;"));
+        assert!(html.contains("Praha &amp; okolí"));
+        assert!(!html.contains("<b>"));
+    }
+
+    #[test]
+    fn renders_a_valid_pdf_over_several_pages() {
+        let pdf = lv(200).to_pdf();
+        assert!(pdf.starts_with(b"%PDF-"));
+        let text = String::from_utf8_lossy(&pdf);
+        let count = text.split("/Count ").nth(1).unwrap();
+        let pages: usize = count[..count.find(' ').unwrap()].parse().unwrap();
+        assert!(pages > 1);
+        assert_eq!(text.matches("/Type /Page ").count(), pages);
+        assert_xref_consistent(&pdf);
+    }
+}
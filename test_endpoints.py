@@ -2,9 +2,12 @@ import subprocess
 import json
 import time
 import random
+import os
+import tempfile
 
 BASE_URL = "http://localhost:3000"
 SUFFIX = f"_TEST_{random.randint(1000, 9999)}"
+COOKIE_JAR = os.path.join(tempfile.mkdtemp(), "cookies")
 
 def run_curl(method, endpoint, data=None, params=None):
     url = f"{BASE_URL}{endpoint}"
@@ -12,7 +15,7 @@ def run_curl(method, endpoint, data=None, params=None):
         query_string = "&".join([f"{k}={v}" for k, v in params.items()])
         url += f"?{query_string}"
 
-    cmd = ["curl", "-s", "-X", method, url]
+    cmd = ["curl", "-s", "-b", COOKIE_JAR, "-X", method, url]
     
     if data:
         cmd.extend(["-H", "Content-Type: application/json"])
@@ -35,23 +38,40 @@ def run_curl(method, endpoint, data=None, params=None):
         print(f"Raw output: {stdout_str}")
         return stdout_str
 
-def get_id(items, key, value, id_field="id"):
-    if not isinstance(items, list):
-        print(f"Expected list, got {type(items)}")
+def http_status(method, endpoint, data):
+    cmd = ["curl", "-s", "-b", COOKIE_JAR, "-o", "/dev/null", "-w", "%{http_code}", "-X", method, f"{BASE_URL}{endpoint}",
+           "-H", "Content-Type: application/json", "-d", json.dumps(data)]
+    print(f"CMD: {' '.join(cmd)}")
+    return int(subprocess.run(cmd, capture_output=True).stdout or 0)
+
+def valid_rodne_cislo():
+    # 10 digits divisible by 11; a remainder of 10 takes check digit 0
+    head = f"{random.randint(60, 99):02}{random.randint(1, 12):02}{random.randint(1, 28):02}{random.randint(0, 999):03}"
+    return f"{head[:6]}/{head[6:]}{int(head) % 11 % 10}"
+
+def created_id(item, id_field="id"):
+    # Creates answer 201 with the stored row
+    if not isinstance(item, dict):
+        print(f"Expected created object, got {type(item)}")
         return None
-    for item in items:
-        if item.get(key) == value:
-            return item.get(id_field)
-    return None
+    return item.get(id_field)
+
+def login():
+    credentials = {
+        "username": os.environ.get("KATASTR_USER", "admin"),
+        "password": os.environ.get("KATASTR_PASSWORD", "heslo"),
+    }
+    subprocess.run(["curl", "-s", "-c", COOKIE_JAR, "-H", "Content-Type: application/json",
+                    "-d", json.dumps(credentials), f"{BASE_URL}/auth"], capture_output=True)
 
 ids = {}
+login()
 
 # 1. Kraj
 print("\n--- Testing Kraj ---")
 kraj_name = f"Kraj{SUFFIX}"
-run_curl("POST", "/kraj", {"nazev": kraj_name})
-krajs = run_curl("GET", "/kraj")
-ids["kraj"] = get_id(krajs, "nazev", kraj_name)
+created = run_curl("POST", "/kraj", {"nazev": kraj_name})
+ids["kraj"] = created_id(created)
 print(f"Created Kraj ID: {ids['kraj']}")
 if ids["kraj"]:
     run_curl("PUT", "/kraj", {"id": ids["kraj"], "nazev": f"{kraj_name}_UPD"})
@@ -60,108 +80,96 @@ if ids["kraj"]:
 print("\n--- Testing Okres ---")
 if ids.get("kraj"):
     okres_name = f"Okres{SUFFIX}"
-    run_curl("POST", "/okres", {"kraj_id": ids["kraj"], "nazev": okres_name})
-    okresy = run_curl("GET", "/okres")
-    ids["okres"] = get_id(okresy, "nazev", okres_name)
+    created = run_curl("POST", "/okres", {"kraj_id": ids["kraj"], "nazev": okres_name})
+    ids["okres"] = created_id(created)
     print(f"Created Okres ID: {ids['okres']}")
 
 # 3. Obec
 print("\n--- Testing Obec ---")
 if ids.get("okres"):
     obec_name = f"Obec{SUFFIX}"
-    run_curl("POST", "/obec", {"okres_id": ids["okres"], "nazev": obec_name})
-    obce = run_curl("GET", "/obec")
-    ids["obec"] = get_id(obce, "nazev", obec_name)
+    created = run_curl("POST", "/obec", {"okres_id": ids["okres"], "nazev": obec_name})
+    ids["obec"] = created_id(created)
     print(f"Created Obec ID: {ids['obec']}")
 
 # 4. KatastralniUzemi
 print("\n--- Testing KatastralniUzemi ---")
 if ids.get("obec"):
     ku_name = f"KU{SUFFIX}"
-    run_curl("POST", "/katastralni_uzemi", {"obec_id": ids["obec"], "nazev": ku_name})
-    kus = run_curl("GET", "/katastralni_uzemi")
-    ids["ku"] = get_id(kus, "nazev", ku_name)
+    created = run_curl("POST", "/katastralni_uzemi", {"obec_id": ids["obec"], "nazev": ku_name})
+    ids["ku"] = created_id(created)
     print(f"Created KU ID: {ids['ku']}")
 
 # 5. Bpej
 print("\n--- Testing Bpej ---")
 bpej_val = random.randint(10000, 99999)
-run_curl("POST", "/bpej", {"hodnota": bpej_val})
-bpejs = run_curl("GET", "/bpej")
-ids["bpej"] = get_id(bpejs, "hodnota", bpej_val)
+created = run_curl("POST", "/bpej", {"hodnota": bpej_val})
+ids["bpej"] = created_id(created)
 print(f"Created Bpej ID: {ids['bpej']}")
 
 # 6. TypRizeni
 print("\n--- Testing TypRizeni ---")
 tr_name = f"TR{SUFFIX}"
-run_curl("POST", "/typ_rizeni", {"nazev": tr_name, "zkratka": "TR"})
-trs = run_curl("GET", "/typ_rizeni")
-ids["typ_rizeni"] = get_id(trs, "nazev", tr_name)
+created = run_curl("POST", "/typ_rizeni", {"nazev": tr_name, "zkratka": "TR"})
+ids["typ_rizeni"] = created_id(created)
 print(f"Created TypRizeni ID: {ids['typ_rizeni']}")
 
 # 7. TypOperace
 print("\n--- Testing TypOperace ---")
 to_name = f"TO{SUFFIX}"
-run_curl("POST", "/typ_operace", {"popis": to_name})
-tos = run_curl("GET", "/typ_operace")
-ids["typ_operace"] = get_id(tos, "popis", to_name)
+created = run_curl("POST", "/typ_operace", {"popis": to_name})
+ids["typ_operace"] = created_id(created)
 print(f"Created TypOperace ID: {ids['typ_operace']}")
 
 # 8. TypUcastnika
 print("\n--- Testing TypUcastnika ---")
 tu_name = f"TU{SUFFIX}"
-run_curl("POST", "/typ_ucastnika", {"nazev": tu_name})
-tus = run_curl("GET", "/typ_ucastnika")
-ids["typ_ucastnika"] = get_id(tus, "nazev", tu_name)
+created = run_curl("POST", "/typ_ucastnika", {"nazev": tu_name})
+ids["typ_ucastnika"] = created_id(created)
 print(f"Created TypUcastnika ID: {ids['typ_ucastnika']}")
 
 # 9. UcastnikRizeni
 print("\n--- Testing UcastnikRizeni ---")
 ur_name = f"UR{SUFFIX}"
-run_curl("POST", "/ucastnik_rizeni", {"jmeno": ur_name})
-urs = run_curl("GET", "/ucastnik_rizeni")
-ids["ucastnik_rizeni"] = get_id(urs, "jmeno", ur_name)
+created = run_curl("POST", "/ucastnik_rizeni", {"jmeno": ur_name})
+ids["ucastnik_rizeni"] = created_id(created)
 print(f"Created UcastnikRizeni ID: {ids['ucastnik_rizeni']}")
 
 # 10. Majitel
 print("\n--- Testing Majitel ---")
 majitel_jmeno = f"Jan{SUFFIX}"
-rc_suffix = random.randint(1000, 9999)
-run_curl("POST", "/majitel", {
+majitel = {
     "jmeno": majitel_jmeno,
     "prijmeni": "Novak",
     "titul": None,
     "bydliste": "Praha",
-    "rodne_cislo": f"123456/{rc_suffix}",
+    "rodne_cislo": valid_rodne_cislo(),
     "ico": None
-})
-majitele = run_curl("GET", "/majitel")
-ids["majitel"] = get_id(majitele, "jmeno", majitel_jmeno)
+}
+# Month 34 - 20 = 14 and day 56 do not exist, and the check digit is wrong
+status = http_status("POST", "/majitel", {**majitel, "rodne_cislo": "123456/7890"})
+assert status == 422, f"Malformed rodne_cislo answered {status}, expected 422"
+created = run_curl("POST", "/majitel", majitel)
+ids["majitel"] = created_id(created)
 print(f"Created Majitel ID: {ids['majitel']}")
 
 # 11. ListVlastnictvi
 print("\n--- Testing ListVlastnictvi ---")
 if ids.get("ku"):
     lv_cislo = random.randint(100, 999)
-    run_curl("POST", "/list_vlastnictvi", {
+    created = run_curl("POST", "/list_vlastnictvi", {
         "katastralni_uzemi_id": ids["ku"],
         "cislo_lv": lv_cislo,
         "vlastnicky_hash": None
     })
-    lvs = run_curl("GET", "/list_vlastnictvi")
-    # Need to filter by KU and cislo_lv
-    if isinstance(lvs, list):
-        for item in lvs:
-            if item["katastralni_uzemi_id"] == ids["ku"] and item["cislo_lv"] == lv_cislo:
-                ids["lv"] = item["id"]
-                break
+    ids["lv"] = created_id(created)
     print(f"Created LV ID: {ids['lv']}")
 
 # 12. ParcelaRow
 print("\n--- Testing ParcelaRow ---")
 if ids.get("ku") and ids.get("lv") and ids.get("bpej"):
     parcela_cislo = random.randint(100, 999)
-    run_curl("POST", "/parcela_row", {
+    created = run_curl("POST", "/parcela_row", {
         "parcelni_cislo": parcela_cislo,
         "cast_parcely": 1,
         "je_stavebni": False,
@@ -172,16 +180,11 @@ if ids.get("ku") and ids.get("lv") and ids.get("bpej"):
         "bpej_id": ids["bpej"],
         "list_vlastnictvi_id": ids["lv"]
     })
-    parcely = run_curl("GET", "/parcela_row")
-    if isinstance(parcely, list):
-        for item in parcely:
-            if item["katastralni_uzemi_id"] == ids["ku"] and item["parcelni_cislo"] == parcela_cislo:
-                ids["parcela"] = item["id"]
-                break
+    ids["parcela"] = created_id(created)
     print(f"Created Parcela ID: {ids['parcela']}")
 
     # Create another parcela for relations
-    run_curl("POST", "/parcela_row", {
+    created = run_curl("POST", "/parcela_row", {
         "parcelni_cislo": parcela_cislo + 1,
         "cast_parcely": 1,
         "je_stavebni": False,
@@ -192,12 +195,7 @@ if ids.get("ku") and ids.get("lv") and ids.get("bpej"):
         "bpej_id": ids["bpej"],
         "list_vlastnictvi_id": ids["lv"]
     })
-    parcely = run_curl("GET", "/parcela_row")
-    if isinstance(parcely, list):
-        for item in parcely:
-            if item["katastralni_uzemi_id"] == ids["ku"] and item["parcelni_cislo"] == parcela_cislo + 1:
-                ids["parcela2"] = item["id"]
-                break
+    ids["parcela2"] = created_id(created)
     print(f"Created Parcela2 ID: {ids['parcela2']}")
 
 
@@ -205,19 +203,14 @@ if ids.get("ku") and ids.get("lv") and ids.get("bpej"):
 print("\n--- Testing Rizeni ---")
 if ids.get("typ_rizeni"):
     rizeni_cislo = random.randint(2025000, 2025999)
-    run_curl("POST", "/rizeni", {
+    created = run_curl("POST", "/rizeni", {
         "rok": 2025,
         "cislo_rizeni": rizeni_cislo,
         "typ_rizeni_id": ids["typ_rizeni"],
         "predmet": "Vklad",
         "poznamka": None
     })
-    rizenis = run_curl("GET", "/rizeni")
-    if isinstance(rizenis, list):
-        for item in rizenis:
-            if item["cislo_rizeni"] == rizeni_cislo:
-                ids["rizeni"] = item["id"]
-                break
+    ids["rizeni"] = created_id(created)
     print(f"Created Rizeni ID: {ids['rizeni']}")
 
 # 14. Vlastnictvi
@@ -229,13 +222,8 @@ if ids.get("parcela") and ids.get("majitel"):
         "podil_setin": 100
     })
     # Verify
-    vlastnictvis = run_curl("GET", "/vlastnictvi")
-    found = False
-    if isinstance(vlastnictvis, list):
-        for item in vlastnictvis:
-            if item["parcela_id"] == ids["parcela"] and item["majitel_id"] == ids["majitel"]:
-                found = True
-                break
+    vlastnictvi = run_curl("GET", f"/vlastnictvi/{ids['parcela']}/{ids['majitel']}")
+    found = isinstance(vlastnictvi, dict)
     print(f"Vlastnictvi created: {found}")
 
 # 15. BremenoParcelaParcela